@@ -0,0 +1,1882 @@
+//! Async variant of the 'Appartement' and 'RawApi', based on tokio.
+//!
+//! This module is only available with the 'async' feature enabled.
+//! It provides the same functionality as the blocking interface, but
+//! all requests are non blocking and the events are delivered as a
+//! 'Stream' instead of a channel.
+
+use crate::{
+    apply_climate, apply_event, authentication_error, build_consumption, build_zones,
+    cache_failure, call_handler, climate_groups, expand_event, fingerprint_file, has_climate,
+    is_idempotent, light_groups, load_zones, parse_last_called_scenes, parse_scene_modes,
+    plan_value, rejected_device_error, requested_device, save_zones, session_expired, set_status,
+    shadow_status, subscription_lost, value_source, zone_outputs_query, Action, Circut,
+    Consumption, Credentials, Device, DeviceOutputs, Endpoint, Error, Event, EventFilter, Health,
+    HeatingMode, MeterSample, MeterValue, MeteringType, MeteringUnit, PropertyNode, Result, Scene,
+    SceneMode, SensorValue, Session, Status, Step, SubscriberState, TemperatureControlConfig,
+    TemperatureControlStatus, TemperatureControlValues, Tls, Type, Value, ValueSource, Zone,
+    ZoneOutputs, ZoneStructure, DEFAULT_EVENT_NAMES, EVENT_BUFFER, LAST_CALLED_SCENES_QUERY,
+    SCENE_MODES_QUERY,
+};
+use futures::StreamExt;
+
+/// The async version of the 'dss::Appartement'.
+///
+/// The same rules as for the blocking version apply, please only use this
+/// interface to a dss installation and not any other at the same time.
+#[derive(Debug, Clone)]
+pub struct Appartement {
+    inner: std::sync::Arc<tokio::sync::Mutex<InnerAppartement>>,
+    health: std::sync::Arc<Health>,
+    events: std::sync::Arc<std::sync::Mutex<EventHub>>,
+}
+
+impl Appartement {
+    /// Connect to a DSS installation and fetch the complete structure of it.
+    /// The structure is fetched in bulk, so this takes only a few seconds.
+    ///
+    /// The certificate is verified against the root certificates, use
+    /// 'connect_file()' or 'from_api()' for the self signed one of the dss.
+    pub async fn connect<S>(host: S, user: S, password: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        Appartement::new(RawApi::connect(host, user, password).await?, None).await
+    }
+
+    /// Connect to a DSS installation and load structure from file
+    pub async fn connect_file<S>(host: S, user: S, password: S, file: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        let file = file.into();
+        let api = crate::RawApi::builder(host)
+            .user(user, password)
+            .tls(Tls::TrustOnFirstUse(fingerprint_file(&file)))
+            .connect_async()
+            .await?;
+
+        Appartement::new(api, Some(file)).await
+    }
+
+    /// Connect to a DSS installation with an application token and fetch
+    /// the complete structure of it.
+    pub async fn connect_token<S>(host: S, application_token: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        Appartement::new(RawApi::connect_token(host, application_token).await?, None).await
+    }
+
+    /// Connect to a DSS installation with an application token and load
+    /// the structure from file.
+    pub async fn connect_token_file<S>(
+        host: S,
+        application_token: S,
+        file: S,
+    ) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        let file = file.into();
+        let api = crate::RawApi::builder(host)
+            .application_token(application_token)
+            .tls(Tls::TrustOnFirstUse(fingerprint_file(&file)))
+            .connect_async()
+            .await?;
+
+        Appartement::new(api, Some(file)).await
+    }
+
+    /// Create the appartment from an already connected api, like one
+    /// created with 'dss::RawApi::builder().connect_async()'.
+    pub async fn from_api(api: RawApi) -> Result<Appartement> {
+        Appartement::new(api, None).await
+    }
+
+    /// Create the appartment from an already connected api and load
+    /// the structure from file.
+    pub async fn from_api_file<S>(api: RawApi, file: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        Appartement::new(api, Some(file.into())).await
+    }
+
+    async fn new(api: RawApi, file: Option<String>) -> Result<Appartement> {
+        // create the Appartment with the inner values
+        let appt = Appartement {
+            health: api.endpoint.health.clone(),
+            inner: std::sync::Arc::new(tokio::sync::Mutex::new(InnerAppartement {
+                api,
+                zones: load_zones(&file)?,
+                file,
+                cache_failed: false,
+            })),
+            events: std::sync::Arc::default(),
+        };
+
+        // update the complete structure if no zones where loaded
+        {
+            let mut a = appt.inner.lock().await;
+            if a.zones.is_empty() {
+                a.update_structure().await?;
+            }
+        }
+
+        Ok(appt)
+    }
+
+    /// Returns an vector of all zones with their groups.
+    ///
+    /// Keep in mind, that the values are in a frozen state.
+    /// If you want to stay informed about changes, use the
+    /// 'event_stream()' function.
+    pub async fn get_zones(&self) -> Vec<Zone> {
+        self.inner.lock().await.zones.clone()
+    }
+
+    /// Updates the complete appartment structure, this command can take some time
+    /// to execute (a few seconds).
+    pub async fn update_all(&self) -> Result<Vec<Zone>> {
+        let mut inner = self.inner.lock().await;
+        inner.update_structure().await?;
+        Ok(inner.zones.clone())
+    }
+
+    pub async fn get_value(&self, zone: usize, group: usize) -> Result<Value> {
+        self.inner.lock().await.get_value(zone, group)
+    }
+
+    pub async fn set_value(&self, zone: usize, group: Option<usize>, value: Value) -> Result<()> {
+        self.inner.lock().await.set_value(zone, group, value).await
+    }
+
+    /// Increase the brightness of all lights within the zone by one step.
+    pub async fn increase_light(&self, zone: usize) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .step_light(zone, Action::AllLightIncrement)
+            .await
+    }
+
+    /// Decrease the brightness of all lights within the zone by one step.
+    pub async fn decrease_light(&self, zone: usize) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .step_light(zone, Action::AllLightDecrement)
+            .await
+    }
+
+    /// Get the temperature control status of a zone, like the actual
+    /// operation mode and temperatures.
+    pub async fn get_temperature_control(&self, zone: usize) -> Result<TemperatureControlStatus> {
+        self.inner
+            .lock()
+            .await
+            .api
+            .get_temperature_control_status(zone)
+            .await
+    }
+
+    /// Call a scene of a type within the zone, like a pressed switch would do.
+    ///
+    /// The apartment wide scenes, like 'Scene::Absent', are called for the zone 0.
+    pub async fn call_scene(&self, zone: usize, typ: Type, scene: Scene) -> Result<()> {
+        if scene == Scene::Unknown {
+            return Err(Error::InvalidInput(
+                "The unknown scene can't be called".into(),
+            ));
+        }
+
+        let zone = if scene.is_apartment() { 0 } else { zone };
+        self.inner
+            .lock()
+            .await
+            .api
+            .call_scene(zone, typ, scene as usize)
+            .await
+    }
+
+    /// Call a scene of a type within the zone by it's number.
+    ///
+    /// The known scenes are called like with 'call_scene', the other numbers
+    /// are sent to the dss as they are.
+    pub async fn call_scene_by_number(&self, zone: usize, typ: Type, scene: usize) -> Result<()> {
+        match Scene::from(scene.min(255) as u8) {
+            Scene::Unknown => {
+                self.inner
+                    .lock()
+                    .await
+                    .api
+                    .call_scene(zone, typ, scene)
+                    .await
+            }
+            known => self.call_scene(zone, typ, known).await,
+        }
+    }
+
+    /// Call a scene of a type within the zone by it's name.
+    ///
+    /// The name is either the name the user has given the scene, like 'Reading'
+    /// or the name of the preset, like 'Preset 2'.
+    pub async fn call_scene_by_name(&self, zone: usize, typ: Type, name: &str) -> Result<()> {
+        let scene = self.find_scene(zone, typ.clone(), name).await?;
+        self.call_scene(zone, typ, scene).await
+    }
+
+    /// Find the scene of a type within the zone by it's name.
+    ///
+    /// The user given names are preferred over the names of the presets.
+    pub async fn find_scene(&self, zone: usize, typ: Type, name: &str) -> Result<Scene> {
+        let api = self.inner.lock().await.api.clone();
+
+        for scene in api.get_scenes(zone, typ.clone()).await? {
+            let scene_name = api.get_scene_name(zone, typ.clone(), scene).await?;
+            if !scene_name.is_empty() && scene_name.eq_ignore_ascii_case(name.trim()) {
+                return Ok(Scene::from(scene.min(255) as u8));
+            }
+        }
+
+        name.parse()
+    }
+
+    /// Get the name the user has given a scene of a type within the zone.
+    ///
+    /// An empty name is returned, when the scene has no name.
+    pub async fn get_scene_name(&self, zone: usize, typ: Type, scene: Scene) -> Result<String> {
+        self.inner
+            .lock()
+            .await
+            .api
+            .get_scene_name(zone, typ, scene as usize)
+            .await
+    }
+
+    /// Give a scene of a type within the zone a name.
+    pub async fn set_scene_name(
+        &self,
+        zone: usize,
+        typ: Type,
+        scene: Scene,
+        name: &str,
+    ) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .api
+            .set_scene_name(zone, typ, scene as usize, name)
+            .await
+    }
+
+    /// Get the actual power consumption and energy meter values
+    /// for every circuit and the whole appartment.
+    pub async fn consumption(&self) -> Result<Consumption> {
+        let api = self.inner.lock().await.api.clone();
+
+        // get the latest values for all meters at once
+        let power = api
+            .get_latest_meter_values(MeteringType::Consumption, MeteringUnit::W)
+            .await?;
+        let energy = api
+            .get_latest_meter_values(MeteringType::Energy, MeteringUnit::Wh)
+            .await?;
+
+        Ok(build_consumption(
+            api.get_circuits().await?,
+            &power,
+            &energy,
+        ))
+    }
+
+    /// The actual state of the connection to the dss, with the time of
+    /// the last successful request and the last received event.
+    pub fn status(&self) -> Status {
+        self.health.status()
+    }
+
+    /// Get a stream, which receives the status when the state of the connection
+    /// changes, starting with the actual one.
+    pub fn status_stream(&self) -> impl futures::Stream<Item = Status> {
+        status_stream(&self.health)
+    }
+
+    /// Get a stream of all events happening in the appartment.
+    ///
+    /// Every stream has it's own buffer of 'EVENT_BUFFER' events, all of
+    /// them are served by a single subscription at the dss. The subscription
+    /// is closed, when the last stream gets dropped.
+    ///
+    /// The failures are delivered as 'Error::Event' or 'Error::EventApply',
+    /// the stream only ends after an error when the subscription can't be restored.
+    pub async fn event_stream(&self) -> Result<EventStream> {
+        self.event_stream_filtered(EventFilter::all(), EVENT_BUFFER)
+            .await
+    }
+
+    /// Get a stream of the events with the given names.
+    pub async fn event_stream_with(&self, names: &[&str]) -> Result<EventStream> {
+        let filter = names
+            .iter()
+            .fold(EventFilter::all(), |filter, name| filter.name(*name));
+        self.event_stream_filtered(filter, EVENT_BUFFER).await
+    }
+
+    /// Get a stream of the events passing the filter, which buffers up
+    /// to 'capacity' events.
+    ///
+    /// When the buffer is full, the following events are dropped for this
+    /// stream, so a slow stream doesn't hold back the others. They are
+    /// counted by 'EventStream::dropped()'. A stream which needs other event
+    /// names than the running subscription, replaces it with a new one,
+    /// the events in between are missed by the other streams.
+    pub async fn event_stream_filtered(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+    ) -> Result<EventStream> {
+        let (send, recv) = tokio::sync::mpsc::channel(capacity);
+        let state = std::sync::Arc::default();
+        let id = self.subscribe(filter, send, &state).await?;
+
+        Ok(EventStream {
+            recv,
+            id,
+            state,
+            hub: self.events.clone(),
+        })
+    }
+
+    /// Add a stream to the hub and return it's id.
+    async fn subscribe(
+        &self,
+        filter: EventFilter,
+        send: tokio::sync::mpsc::Sender<Result<Event>>,
+        state: &std::sync::Arc<SubscriberState>,
+    ) -> Result<usize> {
+        // the lock of the appartment keeps the subscriptions in order
+        let inner = self.inner.lock().await;
+
+        // the subscription at the dss needs to include the names of the filter
+        let (mut names, running) = {
+            let hub = self.events.lock()?;
+            (hub.names.clone(), hub.stop.is_some())
+        };
+        let wanted: Vec<&str> = if filter.names.is_empty() {
+            DEFAULT_EVENT_NAMES.to_vec()
+        } else {
+            filter.names.iter().map(|n| n.as_str()).collect()
+        };
+        let before = names.len();
+        for name in wanted {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+
+        if !running || names.len() != before {
+            let names_ref: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+            let events = inner.api.event_stream_with(&names_ref).await?;
+            self.start_events(events, names)?;
+        }
+
+        let mut hub = self.events.lock()?;
+        hub.next_id += 1;
+        let id = hub.next_id;
+        hub.subscribers.push(Subscriber {
+            id,
+            filter,
+            send,
+            state: state.clone(),
+        });
+
+        Ok(id)
+    }
+
+    /// Start the task, which distributes the events of the subscription
+    /// to the streams of the hub.
+    ///
+    /// An already running subscription is replaced by the new one.
+    fn start_events<S>(&self, events: S, names: Vec<String>) -> Result<()>
+    where
+        S: futures::Stream<Item = Result<Event>> + Send + 'static,
+    {
+        let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+        {
+            // the old task ends, when it's stop sender gets dropped
+            let mut hub = self.events.lock()?;
+            hub.stop = Some(stop);
+            hub.names = names;
+        }
+
+        let appr = self.inner.clone();
+        let hub = self.events.clone();
+
+        tokio::spawn(async move {
+            let mut events = Box::pin(events);
+
+            // the last failure of the subscription, it's kept when the streams get closed
+            let mut failure = None;
+
+            loop {
+                // listen for events or the subscription to be stopped
+                let res = tokio::select! {
+                    _ = &mut stopped => return,
+                    res = events.next() => match res {
+                        Some(res) => res,
+                        None => break,
+                    },
+                };
+
+                // update the appartment structure with the event
+                let (results, failed) = match res {
+                    Ok(event) => (appr.lock().await.handle_event(event).await, false),
+                    Err(e) => (vec![Err(e)], true),
+                };
+
+                // send the results to every stream, a full buffer drops them
+                let mut hub = hub.lock().unwrap_or_else(|e| e.into_inner());
+                for res in results {
+                    match res {
+                        Ok(event) => hub
+                            .subscribers
+                            .retain(|s| !s.filter.matches(&event) || s.send(Ok(event.clone()))),
+                        Err(e) => {
+                            let e = std::sync::Arc::new(e);
+                            let wrap = |e| {
+                                if failed {
+                                    Error::Event(e)
+                                } else {
+                                    Error::EventApply(e)
+                                }
+                            };
+                            hub.subscribers.retain(|s| s.send(Err(wrap(e.clone()))));
+                            if failed {
+                                failure = Some(e);
+                            }
+                        }
+                    }
+                }
+
+                if hub.subscribers.is_empty() {
+                    hub.stop();
+                    return;
+                }
+            }
+
+            // the subscription failed and the error was delivered, close the streams
+            let mut hub = hub.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(tokio::sync::oneshot::error::TryRecvError::Empty) = stopped.try_recv() {
+                hub.close(failure);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Register a handler, which is called for every event passing the filter.
+    ///
+    /// The handler is called by an own task, the failures are only received
+    /// by the event streams. When the subscription can't be restored, the task
+    /// ends and the failure is kept by 'Subscription::error()'. Otherwise the
+    /// handler is unregistered, when the returned subscription gets dropped.
+    pub async fn on<F>(&self, filter: EventFilter, mut handler: F) -> Result<Subscription>
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        let mut events = self.event_stream_filtered(filter, EVENT_BUFFER).await?;
+        let state = events.state.clone();
+
+        let task = tokio::spawn(async move {
+            while let Some(res) = events.next().await {
+                // a panicking handler keeps the task running
+                if let Ok(event) = res {
+                    call_handler(&mut handler, &event, &events.state);
+                }
+            }
+        });
+
+        Ok(Subscription { task, state })
+    }
+}
+
+/// A handler registered with 'Appartement::on()', which is unregistered
+/// when the subscription gets dropped.
+#[derive(Debug)]
+pub struct Subscription {
+    task: tokio::task::JoinHandle<()>,
+    state: std::sync::Arc<SubscriberState>,
+}
+
+impl Subscription {
+    /// The number of events, which were dropped so far because the
+    /// handler didn't keep up with them.
+    pub fn dropped(&self) -> usize {
+        self.state.dropped()
+    }
+
+    /// The number of events, for which the handler panicked. The panic
+    /// is printed by the panic hook and the handler keeps being called.
+    pub fn panics(&self) -> usize {
+        self.state.panics()
+    }
+
+    /// The failure, which closed the subscription and ended the handler.
+    ///
+    /// Once it's set, the handler isn't called anymore.
+    pub fn error(&self) -> Option<Error> {
+        self.state.error()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A stream of the appartment events, which unsubscribes when it gets dropped.
+///
+/// Failures of the subscription are received as 'Error::Event', the ones while
+/// updating the appartment as 'Error::EventApply', the stream keeps receiving
+/// afterwards. Only when the subscription can't be restored, the stream ends
+/// after the error.
+#[derive(Debug)]
+pub struct EventStream {
+    recv: tokio::sync::mpsc::Receiver<Result<Event>>,
+    id: usize,
+    state: std::sync::Arc<SubscriberState>,
+    hub: std::sync::Arc<std::sync::Mutex<EventHub>>,
+}
+
+impl EventStream {
+    /// The number of events and failures, which were dropped so far
+    /// because the buffer of the stream was full.
+    pub fn dropped(&self) -> usize {
+        self.state.dropped()
+    }
+
+    /// The failure, which ended the stream, like a failed login.
+    ///
+    /// It's kept even when it didn't fit into the buffer of the stream anymore.
+    pub fn error(&self) -> Option<Error> {
+        self.state.error()
+    }
+}
+
+impl futures::Stream for EventStream {
+    type Item = Result<Event>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.recv.poll_recv(cx)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        let mut hub = match self.hub.lock() {
+            Ok(hub) => hub,
+            Err(e) => e.into_inner(),
+        };
+        hub.remove(self.id);
+    }
+}
+
+/// The event streams of the appartment, which are all served by a
+/// single subscription at the dss.
+#[derive(Debug, Default)]
+struct EventHub {
+    subscribers: Vec<Subscriber>,
+    next_id: usize,
+    /// The event names subscribed at the dss
+    names: Vec<String>,
+    /// Ends the task of the running subscription, when it gets dropped
+    stop: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl EventHub {
+    /// End the subscription at the dss.
+    fn stop(&mut self) {
+        self.stop = None;
+        self.names.clear();
+    }
+
+    /// Remove the stream with the id.
+    fn remove(&mut self, id: usize) {
+        self.subscribers.retain(|s| s.id != id);
+
+        if self.subscribers.is_empty() {
+            self.stop();
+        }
+    }
+
+    /// Close all streams, they keep the failure.
+    fn close(&mut self, error: Option<std::sync::Arc<Error>>) {
+        for subscriber in self.subscribers.drain(..) {
+            subscriber.state.close(error.clone());
+        }
+        self.stop();
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    id: usize,
+    filter: EventFilter,
+    send: tokio::sync::mpsc::Sender<Result<Event>>,
+    /// The state shared with the receiver
+    state: std::sync::Arc<SubscriberState>,
+}
+
+impl Subscriber {
+    /// Send the result to the stream, returns false when the stream is gone.
+    ///
+    /// When the buffer is full, the result is dropped and counted.
+    fn send(&self, res: Result<Event>) -> bool {
+        match self.send.try_send(res) {
+            Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                self.state.dropped_one();
+                true
+            }
+            res => res.is_ok(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InnerAppartement {
+    api: RawApi,
+    zones: Vec<Zone>,
+    file: Option<String>,
+    /// The last write of the structure file failed
+    cache_failed: bool,
+}
+
+impl InnerAppartement {
+    /// Update the appartment with the event and return the events for the
+    /// stream, the failures are returned between them.
+    async fn handle_event(&mut self, event: Event) -> Vec<Result<Event>> {
+        let mut results = vec![];
+
+        // the values could have changed while the subscription was lost
+        if let Event::Reconnected = event {
+            if let Err(e) = self.update_structure().await {
+                results.push(Err(e));
+            }
+        }
+
+        // expand the events when necessary
+        let expanded = match expand_event(&self.zones, event) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                results.push(Err(e));
+                return results;
+            }
+        };
+
+        for event in expanded {
+            // update the event value for shadow etc.
+            match self.update_event_value(event).await {
+                Ok(event) => {
+                    apply_event(&mut self.zones, &event);
+                    results.push(Ok(event));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        let saved = save_zones(&self.file, &self.zones);
+        if let Some(e) = cache_failure(&mut self.cache_failed, saved) {
+            results.push(Err(e));
+        }
+
+        results
+    }
+
+    fn get_value(&self, zone: usize, group: usize) -> Result<Value> {
+        self.zones
+            .iter()
+            .find(|z| z.id == zone)
+            .ok_or(Error::UnknownZone(zone))?
+            .groups
+            .iter()
+            .find(|g| g.id == group)
+            .map(|g| g.status.clone())
+            .ok_or(Error::UnknownGroup { zone, group })
+    }
+
+    async fn set_value(&mut self, zone: usize, group: Option<usize>, value: Value) -> Result<()> {
+        let plan = plan_value(&self.zones, zone, group, &value)?;
+
+        for step in plan.steps {
+            match step {
+                Step::Action(action) => self.api.call_action(zone, action).await?,
+                Step::ZoneLight(light) => self.api.set_zone_value(zone, Type::Light, light).await?,
+                Step::Light(device, light) => {
+                    self.api.set_light_device_value(device, light).await?
+                }
+                Step::Shadow(device, open, angle) => {
+                    self.api
+                        .set_shadow_device_open(device.clone(), open)
+                        .await?;
+                    self.api.set_shadow_device_angle(device, angle).await?;
+                }
+                // the temperature is set for the actual operation mode
+                Step::Temperature(temperature) => {
+                    let status = self.api.get_temperature_control_status(zone).await?;
+                    self.api
+                        .set_temperature_control_values(
+                            zone,
+                            &[(status.operation_mode, temperature)],
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        // we need to update the state manually, because no event will be triggered
+        if let Some(typ) = plan.status {
+            set_status(&mut self.zones, zone, group, typ, &value)?;
+        }
+        if plan.climate {
+            self.update_climate(zone).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn step_light(&mut self, zone: usize, action: Action) -> Result<()> {
+        self.api.call_action(zone, action).await?;
+
+        // the brightness after the step is unknown, so we read it back for every light group
+        for grp in light_groups(&self.zones, zone)? {
+            if let Ok(value) = self
+                .update_value(Value::Unknown, &Type::Light, zone, grp)
+                .await
+            {
+                set_status(&mut self.zones, zone, Some(grp), Type::Light, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_climate(&mut self, zone: usize) -> Result<()> {
+        // read back the new operation mode and nominal temperature of the zone
+        let status = self.api.get_temperature_control_status(zone).await?;
+        apply_climate(&mut self.zones, zone, &status)
+    }
+
+    async fn update_event_value(&mut self, event: Event) -> Result<Event> {
+        // only scene calls need an update of the value
+        let mut event = match event {
+            Event::CallScene(e) => e,
+            e => return Ok(e),
+        };
+
+        // update the value
+        event.value = self
+            .update_value(event.value, &event.typ, event.zone, event.group)
+            .await?;
+        Ok(Event::CallScene(event))
+    }
+
+    async fn update_value(
+        &self,
+        value: Value,
+        typ: &Type,
+        zone: usize,
+        group: usize,
+    ) -> Result<Value> {
+        match value_source(&self.zones, value, typ, zone, group)? {
+            ValueSource::Known(value) => Ok(value),
+            ValueSource::Climate => Ok(self
+                .api
+                .get_temperature_control_status(zone)
+                .await?
+                .nominal
+                .map(Value::Temperature)
+                .unwrap_or_default()),
+            ValueSource::Light(device) => Ok(Value::Light(
+                self.api.get_light_device_value(&device).await?,
+            )),
+            ValueSource::Shadow(device) => Ok(Value::Shadow(
+                self.api.get_shadow_device_open(&device).await?,
+                self.api.get_shadow_device_angle(&device).await?,
+            )),
+        }
+    }
+
+    async fn update_structure(&mut self) -> Result<()> {
+        // get the whole structure at once, instead of asking every single device
+        let structure = self.api.get_structure().await?;
+
+        // without them the status is unknown and the devices are only sorted into group 0
+        let last_called = self.api.get_last_called_scenes().await.unwrap_or_default();
+        let scene_modes = self.api.get_scene_modes().await.unwrap_or_default();
+
+        let mut zones = build_zones(structure, &last_called, &scene_modes);
+
+        for zone in &mut zones {
+            // get the latest sensor values, a zone without sensors or their support has none
+            zone.sensors = self
+                .api
+                .get_zone_sensor_values(zone.id)
+                .await
+                .unwrap_or_default();
+
+            // add the temperature control group, when the zone climate is controlled,
+            // a zone which rejects the status gets none
+            if has_climate(zone) {
+                if let Ok(status) = self.api.get_temperature_control_status(zone.id).await {
+                    zone.groups.extend(climate_groups(zone.id, &status));
+                }
+            }
+        }
+
+        self.zones = zones.clone();
+
+        for zone in &mut zones {
+            // get the output values of all devices within the zone at once
+            let outputs = if zone.types.contains(&Type::Shadow) {
+                self.api
+                    .get_zone_output_values(zone.id)
+                    .await
+                    .unwrap_or_default()
+            } else {
+                vec![]
+            };
+
+            // for every shadow group get the real shadow values, when not delivered in bulk
+            for group in zone.groups.iter_mut().filter(|g| g.typ == Type::Shadow) {
+                let status = match shadow_status(&outputs, group) {
+                    Some(v) => Ok(v),
+                    None => {
+                        self.update_value(group.status.clone(), &group.typ, zone.id, group.id)
+                            .await
+                    }
+                };
+                if let Ok(v) = status {
+                    group.status = v;
+                }
+            }
+        }
+
+        self.zones = zones;
+        save_zones(&self.file, &self.zones)
+    }
+}
+
+/// The async version of the 'dss::RawApi'.
+#[derive(Debug, Clone)]
+pub struct RawApi {
+    endpoint: Endpoint,
+    credentials: Credentials,
+    session: std::sync::Arc<Session>,
+}
+
+impl RawApi {
+    /// Connect to the Digital Strom Server and try to login.
+    ///
+    /// The certificate of the dss is verified against the root certificates,
+    /// use 'RawApiBuilder::connect_async()' to pin the self signed one of the dss.
+    pub async fn connect<S>(host: S, user: S, password: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        RawApi::new(
+            Endpoint::new(&host.into(), &Tls::default())?,
+            Credentials::Password {
+                user: user.into(),
+                password: password.into(),
+            },
+        )
+        .await
+    }
+
+    /// Connect to the Digital Strom Server and login with an application token.
+    pub async fn connect_token<S>(host: S, application_token: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        RawApi::new(
+            Endpoint::new(&host.into(), &Tls::default())?,
+            Credentials::ApplicationToken(application_token.into()),
+        )
+        .await
+    }
+
+    pub(crate) async fn new(endpoint: Endpoint, credentials: Credentials) -> Result<Self> {
+        let api = RawApi {
+            endpoint,
+            credentials,
+            session: std::sync::Arc::default(),
+        };
+
+        api.login().await?;
+
+        Ok(api)
+    }
+
+    fn get_token(&self) -> Result<String> {
+        Ok(self.session.token.read()?.clone())
+    }
+
+    fn set_token(&self, token: &str) -> Result<()> {
+        let mut t = self.session.token.write()?;
+        *t = token.into();
+        Ok(())
+    }
+
+    async fn login(&self) -> Result<()> {
+        // login with the password or the application token
+        let (request, parameter) = match &self.credentials {
+            Credentials::Password { user, password } => (
+                "system/login",
+                vec![("user", user.as_str()), ("password", password.as_str())],
+            ),
+            Credentials::ApplicationToken(token) => (
+                "system/loginApplication",
+                vec![("loginToken", token.as_str())],
+            ),
+        };
+
+        let json = self
+            .endpoint
+            .retry_async(true, || self.request(request, &parameter))
+            .await
+            .map_err(authentication_error)
+            .inspect_err(|e| self.endpoint.health.failed(e))?;
+
+        // extract the token
+        self.set_token(
+            json.get("token")
+                .ok_or_else(|| Error::InvalidResponse("No token in Json response".into()))?
+                .as_str()
+                .ok_or_else(|| Error::InvalidResponse("Token is not a String".into()))?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Make a single request and check the response.
+    async fn request(
+        &self,
+        request: &str,
+        parameter: &[(&str, &str)],
+    ) -> Result<serde_json::Value> {
+        // make the request
+        let response = self
+            .endpoint
+            .async_client()?
+            .get(self.endpoint.url(request))
+            .query(parameter)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        crate::RawApi::unpack_response(response.json().await?)
+    }
+
+    /// Login again, when the expired token is still the actual one.
+    async fn renew_session(&self, expired: &str) -> Result<()> {
+        let _lock = self.session.login_async.lock().await;
+
+        if self.get_token()? != expired {
+            return Ok(());
+        }
+
+        self.login().await
+    }
+
+    /// Generic requset function, which handles the token inserting/login,
+    /// the json parsing and success check.
+    ///
+    /// Transient failures of reading requests are retried with the backoff of
+    /// the connection, requests which change something only when the dss
+    /// couldn't be reached. When the session expired, the api logs in again and sends the request
+    /// again, up to 'retries' times.
+    ///
+    /// It returns a json value, dependet on the request.
+    pub async fn generic_request<S>(
+        &self,
+        request: S,
+        parameter: Option<Vec<(&str, &str)>>,
+        retries: usize,
+    ) -> Result<serde_json::Value>
+    where
+        S: Into<String>,
+    {
+        let request = request.into();
+        let parameter = parameter.unwrap_or_default();
+        let mut retries = retries;
+
+        loop {
+            // Handle parameter and add token
+            let token = self.get_token()?;
+            let mut para = parameter.clone();
+            para.push(("token", &token));
+
+            match self
+                .endpoint
+                .retry_async(is_idempotent(&request), || self.request(&request, &para))
+                .await
+            {
+                // login again and replay the request
+                Err(e) if session_expired(&e) && retries > 0 => {
+                    retries -= 1;
+                    self.renew_session(&token).await?;
+                }
+                Err(Error::Rejected(msg)) => {
+                    // the dss rejects the requests for unknown devices as well
+                    let device = match requested_device(&request, &parameter) {
+                        Some(device) => device,
+                        None => return Err(Error::Rejected(msg)),
+                    };
+                    return Err(rejected_device_error(msg, device));
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// The actual state of the connection to the dss, with the time of
+    /// the last successful request and the last received event.
+    pub fn status(&self) -> Status {
+        self.endpoint.health.status()
+    }
+
+    /// Get a stream, which receives the status when the state of the connection
+    /// changes, starting with the actual one.
+    pub fn status_stream(&self) -> impl futures::Stream<Item = Status> {
+        status_stream(&self.endpoint.health)
+    }
+
+    /// Create a new event stream, which is listening to events from the dss station.
+    ///
+    /// The subscription is closed, when the stream gets dropped. A failed request
+    /// is delivered as error, followed by 'Event::Reconnected' once the subscription
+    /// is restored. When the login fails, the stream ends after the error.
+    pub async fn event_stream(&self) -> Result<impl futures::Stream<Item = Result<Event>>> {
+        self.event_stream_with(DEFAULT_EVENT_NAMES).await
+    }
+
+    /// Create a new event stream, which is listening to the events
+    /// with the given names.
+    pub async fn event_stream_with(
+        &self,
+        names: &[&str],
+    ) -> Result<impl futures::Stream<Item = Result<Event>>> {
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+
+        // subscribe to the events
+        let id = crate::subscription_id();
+        self.subscribe(&id, &names).await?;
+
+        // create a channel to send data to the stream
+        let (send, recv) = tokio::sync::mpsc::channel(64);
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            // the consecutive failures, to wait longer the longer the dss is unavailable
+            let mut failures = 0;
+
+            'receive: loop {
+                // listen for events at the server or the stream to be dropped
+                let res = tokio::select! {
+                    _ = send.closed() => break,
+                    res = this.generic_request(
+                        "event/get",
+                        Some(vec![("timeout", "3000"), ("subscriptionID", &id)]),
+                        2,
+                    ) => res,
+                };
+
+                let events = match res.and_then(|mut v| crate::RawApi::extract_events(&mut v)) {
+                    Ok(events) => {
+                        failures = 0;
+                        if !events.is_empty() {
+                            this.endpoint.health.event_received();
+                        }
+                        events
+                    }
+                    Err(e) => {
+                        // without a login the subscription can't be restored
+                        let failed = matches!(e, Error::Authentication(_));
+                        let lost = subscription_lost(&e);
+                        if send.send(Err(e)).await.is_err() || failed {
+                            break 'receive;
+                        }
+                        this.endpoint.health.retrying();
+
+                        // the subscription is still there, try again after a while
+                        if !lost {
+                            tokio::select! {
+                                _ = send.closed() => break 'receive,
+                                _ = tokio::time::sleep(this.endpoint.backoff.delay(failures)) => {}
+                            }
+                            failures += 1;
+                            continue;
+                        }
+
+                        // subscribe again with backoff, when the subscription or session got lost
+                        let mut attempt = 0;
+                        loop {
+                            match this.subscribe(&id, &names).await {
+                                Ok(()) => break,
+                                Err(e @ Error::Authentication(_)) => {
+                                    let _ = send.send(Err(e)).await;
+                                    break 'receive;
+                                }
+                                Err(_) => {}
+                            }
+
+                            tokio::select! {
+                                _ = send.closed() => break 'receive,
+                                _ = tokio::time::sleep(this.endpoint.backoff.delay(attempt)) => {}
+                            }
+                            attempt += 1;
+                        }
+                        vec![Event::Reconnected]
+                    }
+                };
+
+                for e in events {
+                    if send.send(Ok(e)).await.is_err() {
+                        break 'receive;
+                    }
+                }
+            }
+
+            // nobody is listening anymore, close the subscription
+            for name in &names {
+                let _ = this
+                    .generic_request(
+                        "event/unsubscribe",
+                        Some(vec![("name", name), ("subscriptionID", &id)]),
+                        0,
+                    )
+                    .await;
+            }
+        });
+
+        Ok(receiver_stream(recv))
+    }
+
+    /// Subscribe to the events with the given names.
+    async fn subscribe(&self, id: &str, names: &[String]) -> Result<()> {
+        for name in names {
+            self.generic_request(
+                "event/subscribe",
+                Some(vec![("name", name), ("subscriptionID", id)]),
+                2,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Request all zones from the DSS system.
+    pub async fn get_zones(&self) -> Result<Vec<Zone>> {
+        let mut json = self
+            .generic_request("apartment/getReachableGroups", None, 2)
+            .await?;
+
+        // unpack the zones
+        let json = json
+            .get_mut("zones")
+            .ok_or_else(|| Error::InvalidResponse("No zones in Json response".into()))?
+            .take();
+
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Receive all devices availble in the appartement.
+    pub async fn get_devices(&self) -> Result<Vec<Device>> {
+        let res = self
+            .generic_request("apartment/getDevices", None, 2)
+            .await?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Receive the complete structure of the appartement in a single request.
+    pub async fn get_structure(&self) -> Result<Vec<ZoneStructure>> {
+        let res = self
+            .generic_request("apartment/getStructure", None, 2)
+            .await?;
+
+        ZoneStructure::from_structure(res)
+    }
+
+    /// Query values from the property tree of the dss.
+    pub async fn property_query<S>(&self, query: S) -> Result<serde_json::Value>
+    where
+        S: Into<String>,
+    {
+        self.generic_request("property/query", Some(vec![("query", &query.into())]), 2)
+            .await
+    }
+
+    /// Query values from the property tree and deserialize them.
+    pub async fn property_query_as<T, S>(&self, query: S) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        S: Into<String>,
+    {
+        Ok(serde_json::from_value(self.property_query(query).await?)?)
+    }
+
+    /// Get a string value from the property tree.
+    pub async fn get_property_string<S>(&self, path: S) -> Result<String>
+    where
+        S: Into<String>,
+    {
+        Ok(self
+            .get_property("property/getString", path.into())
+            .await?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a String".into()))?
+            .to_string())
+    }
+
+    /// Get an integer value from the property tree.
+    pub async fn get_property_integer<S>(&self, path: S) -> Result<i64>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getInteger", path.into())
+            .await?
+            .as_i64()
+            .ok_or_else(|| Error::InvalidResponse("Property is not an integer".into()))
+    }
+
+    /// Get a floating point value from the property tree.
+    pub async fn get_property_floating<S>(&self, path: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getFloating", path.into())
+            .await?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a floating point number".into()))
+    }
+
+    /// Get a boolean value from the property tree.
+    pub async fn get_property_boolean<S>(&self, path: S) -> Result<bool>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getBoolean", path.into())
+            .await?
+            .as_bool()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a boolean".into()))
+    }
+
+    /// Set a string value in the property tree.
+    pub async fn set_property_string<S>(&self, path: S, value: S) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setString", path.into(), value.into())
+            .await
+    }
+
+    /// Set an integer value in the property tree.
+    pub async fn set_property_integer<S>(&self, path: S, value: i64) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setInteger", path.into(), value.to_string())
+            .await
+    }
+
+    /// Set a floating point value in the property tree.
+    pub async fn set_property_floating<S>(&self, path: S, value: f64) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setFloating", path.into(), value.to_string())
+            .await
+    }
+
+    /// Set a boolean value in the property tree.
+    pub async fn set_property_boolean<S>(&self, path: S, value: bool) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setBoolean", path.into(), value.to_string())
+            .await
+    }
+
+    /// List the child nodes of a property tree node.
+    pub async fn get_property_children<S>(&self, path: S) -> Result<Vec<PropertyNode>>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "property/getChildren",
+                Some(vec![("path", &path.into())]),
+                2,
+            )
+            .await?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    async fn get_property(&self, request: &str, path: String) -> Result<serde_json::Value> {
+        let mut res = self
+            .generic_request(request, Some(vec![("path", &path)]), 2)
+            .await?;
+
+        Ok(res
+            .get_mut("value")
+            .ok_or_else(|| Error::InvalidResponse("No value in Json response".into()))?
+            .take())
+    }
+
+    async fn set_property(&self, request: &str, path: String, value: String) -> Result<()> {
+        self.generic_request(request, Some(vec![("path", &path), ("value", &value)]), 2)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the raw output values of all devices within a zone with a single request.
+    pub async fn get_zone_output_values(&self, zone: usize) -> Result<Vec<DeviceOutputs>> {
+        let res: ZoneOutputs = self.property_query_as(zone_outputs_query(zone)).await?;
+        Ok(res.devices)
+    }
+
+    /// Get the last called scene of every zone and type with a single request.
+    pub async fn get_last_called_scenes(&self) -> Result<Vec<(usize, Type, usize)>> {
+        let res = self.property_query(LAST_CALLED_SCENES_QUERY).await?;
+        parse_last_called_scenes(&res)
+    }
+
+    /// Get the scene modes of all devices with a single request.
+    pub async fn get_scene_modes(&self) -> Result<Vec<(String, SceneMode)>> {
+        let res = self.property_query(SCENE_MODES_QUERY).await?;
+        parse_scene_modes(&res)
+    }
+
+    /// Request the scene mode for a specific device.
+    pub async fn get_device_scene_mode<S>(&self, device: S, scene_id: usize) -> Result<SceneMode>
+    where
+        S: Into<String>,
+    {
+        let json = self
+            .generic_request(
+                "device/getSceneMode",
+                Some(vec![
+                    ("dsid", &device.into()),
+                    ("sceneID", &scene_id.to_string()),
+                ]),
+                2,
+            )
+            .await?;
+
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Get all available circuts
+    pub async fn get_circuits(&self) -> Result<Vec<Circut>> {
+        let mut res = self
+            .generic_request("apartment/getCircuits", None, 2)
+            .await?;
+
+        let res = res
+            .get_mut("circuits")
+            .ok_or_else(|| Error::InvalidResponse("No circuits available".into()))?
+            .take();
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the actual power consumption of a circuit in W.
+    pub async fn get_circuit_consumption<S>(&self, circuit: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "circuit/getConsumption",
+                Some(vec![("id", &circuit.into())]),
+                2,
+            )
+            .await?;
+
+        // unpack the consumption
+        res.get("consumption")
+            .ok_or_else(|| Error::InvalidResponse("No consumption returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Consumption is not a number".into()))
+    }
+
+    /// Get the actual energy meter value of a circuit in Wh.
+    pub async fn get_circuit_energy_meter_value<S>(&self, circuit: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "circuit/getEnergyMeterValue",
+                Some(vec![("id", &circuit.into())]),
+                2,
+            )
+            .await?;
+
+        // unpack the meter value
+        res.get("meterValue")
+            .ok_or_else(|| Error::InvalidResponse("No meter value returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Meter value is not a number".into()))
+    }
+
+    /// Get the latest metering values of all meters in the appartment.
+    pub async fn get_latest_meter_values(
+        &self,
+        typ: MeteringType,
+        unit: MeteringUnit,
+    ) -> Result<Vec<MeterValue>> {
+        let mut res = self
+            .generic_request(
+                "metering/getLatest",
+                Some(vec![
+                    ("from", ".meters(all)"),
+                    ("type", typ.as_str()),
+                    ("unit", unit.as_str()),
+                ]),
+                2,
+            )
+            .await?;
+
+        // unpack the values
+        let res = res
+            .get_mut("values")
+            .ok_or_else(|| Error::InvalidResponse("No values returned".into()))?
+            .take();
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the recorded metering values of a single meter.
+    ///
+    /// The resolution is given in seconds, when no count is given
+    /// all available values get returned.
+    pub async fn get_meter_values<S>(
+        &self,
+        meter: S,
+        typ: MeteringType,
+        unit: MeteringUnit,
+        resolution: usize,
+        count: Option<usize>,
+    ) -> Result<Vec<MeterSample>>
+    where
+        S: Into<String>,
+    {
+        let meter = meter.into();
+        let resolution = resolution.to_string();
+        let count = count.map(|c| c.to_string());
+
+        let mut para = vec![
+            ("dsid", meter.as_str()),
+            ("type", typ.as_str()),
+            ("unit", unit.as_str()),
+            ("resolution", resolution.as_str()),
+        ];
+        if let Some(c) = &count {
+            para.push(("valueCount", c));
+        }
+
+        let mut res = self
+            .generic_request("metering/getValues", Some(para), 2)
+            .await?;
+
+        // unpack the values, which are delivered as [timestamp, value] pairs
+        let res = res
+            .get_mut("values")
+            .ok_or_else(|| Error::InvalidResponse("No values returned".into()))?
+            .take();
+        let values: Vec<(u64, f64)> = serde_json::from_value(res)?;
+
+        Ok(values
+            .into_iter()
+            .map(|(timestamp, value)| MeterSample { timestamp, value })
+            .collect())
+    }
+
+    /// Get all available scenes for a specific zone with a type.
+    pub async fn get_scenes(&self, zone: usize, typ: Type) -> Result<Vec<usize>> {
+        let mut json = self
+            .generic_request(
+                "zone/getReachableScenes",
+                Some(vec![
+                    ("id", &zone.to_string()),
+                    ("groupID", &(typ as usize).to_string()),
+                ]),
+                2,
+            )
+            .await?;
+
+        // unpack the scenes
+        let json = json
+            .get_mut("reachableScenes")
+            .ok_or_else(|| Error::InvalidResponse("No scenes returned".into()))?
+            .take();
+
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Return the last called scene for a zone.
+    pub async fn get_last_called_scene(&self, zone: usize, typ: Type) -> Result<usize> {
+        let res = self
+            .generic_request(
+                "zone/getLastCalledScene",
+                Some(vec![
+                    ("id", &zone.to_string()),
+                    ("groupID", &(typ as usize).to_string()),
+                ]),
+                2,
+            )
+            .await?;
+
+        // unpack the scene
+        let number = res
+            .get("scene")
+            .ok_or_else(|| Error::InvalidResponse("No scene returned".into()))?
+            .as_u64()
+            .ok_or_else(|| Error::InvalidResponse("No scene number available".into()))?;
+
+        Ok(number as usize)
+    }
+
+    /// Trigger a scene for a specific zone and type in the dss system.
+    pub async fn call_scene(&self, zone: usize, typ: Type, scene: usize) -> Result<()> {
+        self.generic_request(
+            "zone/callScene",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("sceneNumber", &scene.to_string()),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the name of a scene of a type within the zone.
+    pub async fn get_scene_name(&self, zone: usize, typ: Type, scene: usize) -> Result<String> {
+        let res = self
+            .generic_request(
+                "zone/sceneGetName",
+                Some(vec![
+                    ("id", &zone.to_string()),
+                    ("groupID", &(typ as usize).to_string()),
+                    ("sceneNumber", &scene.to_string()),
+                ]),
+                2,
+            )
+            .await?;
+
+        Ok(res
+            .get("name")
+            .ok_or_else(|| Error::InvalidResponse("No scene name returned".into()))?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("No scene name available".into()))?
+            .to_string())
+    }
+
+    /// Set the name of a scene of a type within the zone.
+    pub async fn set_scene_name(
+        &self,
+        zone: usize,
+        typ: Type,
+        scene: usize,
+        name: &str,
+    ) -> Result<()> {
+        self.generic_request(
+            "zone/sceneSetName",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("sceneNumber", &scene.to_string()),
+                ("newName", name),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Transforms a action to a scene call if possible and executes it
+    pub async fn call_action(&self, zone: usize, action: Action) -> Result<()> {
+        let (typ, scene) = action.to_scene_type().ok_or_else(|| {
+            Error::InvalidInput("Action can't be transformed to scene command".into())
+        })?;
+        self.call_scene(zone, typ, scene).await
+    }
+
+    /// Get the latest sensor values of a zone, like temperature or humidity.
+    pub async fn get_zone_sensor_values(&self, zone: usize) -> Result<Vec<SensorValue>> {
+        let res = self
+            .generic_request(
+                "zone/getSensorValues",
+                Some(vec![("id", &zone.to_string())]),
+                2,
+            )
+            .await?;
+
+        SensorValue::from_zone_values(&res)
+    }
+
+    /// Get the actual value of a sensor from a single device.
+    pub async fn get_device_sensor_value<S>(&self, device: S, sensor_index: usize) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "device/getSensorValue",
+                Some(vec![
+                    ("dsid", &device.into()),
+                    ("sensorIndex", &sensor_index.to_string()),
+                ]),
+                2,
+            )
+            .await?;
+
+        // unpack the value
+        res.get("sensorValue")
+            .ok_or_else(|| Error::InvalidResponse("No sensor value returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Sensor value is not a number".into()))
+    }
+
+    /// Get the actual temperature control status of a zone.
+    pub async fn get_temperature_control_status(
+        &self,
+        zone: usize,
+    ) -> Result<TemperatureControlStatus> {
+        let res = self
+            .generic_request(
+                "zone/getTemperatureControlStatus",
+                Some(vec![("id", &zone.to_string())]),
+                2,
+            )
+            .await?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the temperature control configuration of a zone.
+    pub async fn get_temperature_control_config(
+        &self,
+        zone: usize,
+    ) -> Result<TemperatureControlConfig> {
+        let res = self
+            .generic_request(
+                "zone/getTemperatureControlConfig",
+                Some(vec![("id", &zone.to_string())]),
+                2,
+            )
+            .await?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the nominal temperatures of a zone for every operation mode.
+    pub async fn get_temperature_control_values(
+        &self,
+        zone: usize,
+    ) -> Result<TemperatureControlValues> {
+        let res = self
+            .generic_request(
+                "zone/getTemperatureControlValues",
+                Some(vec![("id", &zone.to_string())]),
+                2,
+            )
+            .await?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Set the nominal temperatures of a zone for the given operation modes.
+    pub async fn set_temperature_control_values(
+        &self,
+        zone: usize,
+        values: &[(HeatingMode, f32)],
+    ) -> Result<()> {
+        let zone = zone.to_string();
+        let values: Vec<(&str, String)> = values
+            .iter()
+            .map(|(mode, v)| (mode.as_key(), v.to_string()))
+            .collect();
+
+        let mut para = vec![("id", zone.as_str())];
+        para.extend(values.iter().map(|(k, v)| (*k, v.as_str())));
+
+        self.generic_request("zone/setTemperatureControlValues", Some(para), 2)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set the output value of all devices of a type within a zone.
+    pub async fn set_zone_value(&self, zone: usize, typ: Type, value: f32) -> Result<()> {
+        // transform to dss range
+        let value = (255.0 * value.clamp(0.0, 1.0)) as usize;
+
+        self.generic_request(
+            "zone/setValue",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("value", &value.to_string()),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the brightness of a single light device.
+    pub async fn get_light_device_value<S>(&self, device: S) -> Result<f32>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "device/getOutputValue",
+                Some(vec![("dsid", &device.into()), ("offset", "0")]),
+                2,
+            )
+            .await?;
+
+        Ok((crate::RawApi::unpack_output_value(&res, 0)? as f32) / 255.0)
+    }
+
+    /// Set the brightness of a single light device.
+    pub async fn set_light_device_value<S>(&self, device: S, value: f32) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        // transform to dss range
+        let value = (255.0 * value.clamp(0.0, 1.0)) as usize;
+
+        self.generic_request(
+            "device/setOutputValue",
+            Some(vec![
+                ("dsid", &device.into()),
+                ("value", &value.to_string()),
+                ("offset", "0"),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the opening status of a single shadow device and resturns it.
+    pub async fn get_shadow_device_open<S>(&self, device: S) -> Result<f32>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "device/getOutputValue",
+                Some(vec![("dsid", &device.into()), ("offset", "2")]),
+                2,
+            )
+            .await?;
+
+        // turn the value around
+        let value = (crate::RawApi::unpack_output_value(&res, 2)? as f32) / 65535.0;
+        Ok(1.0 - value)
+    }
+
+    /// Set the shadow opening for a single device
+    pub async fn set_shadow_device_open<S>(&self, device: S, value: f32) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        // move the direction 1 is down 0 is up and transform to dss range
+        let value = (65535.0 * (1.0 - value.clamp(0.0, 1.0))) as usize;
+
+        self.generic_request(
+            "device/setOutputValue",
+            Some(vec![
+                ("dsid", &device.into()),
+                ("value", &value.to_string()),
+                ("offset", "2"),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the shadow open angle for a single device.
+    pub async fn get_shadow_device_angle<S>(&self, device: S) -> Result<f32>
+    where
+        S: Into<String>,
+    {
+        let res = self
+            .generic_request(
+                "device/getOutputValue",
+                Some(vec![("dsid", &device.into()), ("offset", "4")]),
+                2,
+            )
+            .await?;
+
+        Ok((crate::RawApi::unpack_output_value(&res, 4)? as f32) / 65535.0)
+    }
+
+    /// Set the shade open angle for a single device
+    pub async fn set_shadow_device_angle<S>(&self, device: S, value: f32) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        // transform to dss range
+        let value = (255.0 * value.clamp(0.0, 1.0)) as usize;
+
+        self.generic_request(
+            "device/setOutputValue",
+            Some(vec![
+                ("dsid", &device.into()),
+                ("value", &value.to_string()),
+                ("offset", "4"),
+            ]),
+            2,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// A stream of the actual status and every change of the state.
+fn status_stream(health: &Health) -> impl futures::Stream<Item = Status> {
+    futures::stream::unfold(health.watch_async(), |mut recv| async move {
+        recv.recv().await.map(|status| (status, recv))
+    })
+}
+
+/// Turn a tokio receiver into a stream, which ends when all senders are gone.
+fn receiver_stream<T>(recv: tokio::sync::mpsc::Receiver<T>) -> impl futures::Stream<Item = T> {
+    futures::stream::unfold(recv, |mut recv| async move {
+        recv.recv().await.map(|t| (t, recv))
+    })
+}
@@ -1,3 +1,8 @@
+#[cfg(feature = "async")]
+pub mod async_api;
+#[cfg(feature = "mock")]
+pub mod mock;
+
 /// The appartment gives you a easy and highlevel interface
 /// to a dss installation. It's the main struct this crates
 /// provides.
@@ -12,57 +17,98 @@
 #[derive(Debug, Clone)]
 pub struct Appartement {
     inner: std::sync::Arc<std::sync::Mutex<InnerAppartement>>,
+    events: std::sync::Arc<std::sync::Mutex<EventHub>>,
+    health: std::sync::Arc<Health>,
 }
 
 impl Appartement {
     /// Connect to a DSS installation and fetch the complete structure of it.
-    /// Based on your appartment size, this can take around a minute.
+    /// The structure is fetched in bulk, so this takes only a few seconds.
+    ///
+    /// The certificate is verified against the root certificates, use
+    /// 'connect_file()' or 'from_api()' for the self signed one of the dss.
     pub fn connect<S>(host: S, user: S, password: S) -> Result<Appartement>
     where
         S: Into<String>,
     {
-        // create the Appartment with the inner values
-        let appt = Appartement {
-            inner: std::sync::Arc::new(std::sync::Mutex::new(InnerAppartement {
-                api: RawApi::connect(host, user, password)?,
-                zones: vec![],
-                file: None,
-                thread: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            })),
-        };
+        Appartement::new(RawApi::connect(host, user, password)?, None)
+    }
 
-        // update the complete structure
-        appt.inner.lock()?.update_structure()?;
+    /// Connect to a DSS installation and load structure from file.
+    ///
+    /// The certificate of the dss is trusted on first use and it's
+    /// fingerprint is stored next to the file.
+    ///
+    /// A missing file is created with the fetched structure, an invalid
+    /// one is an error.
+    pub fn connect_file<S>(host: S, user: S, password: S, file: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        let file = file.into();
+        let api = RawApi::builder(host)
+            .user(user, password)
+            .tls(Tls::TrustOnFirstUse(fingerprint_file(&file)))
+            .connect()?;
 
-        Ok(appt)
+        Appartement::new(api, Some(file))
     }
 
-    /// Connect to a DSS installation and load structure from file
-    pub fn connect_file<S>(host: S, user: S, password: S, file: S) -> Result<Appartement>
+    /// Connect to a DSS installation with an application token and fetch
+    /// the complete structure of it.
+    ///
+    /// A token can be requested with 'RawApi::request_application_token()'.
+    pub fn connect_token<S>(host: S, application_token: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        Appartement::new(RawApi::connect_token(host, application_token)?, None)
+    }
+
+    /// Connect to a DSS installation with an application token and load
+    /// the structure from file.
+    ///
+    /// The certificate of the dss is trusted on first use and it's
+    /// fingerprint is stored next to the file.
+    pub fn connect_token_file<S>(host: S, application_token: S, file: S) -> Result<Appartement>
     where
         S: Into<String>,
     {
         let file = file.into();
+        let api = RawApi::builder(host)
+            .application_token(application_token)
+            .tls(Tls::TrustOnFirstUse(fingerprint_file(&file)))
+            .connect()?;
 
-        // load the zones from file
-        let mut zones = vec![];
-        if let Ok(s) = std::fs::read_to_string(&file) {
-            let r = serde_json::from_str(&s);
-            if let Ok(z) = r {
-                zones = z;
-            } else {
-                println!("{:?}", r);
-            }
-        }
+        Appartement::new(api, Some(file))
+    }
+
+    /// Create the appartment from an already connected api, like one
+    /// created with 'RawApi::builder()', and fetch the complete structure.
+    pub fn from_api(api: RawApi) -> Result<Appartement> {
+        Appartement::new(api, None)
+    }
+
+    /// Create the appartment from an already connected api and load
+    /// the structure from file.
+    pub fn from_api_file<S>(api: RawApi, file: S) -> Result<Appartement>
+    where
+        S: Into<String>,
+    {
+        Appartement::new(api, Some(file.into()))
+    }
 
+    fn new(api: RawApi, file: Option<String>) -> Result<Appartement> {
         // create the Appartment with the inner values
         let appt = Appartement {
+            health: api.endpoint.health.clone(),
             inner: std::sync::Arc::new(std::sync::Mutex::new(InnerAppartement {
-                api: RawApi::connect(host, user, password)?,
-                zones,
-                file: Some(file),
-                thread: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+                api,
+                zones: load_zones(&file)?,
+                file,
+                cache_failed: false,
             })),
+            events: std::sync::Arc::new(std::sync::Mutex::new(EventHub::default())),
         };
 
         // update the complete structure if no zones where loaded
@@ -86,7 +132,7 @@ impl Appartement {
     }
 
     /// Updates the complete appartment structure, this command can take some time
-    /// to execute (more than 30 seconds).
+    /// to execute (a few seconds).
     ///
     /// Use the 'get_zones()' function to get the actual structure with updates
     /// values for each group.
@@ -99,858 +145,3116 @@ impl Appartement {
         self.inner.lock()?.get_value(zone, group)
     }
 
+    /// Set the value for a zone or a specific group within the zone.
+    ///
+    /// A light value of 0.0 or 1.0 turns the light off or on, every value
+    /// in between dimms the light to this brightness.
     pub fn set_value(&self, zone: usize, group: Option<usize>, value: Value) -> Result<()> {
         self.inner.lock()?.set_value(zone, group, value)
     }
 
-    /// Get the event channel for the appartment.
-    ///
-    /// When a channel is already open for this apparment, we close the open one
-    /// and create a new one which gets returned.
-    /// Therefore it's not recommended to call this function twice for one appartment.
-    pub fn event_channel(&self) -> Result<std::sync::mpsc::Receiver<Event>> {
-        // when there are threads already existing close them
-        self.inner
-            .lock()?
-            .thread
-            .store(false, std::sync::atomic::Ordering::Relaxed);
+    /// Get the temperature control status of a zone, like the actual
+    /// operation mode and temperatures.
+    pub fn get_temperature_control(&self, zone: usize) -> Result<TemperatureControlStatus> {
+        self.inner.lock()?.api.get_temperature_control_status(zone)
+    }
 
-        // request a new event channel
-        let (recv, status) = self.inner.lock()?.api.new_event_channel()?;
+    /// Call a scene of a type within the zone, like a pressed switch would do.
+    ///
+    /// The apartment wide scenes, like 'Scene::Absent', are called for the zone 0.
+    pub fn call_scene(&self, zone: usize, typ: Type, scene: Scene) -> Result<()> {
+        if scene == Scene::Unknown {
+            return Err(Error::InvalidInput(
+                "The unknown scene can't be called".into(),
+            ));
+        }
 
-        // create the new out channel
-        let (inp, out) = std::sync::mpsc::channel();
+        let zone = if scene.is_apartment() { 0 } else { zone };
+        self.inner.lock()?.api.call_scene(zone, typ, scene as usize)
+    }
 
-        // clone the status for the thread & the structure
-        let internal_status = status.clone();
-        let appr = self.inner.clone();
+    /// Call a scene of a type within the zone by it's number.
+    ///
+    /// The known scenes are called like with 'call_scene', the other numbers
+    /// are sent to the dss as they are.
+    pub fn call_scene_by_number(&self, zone: usize, typ: Type, scene: usize) -> Result<()> {
+        match Scene::from(scene.min(255) as u8) {
+            Scene::Unknown => self.inner.lock()?.api.call_scene(zone, typ, scene),
+            known => self.call_scene(zone, typ, known),
+        }
+    }
 
-        #[allow(clippy::while_let_loop)]
-        std::thread::spawn(move || loop {
-            // listen for events to pop up
-            let event = match recv.recv() {
-                Ok(e) => e,
-                Err(_) => break,
-            };
+    /// Call a scene of a type within the zone by it's name.
+    ///
+    /// The name is either the name the user has given the scene, like 'Reading'
+    /// or the name of the preset, like 'Preset 2'.
+    pub fn call_scene_by_name(&self, zone: usize, typ: Type, name: &str) -> Result<()> {
+        let scene = self.find_scene(zone, typ.clone(), name)?;
+        self.call_scene(zone, typ, scene)
+    }
 
-            // check if the thread should be ended
-            if !internal_status.load(std::sync::atomic::Ordering::Relaxed) {
-                break;
+    /// Find the scene of a type within the zone by it's name.
+    ///
+    /// The user given names are preferred over the names of the presets.
+    pub fn find_scene(&self, zone: usize, typ: Type, name: &str) -> Result<Scene> {
+        let inner = self.inner.lock()?;
+
+        for scene in inner.api.get_scenes(zone, typ.clone())? {
+            let scene_name = inner.api.get_scene_name(zone, typ.clone(), scene)?;
+            if !scene_name.is_empty() && scene_name.eq_ignore_ascii_case(name.trim()) {
+                return Ok(Scene::from(scene.min(255) as u8));
             }
+        }
 
-            // expand the events when necessary
-            let events = appr.lock().unwrap().expand_value(event).unwrap();
+        name.parse()
+    }
 
-            #[allow(clippy::suspicious_operation_groupings)]
-            for event in events {
-                // update the event value for shadow etc.
-                let event = appr.lock().unwrap().update_event_value(event).unwrap();
+    /// Get the name the user has given a scene of a type within the zone.
+    ///
+    /// An empty name is returned, when the scene has no name.
+    pub fn get_scene_name(&self, zone: usize, typ: Type, scene: Scene) -> Result<String> {
+        self.inner
+            .lock()?
+            .api
+            .get_scene_name(zone, typ, scene as usize)
+    }
 
-                // update the appartment structure with the event
-                {
-                    let mut appr = appr.lock().unwrap();
-                    appr.zones.iter_mut().for_each(|z| {
-                        // fine the right zone to the event
-                        if z.id == event.zone {
-                            z.groups.iter_mut().for_each(|g| {
-                                // find the right group typ && id to update the value
-                                if g.typ == event.typ && g.id == event.group {
-                                    g.status = event.value.clone();
-                                }
-                            });
-                        }
-                    });
+    /// Give a scene of a type within the zone a name.
+    pub fn set_scene_name(&self, zone: usize, typ: Type, scene: Scene, name: &str) -> Result<()> {
+        self.inner
+            .lock()?
+            .api
+            .set_scene_name(zone, typ, scene as usize, name)
+    }
 
-                    if let Err(e) = appr.save_status() {
-                        println!("Error while saving: {}", e);
-                    }
-                }
+    /// Increase the brightness of all lights within the zone by one step.
+    pub fn increase_light(&self, zone: usize) -> Result<()> {
+        self.inner
+            .lock()?
+            .step_light(zone, Action::AllLightIncrement)
+    }
 
-                // send the event
-                match inp.send(event) {
-                    Ok(_) => {}
-                    Err(_) => break,
-                }
-            }
-        });
+    /// Decrease the brightness of all lights within the zone by one step.
+    pub fn decrease_light(&self, zone: usize) -> Result<()> {
+        self.inner
+            .lock()?
+            .step_light(zone, Action::AllLightDecrement)
+    }
 
-        // update the thread status with the new one received
-        self.inner.lock()?.thread = status;
-        Ok(out)
+    /// Get the actual power consumption and energy meter values
+    /// for every circuit and the whole appartment.
+    pub fn consumption(&self) -> Result<Consumption> {
+        self.inner.lock()?.consumption()
     }
-}
 
-#[derive(Debug)]
-struct InnerAppartement {
-    api: RawApi,
-    zones: Vec<Zone>,
-    file: Option<String>,
-    thread: std::sync::Arc<std::sync::atomic::AtomicBool>,
-}
+    /// The actual state of the connection to the dss, with the time of
+    /// the last successful request and the last received event.
+    ///
+    /// It doesn't wait for running requests, so it can be used for monitoring.
+    pub fn status(&self) -> Status {
+        self.health.status()
+    }
 
-impl InnerAppartement {
-    fn get_value(&self, zone: usize, group: usize) -> Result<Value> {
-        self.zones
+    /// Get a channel, which receives the status when the state of the connection
+    /// changes, starting with the actual one.
+    pub fn status_channel(&self) -> std::sync::mpsc::Receiver<Status> {
+        self.health.watch()
+    }
+
+    /// Get an event channel for the appartment, which receives all events.
+    ///
+    /// Every channel has it's own buffer of 'EVENT_BUFFER' events, all of
+    /// them are served by a single subscription at the dss.
+    /// The channel is unsubscribed, when the receiver gets dropped.
+    pub fn event_channel(&self) -> Result<EventReceiver> {
+        self.event_channel_filtered(EventFilter::all(), EVENT_BUFFER)
+    }
+
+    /// Get an event channel for the appartment, which only receives the
+    /// events with the given names.
+    pub fn event_channel_with(&self, names: &[&str]) -> Result<EventReceiver> {
+        let filter = names
             .iter()
-            .find(|z| z.id == zone)
-            .and_then(|z| z.groups.iter().find(|g| g.id == group))
-            .map(|g| g.status.clone())
-            .ok_or_else(|| "No Value found for given device".into())
+            .fold(EventFilter::all(), |filter, name| filter.name(*name));
+        self.event_channel_filtered(filter, EVENT_BUFFER)
     }
 
-    fn set_value(&mut self, zone: usize, group: Option<usize>, value: Value) -> Result<()> {
-        // when a group exist we control the special group
-        if let Some(grp) = group {
-            match value {
-                // depending on the value we turn the light on or off
-                Value::Light(light) => {
-                    if light < 0.5 {
-                        self.api.call_action(zone, Action::LightOff(grp))?;
-                    } else {
-                        self.api.call_action(zone, Action::LightOn(grp))?;
-                    }
-                }
-                // actions need to be performed for setting the shadow
-                Value::Shadow(open, angle) => {
-                    if open <= 0.1 {
-                        self.api.call_action(zone, Action::ShadowUp(grp))?;
-                    }
-                    if open >= 0.9 && angle <= 0.1 {
-                        self.api.call_action(zone, Action::ShadowDown(grp))?;
-                    } else {
-                        // we need to set a specific position and angle, this can't be done over an scene
-                        // we need to get all devices for the actual zone and show type
-                        let devices = self
-                            .zones
-                            .iter()
-                            .find(|z| z.id == zone)
-                            .ok_or("Not a valid zone id given")?
-                            .groups
-                            .iter()
-                            .find(|g| g.id == grp)
-                            .ok_or("Not a valid group given")?
-                            .devices
-                            .iter()
-                            .filter(|d| d.device_type == DeviceType::Shadow)
-                            .clone();
-
-                        for dev in devices {
-                            self.api.set_shadow_device_open(dev.id.clone(), open)?;
-                            self.api.set_shadow_device_angle(dev.id.clone(), angle)?;
-                        }
+    /// Get an event channel for the appartment, which only receives the events
+    /// passing the filter and buffers up to 'capacity' events.
+    ///
+    /// When the buffer is full, the following events are dropped for this
+    /// channel, so a slow receiver doesn't hold back the others. They are
+    /// counted by 'EventReceiver::dropped()'.
+    pub fn event_channel_filtered(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+    ) -> Result<EventReceiver> {
+        let (send, recv) = std::sync::mpsc::sync_channel(capacity);
+        let state = std::sync::Arc::default();
+        let mut hub = self.events.lock()?;
+        let id = self.subscribe(&mut hub, filter, Target::Channel(send), &state)?;
+
+        Ok(EventReceiver {
+            recv,
+            id,
+            state,
+            hub: self.events.clone(),
+        })
+    }
 
-                        // we need to update the state of the shadow manually, because no event will be triggered
-                        self.zones
-                            .iter_mut()
-                            .find(|z| z.id == zone)
-                            .ok_or("No valid zone given")?
-                            .groups
-                            .iter_mut()
-                            .find(|g| g.id == grp)
-                            .ok_or("Not a valid group given")?
-                            .status = value;
-                    }
-                }
-                Value::Unknown => (),
+    /// Register a handler, which is called for every event passing the filter.
+    ///
+    /// All handlers of the appartment are called one after another by a single
+    /// dispatcher thread, the failures are only received by the event channels.
+    /// When the subscription can't be restored, the handler is unregistered and
+    /// the failure is kept by 'Subscription::error()'. Otherwise the handler is
+    /// unregistered, when the returned subscription gets dropped.
+    /// ```no_run
+    /// # let appt = dss::Appartement::connect("url", "user", "password").unwrap();
+    /// let filter = dss::EventFilter::zone(2).typ(dss::Type::Light);
+    /// let subscription = appt.on(filter, |event| println!("{:?}", event)).unwrap();
+    /// ```
+    pub fn on<F>(&self, filter: EventFilter, handler: F) -> Result<Subscription>
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        let mut hub = self.events.lock()?;
+
+        let dispatcher = match &hub.dispatcher {
+            Some(dispatcher) => dispatcher.clone(),
+            None => {
+                let dispatcher = self.start_dispatcher();
+                hub.dispatcher = Some(dispatcher.clone());
+                dispatcher
             }
-        }
-        // when no group is defined we controll the whole zone
-        else {
-            match value {
-                // depending on the value we turn the light on or off
-                Value::Light(light) => {
-                    if light < 0.5 {
-                        self.api.call_action(zone, Action::AllLightOff)?;
-                    } else {
-                        self.api.call_action(zone, Action::AllLightOn)?;
-                    }
-                }
-                // actions need to be performed for setting the shadow
-                Value::Shadow(open, angle) => {
-                    if open <= 0.1 {
-                        self.api.call_action(zone, Action::AllShadowUp)?;
-                    }
-                    if open >= 0.9 && angle <= 0.1 {
-                        self.api.call_action(zone, Action::AllShadowDown)?;
-                    } else {
-                        // we need to set a specific position and angle, this can't be done over an scene
-
-                        // we need to get all devices for the actual zone
-                        let devices = self
-                            .zones
-                            .iter()
-                            .find(|z| z.id == zone)
-                            .ok_or("Not a valid zone id given")?
-                            .groups
-                            .iter()
-                            .map(|g| g.devices.clone())
-                            .flatten()
-                            .collect::<Vec<Device>>()
-                            .into_iter()
-                            .filter(|d| d.device_type == DeviceType::Shadow);
-
-                        for dev in devices {
-                            self.api.set_shadow_device_open(dev.id.clone(), open)?;
-                            self.api.set_shadow_device_angle(dev.id.clone(), angle)?;
-                        }
+        };
 
-                        // we need to update the state of the shadow manually, because no event will be triggered
-                        self.zones
-                            .iter_mut()
-                            .find(|z| z.id == zone)
-                            .ok_or("Not a valid zone id given")?
-                            .groups
-                            .iter_mut()
-                            .filter(|g| g.typ == Type::Shadow)
-                            .for_each(|g| g.status = value.clone());
-                    }
+        let state = std::sync::Arc::default();
+        let id = match self.subscribe(&mut hub, filter, Target::Handler(dispatcher), &state) {
+            Ok(id) => id,
+            Err(e) => {
+                // end the dispatcher again, when it was started for this handler
+                if hub.handlers.is_empty() {
+                    hub.dispatcher = None;
                 }
-                Value::Unknown => (),
+                return Err(e);
             }
-        }
-
-        Ok(())
-    }
-
-    fn expand_value(&self, event: Event) -> Result<Vec<Event>> {
-        // when we have an action of type ShadowStepOpen
-        // it effects all groups of a zone and we create multiple events for it
-        // if we have multiple groups for this typ
-        if event.typ == Type::Shadow
-            && (event.action == Action::ShadowStepOpen || event.action == Action::ShadowStepClose)
-        {
-            // we get all group id's with Shadow within the event zone
-            let groups: Vec<usize> = self
-                .zones
-                .iter()
-                .find(|z| z.id == event.zone)
-                .ok_or("No matching zone available")?
-                .groups
-                .iter()
-                .filter(|g| g.typ == Type::Shadow)
-                .map(|g| g.id)
-                .collect();
+        };
 
-            // for each shadow group we create a new event
-            return Ok(groups
-                .iter()
-                .map(|g| {
-                    let mut e = event.clone();
-                    e.group = *g;
-                    e
-                })
-                .collect());
-        }
+        let handler = Handler(Box::new(handler), state.clone());
+        hub.handlers
+            .insert(id, std::sync::Arc::new(std::sync::Mutex::new(handler)));
 
-        Ok(vec![event])
+        Ok(Subscription {
+            id,
+            state,
+            hub: self.events.clone(),
+        })
     }
 
-    fn update_event_value(&mut self, event: Event) -> Result<Event> {
-        // make the event mutable
-        let mut event = event;
-
-        // update the value
-        event.value = self.update_value(event.value, &event.typ, event.zone, event.group)?;
-        Ok(event)
-    }
+    /// Add a subscriber to the hub and return it's id.
+    fn subscribe(
+        &self,
+        hub: &mut EventHub,
+        filter: EventFilter,
+        target: Target,
+        state: &std::sync::Arc<SubscriberState>,
+    ) -> Result<usize> {
+        // the subscription at the dss needs to include the names of the filter
+        let mut names = hub.names.clone();
+        let wanted: Vec<&str> = if filter.names.is_empty() {
+            DEFAULT_EVENT_NAMES.to_vec()
+        } else {
+            filter.names.iter().map(|n| n.as_str()).collect()
+        };
+        for name in wanted {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
 
-    fn update_value(
-        &mut self,
-        value: Value,
-        typ: &Type,
-        zone: usize,
-        group: usize,
-    ) -> Result<Value> {
-        // when the value is already defined, the event is already updated
-        if value != Value::Unknown {
-            return Ok(value);
+        if hub.subscription.is_none() || names != hub.names {
+            self.start_events(hub, names)?;
         }
 
-        // let's fix the shadow events
-        if typ == &Type::Shadow {
-            // get the first device for the defined group
-            let device = self
-                .zones
-                .iter()
-                .find(|z| z.id == zone)
-                .ok_or("No matching zone found")?
-                .groups
-                .iter()
-                .find(|g| g.id == group && g.typ == Type::Shadow)
-                .ok_or("No matching group found")?
-                .devices
-                .get(0)
-                .ok_or("No devices available")?;
+        hub.next_id += 1;
+        let id = hub.next_id;
+        hub.subscribers.push(Subscriber {
+            id,
+            filter,
+            target,
+            state: state.clone(),
+        });
 
-            // get the actual device values
-            let open = self.api.get_shadow_device_open(&device.id)?;
-            let angle = self.api.get_shadow_device_angle(&device.id)?;
+        Ok(id)
+    }
 
-            // return them in the Shadow format
-            return Ok(Value::Shadow(open, angle));
-        }
+    /// Start the thread, which calls the handlers with their events.
+    ///
+    /// It ends, when the last handler is unregistered.
+    fn start_dispatcher(&self) -> std::sync::mpsc::SyncSender<(usize, Event)> {
+        let (send, recv) = std::sync::mpsc::sync_channel::<(usize, Event)>(EVENT_BUFFER);
+        let events = self.events.clone();
+
+        std::thread::spawn(move || {
+            for (id, event) in recv.iter() {
+                let handler = events
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .handlers
+                    .get(&id)
+                    .cloned();
+
+                // the hub isn't locked while the handler runs, so it can use the appartment
+                if let Some(handler) = handler {
+                    // a panicking handler doesn't stop the others
+                    handler
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .call(&event);
+                }
+            }
+        });
 
-        Ok(value)
+        send
     }
 
-    fn update_structure(&mut self) -> Result<()> {
-        let devices = self.api.get_devices()?;
-        let mut zones: Vec<Zone> = self
-            .api
-            .get_zones()?
-            .into_iter()
-            .filter(|z| z.id != 0 && z.id != 65534)
-            .collect();
+    /// Subscribe to the events at the dss and start the thread, which
+    /// distributes them to the subscribers of the hub.
+    ///
+    /// An already running subscription is replaced by the new one.
+    fn start_events(&self, hub: &mut EventHub, names: Vec<String>) -> Result<()> {
+        let names_ref: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+        let (recv, subscription) = self.inner.lock()?.api.event_subscription(&names_ref)?;
+        let status = subscription.running.clone();
+
+        if let Some(old) = hub.subscription.replace(subscription) {
+            old.close();
+        }
+        hub.names = names;
 
-        for zone in &mut zones {
-            // add all the groups
-            for typ in &zone.types {
-                // get all available scenes for this zone
-                let scenes = self.api.get_scenes(zone.id, typ.clone())?;
+        let appr = self.inner.clone();
+        let events = self.events.clone();
 
-                // convert the scenes to groups
-                let mut scene_groups = Group::from_scenes(&scenes, zone.id, &typ);
+        std::thread::spawn(move || {
+            // the last failure of the subscription, it's kept when the channels get closed
+            let mut failure = None;
 
-                // the last called action for shadows are always wrong,
-                // so we set it directly to unknown and dont request the last called scene
-                let action;
-                if typ == &Type::Shadow {
-                    action = Action::Unknown;
-                } else {
-                    // get the last called scene for this typ within a zone
-                    let lcs = self.api.get_last_called_scene(zone.id, typ.clone())?;
-                    // convert the last called scene to an action
-                    action = Action::new(typ.clone(), lcs);
+            for res in recv.iter() {
+                // check if the thread should be ended
+                if !status.load(std::sync::atomic::Ordering::Relaxed) {
+                    return;
                 }
 
-                // add the last called action for each scene group
-                scene_groups
-                    .iter_mut()
-                    .for_each(|g| g.status = Value::from_action(action.clone(), g.id));
-
-                // add the scene groups to the group array
-                zone.groups.append(&mut scene_groups);
-            }
-
-            #[allow(clippy::suspicious_operation_groupings)]
-            // for every group add the devices
-            for group in &mut zone.groups {
-                // loop over all devices
-                // filtered down to light and shadow devices
-                for device in devices.iter().filter(|d| {
-                    d.device_type == DeviceType::Light || d.device_type == DeviceType::Shadow
-                }) {
-                    // if the device matches and the group is 0 (which means general all devices)
-                    if group.id == 0
-                        && device.zone_id == group.zone_id
-                        && group.typ == device.button_type
-                    {
-                        group.devices.push(device.clone());
-                    }
-                    // when the devices matches, but the scene group is not 0 we need to check where to sort the devices
-                    else if device.zone_id == group.zone_id && group.typ == device.button_type {
-                        // check the device mode for this scene within that zone
-                        #[allow(clippy::bind_instead_of_map)]
-                        let _ = self
-                            .api
-                            .get_device_scene_mode(device.id.clone(), group.id)
-                            .and_then(|dsm| {
-                                // when the device cares about this scene group we add it
-                                if !dsm.dont_care {
-                                    group.devices.push(device.clone());
+                // update the appartment structure with the event
+                let (results, failed, poisoned) = match res {
+                    Ok(event) => match appr.lock() {
+                        Ok(mut appr) => (appr.handle_event(event), false, false),
+                        Err(_) => (vec![Err(Error::Poisoned)], true, true),
+                    },
+                    Err(e) => (vec![Err(e)], true, false),
+                };
+
+                // send the results to every subscriber, a full buffer drops them
+                let mut hub = events.lock().unwrap_or_else(|e| e.into_inner());
+                for res in results {
+                    match res {
+                        Ok(event) => hub
+                            .subscribers
+                            .retain(|s| !s.filter.matches(&event) || s.send(Ok(event.clone()))),
+                        Err(e) => {
+                            let e = std::sync::Arc::new(e);
+                            let wrap = |e| {
+                                if failed {
+                                    Error::Event(e)
+                                } else {
+                                    Error::EventApply(e)
                                 }
-                                Ok(())
-                            });
+                            };
+                            hub.subscribers.retain(|s| s.send(Err(wrap(e.clone()))));
+                            if failed {
+                                failure = Some(e);
+                            }
+                        }
                     }
                 }
-            }
-        }
-
-        self.zones = zones.clone();
 
-        for zone in &mut zones {
-            // for every shadow group get the shadow values
-            for group in zone.groups.iter_mut().filter(|g| g.typ == Type::Shadow) {
-                // get the real shadow value
-                let status = self.update_value(group.status.clone(), &group.typ, zone.id, group.id);
+                if hub.subscribers.is_empty() {
+                    hub.stop();
+                    return;
+                }
 
-                // when the value available, then set it
-                match status {
-                    Ok(v) => group.status = v,
-                    Err(_) => continue,
+                if poisoned {
+                    break;
                 }
             }
-        }
 
-        self.zones = zones;
-        if let Err(e) = self.save_status() {
-            println!("Error while saving: {}", e);
-        }
+            // the subscription failed and the error was delivered, close the channels
+            let mut hub = events.lock().unwrap_or_else(|e| e.into_inner());
+            if status.load(std::sync::atomic::Ordering::Relaxed) {
+                hub.close(failure);
+            }
+        });
 
         Ok(())
     }
+}
 
-    fn save_status(&self) -> Result<()> {
-        if let Some(file) = &self.file {
-            let content = serde_json::to_string_pretty(&self.zones)?;
-            std::fs::write(file, content)?;
-        }
-        Ok(())
-    }
+#[derive(Debug)]
+struct InnerAppartement {
+    api: RawApi,
+    zones: Vec<Zone>,
+    file: Option<String>,
+    /// The last write of the structure file failed
+    cache_failed: bool,
 }
 
-impl Drop for InnerAppartement {
-    fn drop(&mut self) {
-        // if it fails we can't stop the threads
-        #[allow(unused_must_use)]
+/// A running subscription of events at the dss.
+#[derive(Debug, Clone)]
+struct EventSubscription {
+    api: RawApi,
+    id: String,
+    names: Vec<String>,
+    /// Tells the receiving thread to go on
+    running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl EventSubscription {
+    /// End the receiving thread and close the subscription at the dss right
+    /// away, so the pending 'event/get' returns.
+    fn close(&self) {
+        if self
+            .running
+            .swap(false, std::sync::atomic::Ordering::Relaxed)
         {
-            self.thread
-                .store(false, std::sync::atomic::Ordering::Relaxed);
+            let this = self.clone();
+            std::thread::spawn(move || this.api.unsubscribe(&this.id, &this.names));
         }
     }
 }
 
-/// Raw interface towards the DSS-Rest service. This is not intend to be used
-/// directly from a API consumer. It misses important status management and
-/// abstraction over the different devices.
-#[derive(Debug, Clone)]
-pub struct RawApi {
-    host: String,
-    user: String,
-    password: String,
-    token: std::sync::Arc<std::sync::RwLock<String>>,
+/// The subscribers of the appartment events, which are all served
+/// by a single subscription at the dss.
+#[derive(Debug, Default)]
+struct EventHub {
+    subscribers: Vec<Subscriber>,
+    next_id: usize,
+    /// The event names subscribed at the dss
+    names: Vec<String>,
+    /// The running subscription
+    subscription: Option<EventSubscription>,
+    /// The registered handlers by their subscriber id
+    handlers: std::collections::HashMap<usize, std::sync::Arc<std::sync::Mutex<Handler>>>,
+    /// The channel to the thread, which calls the handlers
+    dispatcher: Option<std::sync::mpsc::SyncSender<(usize, Event)>>,
 }
 
-impl RawApi {
-    /// Connect to the Digital Strom Server and try to login.
-    pub fn connect<S>(host: S, user: S, password: S) -> Result<Self>
-    where
-        S: Into<String>,
-    {
-        let api = RawApi {
-            host: host.into(),
-            user: user.into(),
-            password: password.into(),
-            token: std::sync::Arc::new(std::sync::RwLock::new(String::from(""))),
-        };
+impl EventHub {
+    /// End the subscription at the dss.
+    fn stop(&mut self) {
+        if let Some(subscription) = self.subscription.take() {
+            subscription.close();
+        }
+        self.names.clear();
+    }
 
-        api.login()?;
+    /// Remove the channel or handler with the id.
+    fn remove(&mut self, id: usize) {
+        self.subscribers.retain(|s| s.id != id);
 
-        Ok(api)
-    }
+        if self.handlers.remove(&id).is_some() && self.handlers.is_empty() {
+            self.dispatcher = None;
+        }
 
-    fn get_token(&self) -> Result<String> {
-        Ok(self.token.read()?.clone())
+        if self.subscribers.is_empty() {
+            self.stop();
+        }
     }
 
-    fn set_token(&self, token: &str) -> Result<()> {
-        let mut t = self.token.write()?;
-        *t = token.into();
-        Ok(())
+    /// Close all channels and drop the handlers, they keep the failure.
+    fn close(&mut self, error: Option<std::sync::Arc<Error>>) {
+        for subscriber in self.subscribers.drain(..) {
+            subscriber.state.close(error.clone());
+        }
+        self.handlers.clear();
+        self.dispatcher = None;
+        self.stop();
     }
+}
 
-    fn login(&self) -> Result<()> {
-        // build the client and allow invalid certificates
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()?;
+#[derive(Debug)]
+struct Subscriber {
+    id: usize,
+    filter: EventFilter,
+    target: Target,
+    /// The state shared with the receiver
+    state: std::sync::Arc<SubscriberState>,
+}
 
-        // make the login request
-        let mut response = client
-            .get(&format!("https://{}:8080/json/system/login", self.host))
-            .query(&[("user", &self.user), ("password", &self.password)])
-            .send()?;
+/// Where the events of a subscriber are delivered to.
+#[derive(Debug)]
+enum Target {
+    /// The channel of an 'EventReceiver'.
+    Channel(std::sync::mpsc::SyncSender<Result<Event>>),
+    /// The dispatcher thread, which calls the handler of the subscriber.
+    Handler(std::sync::mpsc::SyncSender<(usize, Event)>),
+}
 
-        // get the result as Json Value
-        let json: serde_json::Value = response.json()?;
+/// The callback of a handler registered with 'Appartement::on()'.
+struct Handler(
+    Box<dyn FnMut(&Event) + Send>,
+    std::sync::Arc<SubscriberState>,
+);
 
-        // extract the token
-        self.set_token(
-            &json
-                .get("result")
-                .ok_or("No result in Json response")?
-                .get("token")
-                .ok_or("No token in Json response")?
-                .as_str()
-                .ok_or("Token is not a String")?
-                .to_string(),
-        )?;
+impl Handler {
+    /// Call the handler, a panic is counted instead of ending the caller.
+    fn call(&mut self, event: &Event) {
+        call_handler(&mut self.0, event, &self.1);
+    }
+}
 
-        Ok(())
+/// Call the handler with the event, a panic is counted in the state of the
+/// subscription, so the other events and handlers keep being served.
+fn call_handler<F>(handler: &mut F, event: &Event, state: &SubscriberState)
+where
+    F: FnMut(&Event) + ?Sized,
+{
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handler(event)));
+    if res.is_err() {
+        state
+            .panics
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
+}
 
-    /// Generic requset function, which handles the token inserting/login,
-    /// the json parsing and success check.
-    ///
-    /// It returns a json value, dependet on the request.
-    pub fn generic_request<S>(
-        &self,
-        request: S,
-        parameter: Option<Vec<(&str, &str)>>,
-        retries: usize,
-    ) -> Result<serde_json::Value>
-    where
-        S: Into<String>,
-    {
-        let request = request.into();
-        let token = self.get_token()?;
-
-        // Handle parameter and add token
-        let para = match parameter.clone() {
-            None => vec![("token", token.as_ref())],
-            Some(mut p) => {
-                p.push(("token", &token));
-                p
-            }
-        };
+impl std::fmt::Debug for Handler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Handler")
+    }
+}
+
+/// The state of an event subscriber, which is shared with it's receiver.
+#[derive(Debug, Default)]
+struct SubscriberState {
+    /// The number of events dropped, because the buffer was full
+    dropped: std::sync::atomic::AtomicUsize,
+    /// The number of events, for which the handler panicked
+    panics: std::sync::atomic::AtomicUsize,
+    /// The failure, which closed the subscription
+    error: std::sync::Mutex<Option<std::sync::Arc<Error>>>,
+}
 
-        // build the client and allow invalid certificates
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .timeout(None)
-            .build()?;
+impl SubscriberState {
+    fn dropped_one(&self) {
+        self.dropped
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
 
-        // make the login request
-        let mut response = client
-            .get(&format!("https://{}:8080/json/{}", self.host, request))
-            .query(&para)
-            .send()?;
+    fn dropped(&self) -> usize {
+        self.dropped.load(std::sync::atomic::Ordering::Relaxed)
+    }
 
-        let mut json: serde_json::Value = response.json()?;
+    fn panics(&self) -> usize {
+        self.panics.load(std::sync::atomic::Ordering::Relaxed)
+    }
 
-        // check if we got a response in right format
-        let ok_code = json
-            .get("ok")
-            .ok_or("No ok in Json response")?
-            .as_bool()
-            .ok_or("No boolean ok code")?;
-
-        // check if the ok code is false
-        if !ok_code {
-            // get the error message
-            let msg = json
-                .get("message")
-                .ok_or("No error message in Json response")?
-                .as_str()
-                .ok_or("Error message is not a string")?;
+    /// Keep the failure, which closed the subscription.
+    fn close(&self, error: Option<std::sync::Arc<Error>>) {
+        *self.error.lock().unwrap_or_else(|e| e.into_inner()) = error;
+    }
 
-            // if there are retries left, login again and retry
-            if retries > 0 {
-                self.login()?;
-                self.generic_request(request, parameter, retries - 1)?;
-            } else {
-                return Err(msg.into());
-            }
+    fn error(&self) -> Option<Error> {
+        self.error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .map(Error::Event)
+    }
+}
+
+impl Subscriber {
+    /// Send the result to the channel, returns false when the receiver is gone.
+    ///
+    /// When the buffer is full, the result is dropped and counted.
+    fn send(&self, res: Result<Event>) -> bool {
+        let full = || {
+            self.state.dropped_one();
+            true
+        };
+
+        match (&self.target, res) {
+            (Target::Channel(send), res) => match send.try_send(res) {
+                Err(std::sync::mpsc::TrySendError::Full(_)) => full(),
+                res => res.is_ok(),
+            },
+            (Target::Handler(send), Ok(event)) => match send.try_send((self.id, event)) {
+                Err(std::sync::mpsc::TrySendError::Full(_)) => full(),
+                res => res.is_ok(),
+            },
+            // the handlers only get the events
+            (Target::Handler(_), Err(_)) => true,
         }
+    }
+}
 
-        if !json
-            .get("ok")
-            .ok_or("No ok in Json response")?
-            .as_bool()
-            .ok_or("No boolean ok code")?
-        {
-            println!("Response error: {}", json);
-            return Err("Request failed, no ok code received".into());
+impl InnerAppartement {
+    fn get_value(&self, zone: usize, group: usize) -> Result<Value> {
+        self.zones
+            .iter()
+            .find(|z| z.id == zone)
+            .ok_or(Error::UnknownZone(zone))?
+            .groups
+            .iter()
+            .find(|g| g.id == group)
+            .map(|g| g.status.clone())
+            .ok_or(Error::UnknownGroup { zone, group })
+    }
+
+    fn set_value(&mut self, zone: usize, group: Option<usize>, value: Value) -> Result<()> {
+        let plan = plan_value(&self.zones, zone, group, &value)?;
+
+        for step in plan.steps {
+            match step {
+                Step::Action(action) => self.api.call_action(zone, action)?,
+                Step::ZoneLight(light) => self.api.set_zone_value(zone, Type::Light, light)?,
+                Step::Light(device, light) => self.api.set_light_device_value(device, light)?,
+                Step::Shadow(device, open, angle) => {
+                    self.api.set_shadow_device_open(device.clone(), open)?;
+                    self.api.set_shadow_device_angle(device, angle)?;
+                }
+                // the temperature is set for the actual operation mode
+                Step::Temperature(temperature) => {
+                    let status = self.api.get_temperature_control_status(zone)?;
+                    self.api.set_temperature_control_values(
+                        zone,
+                        &[(status.operation_mode, temperature)],
+                    )?;
+                }
+            }
         }
 
-        // take the result and return it
-        match json.get_mut("result") {
-            None => Ok(serde_json::json!(null)),
-            Some(j) => Ok(j.take()),
+        // we need to update the state manually, because no event will be triggered
+        if let Some(typ) = plan.status {
+            set_status(&mut self.zones, zone, group, typ, &value)?;
         }
+        if plan.climate {
+            self.update_climate(zone)?;
+        }
+
+        Ok(())
     }
 
-    /// Create a new event channel, which is listinging to events from the dss station.
-    pub fn new_event_channel(
-        &mut self,
-    ) -> Result<(
-        std::sync::mpsc::Receiver<Event>,
-        std::sync::Arc<std::sync::atomic::AtomicBool>,
-    )> {
-        // shareable boolean to stop threads
-        let thread_status = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    fn update_climate(&mut self, zone: usize) -> Result<()> {
+        // read back the new operation mode and nominal temperature of the zone
+        let status = self.api.get_temperature_control_status(zone)?;
+        apply_climate(&mut self.zones, zone, &status)
+    }
 
-        // subscribe to event
-        self.generic_request(
-            "event/subscribe",
-            Some(vec![("name", "callScene"), ("subscriptionID", "911")]),
-            2,
-        )?;
+    fn step_light(&mut self, zone: usize, action: Action) -> Result<()> {
+        self.api.call_action(zone, action)?;
 
-        // create a channel to send data from one to the other thread
-        let (send, recv) = std::sync::mpsc::channel();
+        // the brightness after the step is unknown, so we read it back for every light group
+        for grp in light_groups(&self.zones, zone)? {
+            if let Ok(value) = self.update_value(Value::Unknown, &Type::Light, zone, grp) {
+                set_status(&mut self.zones, zone, Some(grp), Type::Light, &value)?;
+            }
+        }
 
-        // this thread is just receiving data and directly reconnects
-        let this = self.clone();
-        let ts = thread_status.clone();
-        std::thread::spawn(move || loop {
-            // listen for events at the server
-            let res = this.generic_request(
-                "event/get",
-                Some(vec![("timeout", "3000"), ("subscriptionID", "911")]),
-                2,
-            );
+        Ok(())
+    }
 
-            // check if the thread should be ended
-            if !ts.load(std::sync::atomic::Ordering::Relaxed) {
-                break;
+    /// Update the appartment with the event and return the events for the
+    /// channels, the failures are returned between them.
+    fn handle_event(&mut self, event: Event) -> Vec<Result<Event>> {
+        let mut results = vec![];
+
+        // the values could have changed while the subscription was lost
+        if let Event::Reconnected = event {
+            if let Err(e) = self.update_structure() {
+                results.push(Err(e));
             }
+        }
 
-            // we have no plan B when the sending fails
-            #[allow(unused_must_use)]
-            {
-                // send the result to the next thread
-                send.send(res);
+        // expand the events when necessary
+        let expanded = match expand_event(&self.zones, event) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                results.push(Err(e));
+                return results;
             }
-        });
+        };
 
-        // create a channel to send the event to the receiver
-        let (inp, out) = std::sync::mpsc::channel();
+        for event in expanded {
+            // update the event value for shadow etc.
+            match self.update_event_value(event) {
+                Ok(event) => {
+                    apply_event(&mut self.zones, &event);
+                    results.push(Ok(event));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
 
-        // this thread is processing the data and calls the event handler
-        let this = self.clone();
-        let ts = thread_status.clone();
-        std::thread::spawn(move || loop {
-            // we have no plan B when an error occours
-            #[allow(unused_must_use)]
-            {
-                // receive from the channel and continue if no channel recv error occoured
-                let res = recv.recv();
+        let saved = self.save_status();
+        if let Some(e) = cache_failure(&mut self.cache_failed, saved) {
+            results.push(Err(e));
+        }
 
-                // check if the thread should be ended
-                if !ts.load(std::sync::atomic::Ordering::Relaxed) {
-                    break;
+        results
+    }
+
+    fn update_event_value(&mut self, event: Event) -> Result<Event> {
+        // only scene calls need an update of the value
+        let mut event = match event {
+            Event::CallScene(e) => e,
+            e => return Ok(e),
+        };
+
+        // update the value
+        event.value = self.update_value(event.value, &event.typ, event.zone, event.group)?;
+        Ok(Event::CallScene(event))
+    }
+
+    fn update_value(&self, value: Value, typ: &Type, zone: usize, group: usize) -> Result<Value> {
+        match value_source(&self.zones, value, typ, zone, group)? {
+            ValueSource::Known(value) => Ok(value),
+            ValueSource::Climate => Ok(self
+                .api
+                .get_temperature_control_status(zone)?
+                .nominal
+                .map(Value::Temperature)
+                .unwrap_or_default()),
+            ValueSource::Light(device) => {
+                Ok(Value::Light(self.api.get_light_device_value(&device)?))
+            }
+            ValueSource::Shadow(device) => Ok(Value::Shadow(
+                self.api.get_shadow_device_open(&device)?,
+                self.api.get_shadow_device_angle(&device)?,
+            )),
+        }
+    }
+
+    fn update_structure(&mut self) -> Result<()> {
+        // get the whole structure at once, instead of asking every single device
+        let structure = self.api.get_structure()?;
+
+        // without them the status is unknown and the devices are only sorted into group 0
+        let last_called = self.api.get_last_called_scenes().unwrap_or_default();
+        let scene_modes = self.api.get_scene_modes().unwrap_or_default();
+
+        let mut zones = build_zones(structure, &last_called, &scene_modes);
+
+        for zone in &mut zones {
+            // get the latest sensor values, a zone without sensors or their support has none
+            zone.sensors = self.api.get_zone_sensor_values(zone.id).unwrap_or_default();
+
+            // add the temperature control group, when the zone climate is controlled,
+            // a zone which rejects the status gets none
+            if has_climate(zone) {
+                if let Ok(status) = self.api.get_temperature_control_status(zone.id) {
+                    zone.groups.extend(climate_groups(zone.id, &status));
                 }
+            }
+        }
 
-                #[allow(clippy::bind_instead_of_map)]
-                res.and_then(|res| {
-                    // continue when no reqwest (http) error occoured
-                    res.and_then(|mut v| {
-                        // extract the json into an event array
-                        this.extract_events(&mut v).and_then(|es| {
-                            // for each event call the event handler function
-                            es.into_iter().for_each(|e| {
-                                let _tmp = inp.send(e);
-                            });
-                            Ok(())
-                        });
-                        Ok(())
-                    });
-                    Ok(())
-                });
+        self.zones = zones.clone();
+
+        for zone in &mut zones {
+            // get the output values of all devices within the zone at once
+            let outputs = if zone.types.contains(&Type::Shadow) {
+                self.api.get_zone_output_values(zone.id).unwrap_or_default()
+            } else {
+                vec![]
+            };
+
+            // for every shadow group get the real shadow values, when not delivered in bulk
+            for group in zone.groups.iter_mut().filter(|g| g.typ == Type::Shadow) {
+                let status = match shadow_status(&outputs, group) {
+                    Some(v) => Ok(v),
+                    None => self.update_value(group.status.clone(), &group.typ, zone.id, group.id),
+                };
+                if let Ok(v) = status {
+                    group.status = v;
+                }
             }
-        });
+        }
 
-        Ok((out, thread_status))
+        self.zones = zones;
+        self.save_status()
     }
 
-    fn extract_events(&self, json: &mut serde_json::Value) -> Result<Vec<Event>> {
-        let events = json
-            .get_mut("events")
-            .ok_or("No events available")?
-            .as_array_mut()
-            .take()
-            .ok_or("Events not in array")?;
-
-        let mut out = vec![];
+    fn consumption(&self) -> Result<Consumption> {
+        // get the latest values for all meters at once
+        let power = self
+            .api
+            .get_latest_meter_values(MeteringType::Consumption, MeteringUnit::W)?;
+        let energy = self
+            .api
+            .get_latest_meter_values(MeteringType::Energy, MeteringUnit::Wh)?;
 
-        for e in events {
-            let name = e
-                .get("name")
-                .ok_or("No name for event")?
-                .as_str()
-                .ok_or("Event name not a string")?
-                .to_string();
-            let props = e
-                .get_mut("properties")
-                .ok_or("No properties in event")?
-                .take();
+        Ok(build_consumption(self.api.get_circuits()?, &power, &energy))
+    }
 
-            let mut event: Event = serde_json::from_value(props)?;
-            event.name = name;
+    fn save_status(&self) -> Result<()> {
+        save_zones(&self.file, &self.zones)
+    }
+}
 
-            event.action = Action::from(event.clone());
+/// A single request, which is needed to set a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Call the scene of the action
+    Action(Action),
+    /// Dim all lights of the zone to the brightness
+    ZoneLight(f32),
+    /// Dim a light device to the brightness
+    Light(String, f32),
+    /// Move a shadow device to the position and angle
+    Shadow(String, f32, f32),
+    /// Set the nominal temperature of the actual operation mode
+    Temperature(f32),
+}
 
-            event.group = Group::group_id_from_scene_id(event.scene);
+/// The requests to set a value and how the status changes afterwards.
+#[derive(Debug, Default)]
+struct ValuePlan {
+    steps: Vec<Step>,
+    /// The groups of this type get the value as status, because no event will be triggered
+    status: Option<Type>,
+    /// The climate of the zone needs to be read back
+    climate: bool,
+}
 
-            event.value = Value::from_action(event.action.clone(), event.group);
+/// Decide which requests are needed to set the value of a group or the whole zone.
+fn plan_value(
+    zones: &[Zone],
+    zone: usize,
+    group: Option<usize>,
+    value: &Value,
+) -> Result<ValuePlan> {
+    let mut plan = ValuePlan::default();
+
+    // the devices of the type within the group or the whole zone
+    let devices = |typ: Type, device_type: DeviceType| -> Result<Vec<String>> {
+        let groups = &zones
+            .iter()
+            .find(|z| z.id == zone)
+            .ok_or(Error::UnknownZone(zone))?
+            .groups;
+        let groups: Vec<&Group> = match group {
+            Some(grp) => vec![groups
+                .iter()
+                .find(|g| g.id == grp && g.typ == typ)
+                .ok_or(Error::UnknownGroup { zone, group: grp })?],
+            None => groups.iter().collect(),
+        };
 
-            out.push(event);
+        Ok(groups
+            .iter()
+            .flat_map(|g| g.devices.iter())
+            .filter(|d| d.device_type == device_type)
+            .map(|d| d.id.clone())
+            .collect())
+    };
+
+    match *value {
+        // depending on the value we turn the light on or off
+        Value::Light(light) if light <= 0.0 => {
+            let action = group.map_or(Action::AllLightOff, Action::LightOff);
+            plan.steps.push(Step::Action(action));
+        }
+        Value::Light(light) if light >= 1.0 => {
+            let action = group.map_or(Action::AllLightOn, Action::LightOn);
+            plan.steps.push(Step::Action(action));
+        }
+        // a specific brightness can't be set over a scene, so the lights are dimmed
+        Value::Light(light) => {
+            match group {
+                Some(_) => plan.steps.extend(
+                    devices(Type::Light, DeviceType::Light)?
+                        .into_iter()
+                        .map(|d| Step::Light(d, light)),
+                ),
+                None => plan.steps.push(Step::ZoneLight(light)),
+            }
+            plan.status = Some(Type::Light);
+        }
+        // actions need to be performed for setting the shadow
+        Value::Shadow(open, angle) => {
+            if open <= 0.1 {
+                let action = group.map_or(Action::AllShadowUp, Action::ShadowUp);
+                plan.steps.push(Step::Action(action));
+            }
+            if open >= 0.9 && angle <= 0.1 {
+                let action = group.map_or(Action::AllShadowDown, Action::ShadowDown);
+                plan.steps.push(Step::Action(action));
+            } else {
+                // a specific position and angle can't be set over a scene
+                plan.steps.extend(
+                    devices(Type::Shadow, DeviceType::Shadow)?
+                        .into_iter()
+                        .map(|d| Step::Shadow(d, open, angle)),
+                );
+                plan.status = Some(Type::Shadow);
+            }
+        }
+        // the climate is always set for the whole zone, the operation modes are called as scenes
+        Value::HeatingMode(mode) => {
+            plan.steps.push(Step::Action(Action::HeatingMode(mode)));
+            plan.climate = true;
         }
+        Value::Temperature(temperature) => {
+            plan.steps.push(Step::Temperature(temperature));
+            plan.climate = true;
+        }
+        Value::Unknown => (),
+    }
 
-        Ok(out)
+    Ok(plan)
+}
+
+/// Set the status of a group or all groups of a type within a zone.
+fn set_status(
+    zones: &mut [Zone],
+    zone: usize,
+    group: Option<usize>,
+    typ: Type,
+    value: &Value,
+) -> Result<()> {
+    zones
+        .iter_mut()
+        .find(|z| z.id == zone)
+        .ok_or(Error::UnknownZone(zone))?
+        .groups
+        .iter_mut()
+        .filter(|g| g.typ == typ && group.is_none_or(|grp| g.id == grp))
+        .for_each(|g| g.status = value.clone());
+
+    Ok(())
+}
+
+/// Update the climate groups of a zone with it's temperature control status.
+fn apply_climate(zones: &mut [Zone], zone: usize, status: &TemperatureControlStatus) -> Result<()> {
+    for group in climate_groups(zone, status) {
+        set_status(zones, zone, None, group.typ, &group.status)?;
     }
+    Ok(())
+}
 
-    /// Receive the appartement name.
-    pub fn get_appartement_name(&self) -> Result<String> {
-        // extract the name
-        Ok(self
-            .generic_request("apartment/getName", None, 2)?
-            .get("result")
-            .ok_or("No result in Json response")?
-            .get("name")
-            .ok_or("No name in Json response")?
-            .as_str()
-            .ok_or("Name is not a String")?
-            .to_string())
+/// The ids of the light groups within a zone.
+fn light_groups(zones: &[Zone], zone: usize) -> Result<Vec<usize>> {
+    Ok(zones
+        .iter()
+        .find(|z| z.id == zone)
+        .ok_or(Error::UnknownZone(zone))?
+        .groups
+        .iter()
+        .filter(|g| g.typ == Type::Light)
+        .map(|g| g.id)
+        .collect())
+}
+
+/// Where the unknown value of a group is read from.
+#[derive(Debug, Clone, PartialEq)]
+enum ValueSource {
+    /// The value is already known
+    Known(Value),
+    /// The nominal temperature of the zone
+    Climate,
+    /// The brightness of the light device
+    Light(String),
+    /// The position and angle of the shadow device
+    Shadow(String),
+}
+
+/// Decide where the value of a group is read from, when it's unknown.
+fn value_source(
+    zones: &[Zone],
+    value: Value,
+    typ: &Type,
+    zone: usize,
+    group: usize,
+) -> Result<ValueSource> {
+    // when the value is already defined, the event is already updated
+    if value != Value::Unknown {
+        return Ok(ValueSource::Known(value));
     }
 
-    /// Set the appartement name within the DSS.
-    pub fn set_appartement_name<S>(&self, new_name: S) -> Result<bool>
-    where
-        S: Into<String>,
-    {
-        // extract the name
-        Ok(self
-            .generic_request(
-                "apartment/getName",
-                Some(vec![("newName", &new_name.into())]),
-                2,
-            )?
-            .get("ok")
-            .ok_or("No ok in Json response")?
-            .as_bool()
-            .ok_or("No boolean ok code")?)
+    // the nominal temperature changes with the operation mode
+    let device_type = match typ {
+        Type::TemperatureControl => return Ok(ValueSource::Climate),
+        Type::Shadow => DeviceType::Shadow,
+        Type::Light => DeviceType::Light,
+        _ => return Ok(ValueSource::Known(value)),
+    };
+
+    // the first device of the group with the matching device type
+    let device = zones
+        .iter()
+        .find(|z| z.id == zone)
+        .ok_or(Error::UnknownZone(zone))?
+        .groups
+        .iter()
+        .find(|g| g.id == group && &g.typ == typ)
+        .ok_or(Error::UnknownGroup { zone, group })?
+        .devices
+        .iter()
+        .find(|d| d.device_type == device_type)
+        .ok_or(Error::NoDevices { zone, group })?
+        .id
+        .clone();
+
+    match device_type {
+        DeviceType::Shadow => Ok(ValueSource::Shadow(device)),
+        _ => Ok(ValueSource::Light(device)),
     }
+}
 
-    /// Request all zones from the DSS system.
-    pub fn get_zones(&self) -> Result<Vec<Zone>> {
-        let mut json = self.generic_request("apartment/getReachableGroups", None, 2)?;
+/// Whether the climate of the zone is controlled.
+fn has_climate(zone: &Zone) -> bool {
+    zone.types
+        .iter()
+        .any(|t| t == &Type::Heating || t == &Type::TemperatureControl)
+}
 
-        // unpack the zones
-        let json = json
-            .get_mut("zones")
-            .ok_or("No zones in Json response")?
-            .take();
+/// The shadow value of a group, when it's first device was delivered in the output values.
+fn shadow_status(outputs: &[DeviceOutputs], group: &Group) -> Option<Value> {
+    group
+        .devices
+        .first()
+        .and_then(|d| shadow_from_outputs(outputs, &d.id))
+}
 
-        // transform the data to the zones
-        Ok(serde_json::from_value(json)?)
+/// A failing structure file is only reported once, until it works again.
+fn cache_failure(failed: &mut bool, saved: Result<()>) -> Option<Error> {
+    match saved {
+        Ok(()) => {
+            *failed = false;
+            None
+        }
+        Err(e) if !*failed => {
+            *failed = true;
+            Some(e)
+        }
+        Err(_) => None,
     }
+}
 
-    /// Get the name of a specific zone from the DSS system.
-    pub fn get_zone_name(&self, id: usize) -> Result<String> {
-        let res = self.generic_request("zone/getName", Some(vec![("id", &id.to_string())]), 2)?;
+/// Build the zones with their scene groups and devices out of the appartment structure.
+fn build_zones(
+    structure: Vec<ZoneStructure>,
+    last_called: &[(usize, Type, usize)],
+    scene_modes: &[(String, SceneMode)],
+) -> Vec<Zone> {
+    structure
+        .into_iter()
+        .filter(|z| z.id != 0 && z.id != 65534)
+        .map(|zs| {
+            // only the types with devices are reachable
+            let types: Vec<Type> = zs
+                .groups
+                .iter()
+                .filter(|g| !g.devices.is_empty() && g.typ() != Type::Unknown)
+                .map(|g| g.typ())
+                .collect();
 
-        // unpack the name
-        let name = res
-            .get("name")
-            .ok_or("No name returned")?
-            .as_str()
-            .ok_or("No String value available")?;
+            let mut groups = vec![];
+            for g in zs.groups.iter().filter(|g| types.contains(&g.typ())) {
+                let typ = g.typ();
 
-        Ok(name.to_string())
+                // the climate groups are built from the temperature control status
+                if typ == Type::Heating || typ == Type::TemperatureControl {
+                    continue;
+                }
+
+                // convert the scenes to groups
+                let mut scene_groups = Group::from_scenes(&g.scenes, zs.id, &typ);
+
+                // the last called action for shadows are always wrong,
+                // so we set it directly to unknown
+                let action = if typ == Type::Shadow {
+                    Action::Unknown
+                } else {
+                    last_called
+                        .iter()
+                        .find(|(z, t, _)| *z == zs.id && t == &typ)
+                        .map(|(_, _, scene)| Action::new(typ.clone(), *scene))
+                        .unwrap_or_default()
+                };
+
+                for group in &mut scene_groups {
+                    // add the last called action for each scene group
+                    group.status = Value::from_action(action.clone(), group.id);
+
+                    // the group 0 contains all light and shadow devices,
+                    // the others only the ones which care about the scene group
+                    for device in zs.devices.iter().filter(|d| {
+                        (d.device_type == DeviceType::Light || d.device_type == DeviceType::Shadow)
+                            && d.button_type == typ
+                    }) {
+                        if group.id == 0
+                            || scene_modes.iter().any(|(id, m)| {
+                                id == &device.id && m.scene == group.id && !m.dont_care
+                            })
+                        {
+                            group.devices.push(device.clone());
+                        }
+                    }
+                }
+
+                groups.append(&mut scene_groups);
+            }
+
+            Zone {
+                id: zs.id,
+                name: zs.name,
+                types,
+                groups,
+                sensors: vec![],
+            }
+        })
+        .collect()
+}
+
+/// Match the latest meter values to the circuits and sum them up.
+fn build_consumption(
+    circuits: Vec<Circut>,
+    power: &[MeterValue],
+    energy: &[MeterValue],
+) -> Consumption {
+    let circuits: Vec<CircuitConsumption> = circuits
+        .into_iter()
+        .map(|c| CircuitConsumption {
+            power: power.iter().find(|v| v.id == c.id).map(|v| v.value),
+            energy: energy.iter().find(|v| v.id == c.id).map(|v| v.value),
+            circuit: c,
+        })
+        .collect();
+
+    // the sums are only known, when every circuit delivered it's value
+    Consumption {
+        power: circuits.iter().map(|c| c.power).sum(),
+        energy: circuits.iter().map(|c| c.energy).sum(),
+        circuits,
     }
+}
 
-    /// Receive all devices availble in the appartement.
-    pub fn get_devices(&self) -> Result<Vec<Device>> {
-        let res = self.generic_request("apartment/getDevices", None, 2)?;
+/// Build the climate groups of a zone out of it's temperature control status.
+///
+/// The nominal temperature is the status of the temperature control group and
+/// the operation mode the one of the heating group. A zone without control has none.
+fn climate_groups(zone: usize, status: &TemperatureControlStatus) -> Vec<Group> {
+    if status.control_mode == 0 {
+        return vec![];
+    }
 
-        Ok(serde_json::from_value(res)?)
+    let mut temperature = Group::new(0, zone, Type::TemperatureControl);
+    temperature.status = status.nominal.map(Value::Temperature).unwrap_or_default();
+
+    let mut heating = Group::new(0, zone, Type::Heating);
+    heating.status = Value::HeatingMode(status.operation_mode);
+
+    vec![temperature, heating]
+}
+
+/// Property query for the last called scenes of all zones and types.
+const LAST_CALLED_SCENES_QUERY: &str = "/apartment/zones/*(ZoneID)/groups/*(group,lastCalledScene)";
+
+/// Property query for the scene modes of all devices.
+const SCENE_MODES_QUERY: &str = concat!(
+    "/apartment/zones/*(ZoneID)/devices/*(dSID)/scenes/",
+    "*(sceneID,dontCare,localPrio,specialMode,flashMode,ledconIndex)"
+);
+
+/// Property query for the output values of all devices within a zone.
+fn zone_outputs_query(zone: usize) -> String {
+    format!(
+        "/apartment/zones/zone{}/devices/*(dSID)/outputValues/*(offset,value)",
+        zone
+    )
+}
+
+/// Read the shadow value of a device out of the output values.
+fn shadow_from_outputs(outputs: &[DeviceOutputs], device: &str) -> Option<Value> {
+    let outputs = outputs.iter().find(|o| o.device == device)?;
+    let open = outputs.value(2)? as f32 / 65535.0;
+    let angle = outputs.value(4)? as f32 / 65535.0;
+
+    // the opening is turned around
+    Some(Value::Shadow(1.0 - open, angle))
+}
+
+/// Check if the request failed, because the session token expired.
+fn session_expired(err: &Error) -> bool {
+    match err {
+        Error::Rejected(msg) => msg.to_lowercase().contains("not logged in"),
+        Error::Transport(e) => e.status().is_some_and(|s| {
+            s == reqwest::StatusCode::UNAUTHORIZED || s == reqwest::StatusCode::FORBIDDEN
+        }),
+        _ => false,
     }
+}
 
-    /// Request the scene mode for a specific device.
-    pub fn get_device_scene_mode<S>(&self, device: S, scene_id: usize) -> Result<SceneMode>
-    where
-        S: Into<String>,
-    {
-        let json = self.generic_request(
+/// Check if the event subscription got lost, like after a restart of
+/// the dss, so it has to be subscribed again.
+fn subscription_lost(err: &Error) -> bool {
+    match err {
+        Error::Rejected(msg) => msg.to_lowercase().contains("subscription"),
+        e => session_expired(e),
+    }
+}
+
+/// A rejected login is reported as authentication failure.
+fn authentication_error(err: Error) -> Error {
+    match err {
+        Error::Rejected(msg) => Error::Authentication(msg),
+        e => e,
+    }
+}
+
+/// The device a request is sent for, like for 'device/getOutputValue'.
+fn requested_device<'a>(request: &str, parameter: &[(&str, &'a str)]) -> Option<&'a str> {
+    if !request.starts_with("device/") {
+        return None;
+    }
+
+    parameter
+        .iter()
+        .find(|(name, _)| *name == "dsid")
+        .map(|(_, dsid)| *dsid)
+}
+
+/// A rejected request for a device is reported as unknown device, when
+/// the dss couldn't find the device.
+fn rejected_device_error(msg: String, device: &str) -> Error {
+    if msg.to_lowercase().contains("could not find device") {
+        Error::UnknownDevice(device.to_string())
+    } else {
+        Error::Rejected(msg)
+    }
+}
+
+/// Extract the '(zone, type, scene)' tuples from the last called scenes query.
+fn parse_last_called_scenes(res: &serde_json::Value) -> Result<Vec<(usize, Type, usize)>> {
+    let mut out = vec![];
+
+    for zone in res
+        .get("zones")
+        .and_then(|z| z.as_array())
+        .ok_or_else(|| Error::InvalidResponse("No zones in Json response".into()))?
+    {
+        let id = prop(zone, "ZoneID")
+            .ok_or_else(|| Error::InvalidResponse("No zone id in Json response".into()))?;
+
+        for group in zone
+            .get("groups")
+            .and_then(|g| g.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let (Some(typ), Some(scene)) =
+                (prop::<u8>(group, "group"), prop(group, "lastCalledScene"))
+            {
+                out.push((id, Type::from(typ), scene));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Extract the '(device, mode)' tuples from the scene modes query.
+fn parse_scene_modes(res: &serde_json::Value) -> Result<Vec<(String, SceneMode)>> {
+    let mut out = vec![];
+
+    for zone in res
+        .get("zones")
+        .and_then(|z| z.as_array())
+        .ok_or_else(|| Error::InvalidResponse("No zones in Json response".into()))?
+    {
+        for device in zone
+            .get("devices")
+            .and_then(|d| d.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let id: String = prop(device, "dSID")
+                .ok_or_else(|| Error::InvalidResponse("No device id in Json response".into()))?;
+
+            for scene in device
+                .get("scenes")
+                .and_then(|s| s.as_array())
+                .into_iter()
+                .flatten()
+            {
+                out.push((id.clone(), serde_json::from_value(scene.clone())?));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// The file to store the fingerprint of the dss certificate, next to the structure cache.
+fn fingerprint_file(file: &str) -> String {
+    format!("{}.fingerprint", file)
+}
+
+/// Load the zones from the structure file, when one is defined and exists.
+///
+/// An unreadable or invalid file is an error, it's not replaced silently.
+fn load_zones(file: &Option<String>) -> Result<Vec<Zone>> {
+    let content = match file.as_ref().map(std::fs::read_to_string) {
+        Some(Ok(content)) => content,
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => return Ok(vec![]),
+    };
+
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Save the zones to the structure file, when one is defined.
+fn save_zones(file: &Option<String>, zones: &[Zone]) -> Result<()> {
+    if let Some(file) = file {
+        let content = serde_json::to_string_pretty(zones)?;
+        std::fs::write(file, content)?;
+    }
+    Ok(())
+}
+
+/// Expand a single event to multiple ones, when it affects multiple groups.
+fn expand_event(zones: &[Zone], event: Event) -> Result<Vec<Event>> {
+    // only scene calls can affect multiple groups
+    let event = match event {
+        Event::CallScene(e) => e,
+        e => return Ok(vec![e]),
+    };
+
+    // when we have an action of type ShadowStepOpen
+    // it effects all groups of a zone and we create multiple events for it
+    // if we have multiple groups for this typ
+    if event.typ == Type::Shadow
+        && (event.action == Action::ShadowStepOpen || event.action == Action::ShadowStepClose)
+    {
+        // we get all group id's with Shadow within the event zone
+        let groups: Vec<usize> = zones
+            .iter()
+            .find(|z| z.id == event.zone)
+            .ok_or(Error::UnknownZone(event.zone))?
+            .groups
+            .iter()
+            .filter(|g| g.typ == Type::Shadow)
+            .map(|g| g.id)
+            .collect();
+
+        // for each shadow group we create a new event
+        return Ok(groups
+            .iter()
+            .map(|g| {
+                let mut e = event.clone();
+                e.group = *g;
+                Event::CallScene(e)
+            })
+            .collect());
+    }
+
+    Ok(vec![Event::CallScene(event)])
+}
+
+/// Update the status of the group or the sensor values the event belongs to.
+fn apply_event(zones: &mut [Zone], event: &Event) {
+    let event = match event {
+        Event::CallScene(e) => e,
+        // sensor events only update the sensor values of the zone
+        Event::ZoneSensorValue(e) => {
+            if let Some(z) = zones.iter_mut().find(|z| z.id == e.zone) {
+                z.update_sensor(e.sensor.clone());
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    zones.iter_mut().for_each(|z| {
+        // fine the right zone to the event
+        if z.id == event.zone {
+            z.groups.iter_mut().for_each(|g| {
+                // find the right group typ && id to update the value
+                if g.typ == event.typ && g.id == event.group {
+                    g.status = event.value.clone();
+                }
+
+                // the operation mode is the status of the heating group
+                if let Action::HeatingMode(mode) = event.action {
+                    if g.typ == Type::Heating {
+                        g.status = Value::HeatingMode(mode);
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Raw interface towards the DSS-Rest service. This is not intend to be used
+/// directly from a API consumer. It misses important status management and
+/// abstraction over the different devices.
+#[derive(Debug, Clone)]
+pub struct RawApi {
+    endpoint: Endpoint,
+    credentials: Credentials,
+    session: std::sync::Arc<Session>,
+}
+
+/// The session token of the dss, shared by all clones of an api.
+///
+/// When the token expires only one thread logs in again, the others
+/// wait for the new token instead of creating their own sessions.
+#[derive(Debug, Default)]
+struct Session {
+    token: std::sync::RwLock<String>,
+    login: std::sync::Mutex<()>,
+    #[cfg(feature = "async")]
+    login_async: tokio::sync::Mutex<()>,
+}
+
+/// The credentials which are used to (re-)login at the dss.
+#[derive(Debug, Clone)]
+enum Credentials {
+    Password { user: String, password: String },
+    ApplicationToken(String),
+}
+
+/// Where and how the requests are sent to the dss.
+///
+/// The http clients are created once and shared by all clones of the
+/// endpoint, so the connections are kept alive and reused.
+#[derive(Debug, Clone)]
+struct Endpoint {
+    base_url: String,
+    timeout: Option<std::time::Duration>,
+    headers: reqwest::header::HeaderMap,
+    verification: Verification,
+    backoff: Backoff,
+    clients: std::sync::Arc<Clients>,
+    health: std::sync::Arc<Health>,
+}
+
+/// The pooled http clients of an endpoint, created on first use.
+#[derive(Debug, Default)]
+struct Clients {
+    blocking: std::sync::Mutex<Option<reqwest::blocking::Client>>,
+    #[cfg(feature = "async")]
+    nonblocking: std::sync::Mutex<Option<reqwest::Client>>,
+}
+
+/// The status of the connection, shared by all clones of an endpoint.
+#[derive(Debug, Default)]
+struct Health(std::sync::Mutex<HealthState>);
+
+#[derive(Debug, Default)]
+struct HealthState {
+    status: Status,
+    watchers: Vec<Watcher>,
+}
+
+/// Where the changes of the status are delivered to.
+#[derive(Debug)]
+enum Watcher {
+    /// The channel of 'status_channel()'.
+    Channel(std::sync::mpsc::Sender<Status>),
+    /// The stream of the async 'status_stream()'.
+    #[cfg(feature = "async")]
+    Stream(tokio::sync::mpsc::UnboundedSender<Status>),
+}
+
+impl Watcher {
+    /// Send the status, returns false when the receiver is gone.
+    fn send(&self, status: Status) -> bool {
+        match self {
+            Watcher::Channel(send) => send.send(status).is_ok(),
+            #[cfg(feature = "async")]
+            Watcher::Stream(send) => send.send(status).is_ok(),
+        }
+    }
+}
+
+impl Health {
+    fn status(&self) -> Status {
+        self.lock().status.clone()
+    }
+
+    /// Get a channel, which receives the actual status and every change of the state.
+    fn watch(&self) -> std::sync::mpsc::Receiver<Status> {
+        let (send, recv) = std::sync::mpsc::channel();
+        self.add_watcher(Watcher::Channel(send));
+        recv
+    }
+
+    /// Get a tokio channel, which receives the actual status and every change of the state.
+    #[cfg(feature = "async")]
+    fn watch_async(&self) -> tokio::sync::mpsc::UnboundedReceiver<Status> {
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        self.add_watcher(Watcher::Stream(send));
+        recv
+    }
+
+    fn add_watcher(&self, watcher: Watcher) {
+        let mut health = self.lock();
+
+        if watcher.send(health.status.clone()) {
+            health.watchers.push(watcher);
+        }
+    }
+
+    /// Update the status and inform the watchers, when the state changed.
+    fn update<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Status),
+    {
+        let mut health = self.lock();
+        let state = health.status.state;
+        update(&mut health.status);
+
+        if health.status.state != state {
+            let status = health.status.clone();
+            health.watchers.retain(|w| w.send(status.clone()));
+        }
+    }
+
+    /// Update the status with the result of a request.
+    fn record<T>(&self, res: Result<T>) -> Result<T> {
+        match &res {
+            Ok(_) => self.update(|s| {
+                s.state = ConnectionState::Connected;
+                s.last_request = Some(std::time::SystemTime::now());
+            }),
+            Err(e) => self.failed(e),
+        }
+        res
+    }
+
+    /// A failed login or an unreachable dss changes the state,
+    /// other errors are answers of a reachable dss.
+    fn failed(&self, err: &Error) {
+        let state = match err {
+            Error::Authentication(_) => ConnectionState::AuthFailed,
+            e if e.is_transient() => ConnectionState::Disconnected,
+            _ => return,
+        };
+        self.update(|s| s.state = state);
+    }
+
+    /// The connection got lost and is restored.
+    fn retrying(&self) {
+        self.update(|s| {
+            if s.state == ConnectionState::Connected {
+                s.state = ConnectionState::Reconnecting;
+            }
+        });
+    }
+
+    fn event_received(&self) {
+        self.update(|s| s.last_event = Some(std::time::SystemTime::now()));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HealthState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The status of the connection to the dss.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Whether the dss is reachable and the login is valid.
+    pub state: ConnectionState,
+    /// The time of the last successful request.
+    pub last_request: Option<std::time::SystemTime>,
+    /// The time the last event was received.
+    pub last_event: Option<std::time::SystemTime>,
+}
+
+/// The state of the connection to the dss.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The last request succeeded.
+    Connected,
+    /// A request failed and is sent again, or the event subscription is restored.
+    Reconnecting,
+    /// The login with the credentials failed.
+    AuthFailed,
+    /// The dss can't be reached.
+    #[default]
+    Disconnected,
+}
+
+impl Endpoint {
+    fn new(host: &str, tls: &Tls) -> Result<Self> {
+        Ok(Endpoint {
+            base_url: base_url(host),
+            timeout: None,
+            headers: reqwest::header::HeaderMap::new(),
+            verification: tls.verification()?,
+            backoff: Backoff::default(),
+            clients: std::sync::Arc::default(),
+            health: std::sync::Arc::default(),
+        })
+    }
+
+    /// Run the request until it succeeds or fails with a not transient error,
+    /// waiting between the attempts as defined by the backoff.
+    ///
+    /// Requests, which are not 'idempotent', are only sent again when they
+    /// couldn't reach the dss at all, so a scene isn't called twice.
+    fn retry<T, F>(&self, idempotent: bool, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request() {
+                Err(e)
+                    if e.is_transient()
+                        && (idempotent || not_sent(&e))
+                        && attempt < self.backoff.retries =>
+                {
+                    self.health.retrying();
+                    std::thread::sleep(self.backoff.delay(attempt));
+                    attempt += 1;
+                }
+                res => return self.health.record(res),
+            }
+        }
+    }
+
+    /// The async version of 'retry'.
+    #[cfg(feature = "async")]
+    async fn retry_async<T, F, Fut>(&self, idempotent: bool, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(e)
+                    if e.is_transient()
+                        && (idempotent || not_sent(&e))
+                        && attempt < self.backoff.retries =>
+                {
+                    self.health.retrying();
+                    tokio::time::sleep(self.backoff.delay(attempt)).await;
+                    attempt += 1;
+                }
+                res => return self.health.record(res),
+            }
+        }
+    }
+
+    /// The full url of a json api request.
+    fn url(&self, request: &str) -> String {
+        format!("{}/json/{}", self.base_url, request)
+    }
+
+    /// The blocking client of the endpoint.
+    fn blocking_client(&self) -> Result<reqwest::blocking::Client> {
+        let mut client = self.clients.blocking.lock()?;
+
+        if let Some(c) = client.as_ref() {
+            return Ok(c.clone());
+        }
+
+        let c = self.build_blocking_client()?;
+        *client = Some(c.clone());
+        Ok(c)
+    }
+
+    fn build_blocking_client(&self) -> Result<reqwest::blocking::Client> {
+        let builder = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .default_headers(self.headers.clone());
+
+        let builder = match &self.verification {
+            Verification::Roots => builder,
+            Verification::Insecure => builder.danger_accept_invalid_certs(true),
+            Verification::Pinned(pin) => builder.use_preconfigured_tls(pin.client_config()),
+        };
+
+        Ok(builder.build()?)
+    }
+
+    /// The async client of the endpoint.
+    #[cfg(feature = "async")]
+    fn async_client(&self) -> Result<reqwest::Client> {
+        let mut client = self.clients.nonblocking.lock()?;
+
+        if let Some(c) = client.as_ref() {
+            return Ok(c.clone());
+        }
+
+        let c = self.build_async_client()?;
+        *client = Some(c.clone());
+        Ok(c)
+    }
+
+    #[cfg(feature = "async")]
+    fn build_async_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder().default_headers(self.headers.clone());
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let builder = match &self.verification {
+            Verification::Roots => builder,
+            Verification::Insecure => builder.danger_accept_invalid_certs(true),
+            Verification::Pinned(pin) => builder.use_preconfigured_tls(pin.client_config()),
+        };
+
+        Ok(builder.build()?)
+    }
+}
+
+/// How often and how long to wait, before a request which failed
+/// with a transient error, like a timeout, is sent again.
+///
+/// The delay doubles with every attempt, up to the maximum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// The number of retries after the first attempt failed.
+    pub retries: usize,
+    /// The delay before the first retry.
+    pub initial: std::time::Duration,
+    /// The longest delay between two attempts.
+    pub max: std::time::Duration,
+}
+
+impl Backoff {
+    /// Never retry a failed request.
+    pub fn none() -> Self {
+        Backoff {
+            retries: 0,
+            ..Backoff::default()
+        }
+    }
+
+    fn delay(&self, attempt: usize) -> std::time::Duration {
+        let factor = 2u32.saturating_pow(attempt as u32);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            retries: 3,
+            initial: std::time::Duration::from_millis(250),
+            max: std::time::Duration::from_secs(5),
+        }
+    }
+}
+
+/// How the certificate of the dss gets verified.
+///
+/// The dss comes with a self signed certificate, which can't be verified
+/// against the usual root certificates. Instead the certificate gets pinned,
+/// either directly or by it's SHA-256 fingerprint. By default the certificate
+/// is verified against the root certificates.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Tls {
+    /// Verify the certificate against the usual root certificates,
+    /// like for a reverse proxy with an official certificate.
+    #[default]
+    Roots,
+    /// Pin the given certificate of the dss in PEM or DER format.
+    Certificate(Vec<u8>),
+    /// Pin the SHA-256 fingerprint of the dss certificate as hex
+    /// string, like 'AB:CD:EF:...'.
+    Fingerprint(String),
+    /// Trust the certificate of the first connection and pin it afterwards.
+    /// The fingerprint is stored in the given file and used for all later
+    /// connections, also of other processes.
+    TrustOnFirstUse(String),
+    /// Accept every certificate, this is not secure and only meant
+    /// for testing purposes.
+    Insecure,
+}
+
+impl Tls {
+    fn verification(&self) -> Result<Verification> {
+        let (fingerprint, file) = match self {
+            Tls::Roots => return Ok(Verification::Roots),
+            Tls::Insecure => return Ok(Verification::Insecure),
+            Tls::Certificate(cert) => {
+                // a pem file is converted to der first
+                let der = if cert.starts_with(b"-----BEGIN") {
+                    rustls_pemfile::certs(&mut cert.as_slice())?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| Error::InvalidInput("No certificate in PEM found".into()))?
+                } else {
+                    cert.clone()
+                };
+                (Some(fingerprint(&der)), None)
+            }
+            Tls::Fingerprint(fp) => (Some(parse_fingerprint(fp)?), None),
+            Tls::TrustOnFirstUse(file) => {
+                // an already stored fingerprint is pinned directly
+                let fp = if std::path::Path::new(file).exists() {
+                    Some(parse_fingerprint(&std::fs::read_to_string(file)?)?)
+                } else {
+                    None
+                };
+                (fp, Some(file.clone()))
+            }
+        };
+
+        Ok(Verification::Pinned(std::sync::Arc::new(
+            PinnedCertificate {
+                fingerprint: std::sync::Mutex::new(fingerprint),
+                file,
+            },
+        )))
+    }
+}
+
+/// The verification of the dss certificate, created from the 'Tls' setting.
+#[derive(Debug, Clone)]
+enum Verification {
+    Roots,
+    Insecure,
+    Pinned(std::sync::Arc<PinnedCertificate>),
+}
+
+/// Verifies the certificate of the dss against a pinned fingerprint.
+///
+/// Without a fingerprint, the first certificate seen gets pinned.
+#[derive(Debug)]
+struct PinnedCertificate {
+    fingerprint: std::sync::Mutex<Option<[u8; 32]>>,
+    file: Option<String>,
+}
+
+impl PinnedCertificate {
+    fn client_config(self: &std::sync::Arc<Self>) -> rustls::ClientConfig {
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(self.clone())
+            .with_no_client_auth()
+    }
+}
+
+impl rustls::client::ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> std::result::Result<rustls::client::ServerCertVerified, rustls::Error> {
+        let actual = fingerprint(&end_entity.0);
+        let mut pinned = self
+            .fingerprint
+            .lock()
+            .map_err(|_| rustls::Error::General("Poison error".into()))?;
+
+        match *pinned {
+            Some(fp) if fp == actual => Ok(rustls::client::ServerCertVerified::assertion()),
+            Some(_) => Err(rustls::Error::General(format!(
+                "Certificate fingerprint {} doesn't match the pinned one",
+                format_fingerprint(&actual)
+            ))),
+            // trust on first use, remember the certificate for later connections
+            None => {
+                if let Some(file) = &self.file {
+                    std::fs::write(file, format_fingerprint(&actual))
+                        .map_err(|e| rustls::Error::General(e.to_string()))?;
+                }
+                *pinned = Some(actual);
+                Ok(rustls::client::ServerCertVerified::assertion())
+            }
+        }
+    }
+}
+
+/// The SHA-256 fingerprint of a DER encoded certificate.
+fn fingerprint(der: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+    sha2::Sha256::digest(der).into()
+}
+
+/// Format a fingerprint as hex string, like 'AB:CD:EF:...'.
+fn format_fingerprint(fp: &[u8; 32]) -> String {
+    fp.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// Parse a hex fingerprint, the bytes can be separated by colons or spaces.
+fn parse_fingerprint(s: &str) -> Result<[u8; 32]> {
+    let hex: String = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+
+    if hex.len() != 64 {
+        return Err(Error::InvalidInput(
+            "The fingerprint needs to be a SHA-256 hex string".into(),
+        ));
+    }
+
+    let mut out = [0; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| {
+            Error::InvalidInput("The fingerprint contains invalid hex characters".into())
+        })?;
+    }
+
+    Ok(out)
+}
+
+/// Builder for a 'RawApi' with a custom connection setup, like a
+/// reverse proxy with a path prefix, a timeout or additional headers.
+///
+/// ```no_run
+/// let api = dss::RawApi::builder("https://dss.example.com/proxy")
+///     .user("dssadmin", "secret")
+///     .timeout(std::time::Duration::from_secs(10))
+///     .header("Authorization", "Basic ZHNzOmRzcw==")
+///     .connect()
+///     .unwrap();
+///
+/// let appt = dss::Appartement::from_api(api).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawApiBuilder {
+    base_url: String,
+    timeout: Option<std::time::Duration>,
+    headers: Vec<(String, String)>,
+    credentials: Option<Credentials>,
+    tls: Tls,
+    backoff: Backoff,
+}
+
+impl RawApiBuilder {
+    /// Login with the user and password.
+    pub fn user<S>(mut self, user: S, password: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.credentials = Some(Credentials::Password {
+            user: user.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Login with an application token.
+    pub fn application_token<S>(mut self, application_token: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.credentials = Some(Credentials::ApplicationToken(application_token.into()));
+        self
+    }
+
+    /// Set the timeout of a single request, by default there is none.
+    ///
+    /// The event requests are waiting up to 3 seconds for new events,
+    /// so the timeout should be longer than that.
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how the certificate of the dss gets verified, by default
+    /// it's verified against the root certificates.
+    pub fn tls(mut self, tls: Tls) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Set how transient failures are retried, by default three times
+    /// starting with a delay of 250 milliseconds.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Add a header, which is sent with every request.
+    pub fn header<S>(mut self, name: S, value: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Connect to the dss and login with the given credentials.
+    pub fn connect(self) -> Result<RawApi> {
+        let (endpoint, credentials) = self.build()?;
+        RawApi::new(endpoint, credentials)
+    }
+
+    /// Connect to the dss with the async api and login with the given credentials.
+    #[cfg(feature = "async")]
+    pub async fn connect_async(self) -> Result<async_api::RawApi> {
+        let (endpoint, credentials) = self.build()?;
+        async_api::RawApi::new(endpoint, credentials).await
+    }
+
+    /// Request a new application token with the given user and password
+    /// and enable it, see 'RawApi::request_application_token()'.
+    pub fn request_application_token<S>(self, application_name: S) -> Result<String>
+    where
+        S: Into<String>,
+    {
+        let (endpoint, credentials) = self.build()?;
+
+        // request a new, not yet enabled application token
+        let application_token = RawApi::unauthenticated_request(
+            &endpoint,
+            "system/requestApplicationToken",
+            &[("applicationName", &application_name.into())],
+        )?
+        .get("applicationToken")
+        .ok_or_else(|| Error::InvalidResponse("No applicationToken in Json response".into()))?
+        .as_str()
+        .ok_or_else(|| Error::InvalidResponse("Application token is not a String".into()))?
+        .to_string();
+
+        // login with the password to enable the new token
+        let api = RawApi::new(endpoint, credentials)?;
+        api.generic_request(
+            "system/enableToken",
+            Some(vec![("applicationToken", &application_token)]),
+            2,
+        )?;
+
+        Ok(application_token)
+    }
+
+    fn build(self) -> Result<(Endpoint, Credentials)> {
+        let credentials = self
+            .credentials
+            .ok_or_else(|| Error::InvalidInput("No credentials given".into()))?;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| Error::InvalidInput(format!("Invalid header name {}", name)))?,
+                reqwest::header::HeaderValue::from_str(value).map_err(|_| {
+                    Error::InvalidInput(format!("Invalid header value for {}", name))
+                })?,
+            );
+        }
+
+        let endpoint = Endpoint {
+            base_url: base_url(&self.base_url),
+            timeout: self.timeout,
+            headers,
+            verification: self.tls.verification()?,
+            backoff: self.backoff,
+            clients: std::sync::Arc::default(),
+            health: std::sync::Arc::default(),
+        };
+
+        Ok((endpoint, credentials))
+    }
+}
+
+impl RawApi {
+    /// Create a builder to setup the connection in detail.
+    ///
+    /// The host can be a plain host name, which is reached over https
+    /// at port 8080 when no other port is given, or a full base url like
+    /// 'https://dss.example.com:443/dss'.
+    pub fn builder<S>(host: S) -> RawApiBuilder
+    where
+        S: Into<String>,
+    {
+        RawApiBuilder {
+            base_url: host.into(),
+            timeout: None,
+            headers: vec![],
+            credentials: None,
+            tls: Tls::default(),
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// Connect to the Digital Strom Server and try to login.
+    ///
+    /// The certificate of the dss is verified against the root certificates,
+    /// use 'RawApi::builder()' to pin the self signed one of the dss.
+    pub fn connect<S>(host: S, user: S, password: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        RawApi::new(
+            Endpoint::new(&host.into(), &Tls::default())?,
+            Credentials::Password {
+                user: user.into(),
+                password: password.into(),
+            },
+        )
+    }
+
+    /// Connect to the Digital Strom Server and login with an application token.
+    ///
+    /// The token needs to be requested and enabled once, for example
+    /// with the 'request_application_token()' function.
+    pub fn connect_token<S>(host: S, application_token: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        RawApi::new(
+            Endpoint::new(&host.into(), &Tls::default())?,
+            Credentials::ApplicationToken(application_token.into()),
+        )
+    }
+
+    fn new(endpoint: Endpoint, credentials: Credentials) -> Result<Self> {
+        let api = RawApi {
+            endpoint,
+            credentials,
+            session: std::sync::Arc::default(),
+        };
+
+        api.login()?;
+
+        Ok(api)
+    }
+
+    /// Request a new application token from the dss and enable it with
+    /// the given user and password.
+    ///
+    /// The returned token should be stored by the caller, to be used
+    /// with 'connect_token()' instead of the password afterwards.
+    pub fn request_application_token<S>(
+        host: S,
+        user: S,
+        password: S,
+        application_name: S,
+    ) -> Result<String>
+    where
+        S: Into<String>,
+    {
+        RawApi::builder(host)
+            .user(user, password)
+            .request_application_token(application_name)
+    }
+
+    /// Returns the application token, when the api was connected with one.
+    pub fn application_token(&self) -> Option<&str> {
+        match &self.credentials {
+            Credentials::ApplicationToken(t) => Some(t),
+            Credentials::Password { .. } => None,
+        }
+    }
+
+    fn get_token(&self) -> Result<String> {
+        Ok(self.session.token.read()?.clone())
+    }
+
+    fn set_token(&self, token: &str) -> Result<()> {
+        let mut t = self.session.token.write()?;
+        *t = token.into();
+        Ok(())
+    }
+
+    fn login(&self) -> Result<()> {
+        // login with the password or the application token
+        let json = self
+            .endpoint
+            .retry(true, || match &self.credentials {
+                Credentials::Password { user, password } => RawApi::unauthenticated_request(
+                    &self.endpoint,
+                    "system/login",
+                    &[("user", user), ("password", password)],
+                ),
+                Credentials::ApplicationToken(token) => RawApi::unauthenticated_request(
+                    &self.endpoint,
+                    "system/loginApplication",
+                    &[("loginToken", token)],
+                ),
+            })
+            .map_err(authentication_error)
+            .inspect_err(|e| self.endpoint.health.failed(e))?;
+
+        // extract the token
+        self.set_token(
+            json.get("token")
+                .ok_or_else(|| Error::InvalidResponse("No token in Json response".into()))?
+                .as_str()
+                .ok_or_else(|| Error::InvalidResponse("Token is not a String".into()))?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Login again, when the expired token is still the actual one.
+    ///
+    /// Threads which are waiting for the lock, while another thread
+    /// renews the session, use the new token afterwards.
+    fn renew_session(&self, expired: &str) -> Result<()> {
+        let _lock = self.session.login.lock()?;
+
+        if self.get_token()? != expired {
+            return Ok(());
+        }
+
+        self.login()
+    }
+
+    /// Request without a session token, used for the login requests.
+    ///
+    /// It returns the result json value of the response.
+    fn unauthenticated_request(
+        endpoint: &Endpoint,
+        request: &str,
+        parameter: &[(&str, &String)],
+    ) -> Result<serde_json::Value> {
+        // make the request
+        let response = endpoint
+            .blocking_client()?
+            .get(endpoint.url(request))
+            .query(parameter)
+            .send()?
+            .error_for_status()?;
+
+        // get the result as Json Value and check it
+        RawApi::unpack_response(response.json()?)
+    }
+
+    /// Check the ok code of a response and return the result json value of it.
+    fn unpack_response(mut json: serde_json::Value) -> Result<serde_json::Value> {
+        // check if the request was successful
+        if !json
+            .get("ok")
+            .ok_or_else(|| Error::InvalidResponse("No ok in Json response".into()))?
+            .as_bool()
+            .ok_or_else(|| Error::InvalidResponse("No boolean ok code".into()))?
+        {
+            return Err(Error::Rejected(
+                json.get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("Request failed, no ok code received")
+                    .into(),
+            ));
+        }
+
+        // take the result and return it
+        match json.get_mut("result") {
+            None => Ok(serde_json::json!(null)),
+            Some(j) => Ok(j.take()),
+        }
+    }
+
+    /// Make a single request and check the response.
+    fn request(&self, request: &str, parameter: &[(&str, &str)]) -> Result<serde_json::Value> {
+        let response = self
+            .endpoint
+            .blocking_client()?
+            .get(self.endpoint.url(request))
+            .query(parameter)
+            .send()?
+            .error_for_status()?;
+
+        RawApi::unpack_response(response.json()?)
+    }
+
+    /// The actual state of the connection to the dss, with the time of
+    /// the last successful request and the last received event.
+    pub fn status(&self) -> Status {
+        self.endpoint.health.status()
+    }
+
+    /// Get a channel, which receives the status when the state of the connection
+    /// changes, starting with the actual one.
+    pub fn status_channel(&self) -> std::sync::mpsc::Receiver<Status> {
+        self.endpoint.health.watch()
+    }
+
+    /// Generic requset function, which handles the token inserting/login,
+    /// the json parsing and success check.
+    ///
+    /// Transient failures of reading requests are retried with the backoff of
+    /// the connection, requests which change something only when the dss
+    /// couldn't be reached. When the session expired, the api logs in again and sends the request
+    /// again, up to 'retries' times.
+    ///
+    /// It returns a json value, dependet on the request.
+    pub fn generic_request<S>(
+        &self,
+        request: S,
+        parameter: Option<Vec<(&str, &str)>>,
+        retries: usize,
+    ) -> Result<serde_json::Value>
+    where
+        S: Into<String>,
+    {
+        let request = request.into();
+        let parameter = parameter.unwrap_or_default();
+        let mut retries = retries;
+
+        loop {
+            // Handle parameter and add token
+            let token = self.get_token()?;
+            let mut para = parameter.clone();
+            para.push(("token", &token));
+
+            match self
+                .endpoint
+                .retry(is_idempotent(&request), || self.request(&request, &para))
+            {
+                // login again and replay the request
+                Err(e) if session_expired(&e) && retries > 0 => {
+                    retries -= 1;
+                    self.renew_session(&token)?;
+                }
+                Err(Error::Rejected(msg)) => {
+                    // the dss rejects the requests for unknown devices as well
+                    let device = match requested_device(&request, &parameter) {
+                        Some(device) => device,
+                        None => return Err(Error::Rejected(msg)),
+                    };
+                    return Err(rejected_device_error(msg, device));
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Create a new event channel, which is listinging to events from the dss station.
+    ///
+    /// It subscribes to all the events defined in 'DEFAULT_EVENT_NAMES'.
+    pub fn new_event_channel(
+        &mut self,
+    ) -> Result<(
+        std::sync::mpsc::Receiver<Result<Event>>,
+        std::sync::Arc<std::sync::atomic::AtomicBool>,
+    )> {
+        self.new_event_channel_with(DEFAULT_EVENT_NAMES)
+    }
+
+    /// Create a new event channel, which is listinging to the events
+    /// with the given names from the dss station.
+    ///
+    /// A failed request is delivered as error, when the subscription got lost
+    /// it's followed by 'Event::Reconnected' once the subscription is restored.
+    /// When the login fails, the error is the last message and the channel
+    /// gets closed.
+    pub fn new_event_channel_with(
+        &mut self,
+        names: &[&str],
+    ) -> Result<(
+        std::sync::mpsc::Receiver<Result<Event>>,
+        std::sync::Arc<std::sync::atomic::AtomicBool>,
+    )> {
+        let (recv, subscription) = self.event_subscription(names)?;
+        Ok((recv, subscription.running))
+    }
+
+    /// Subscribe to the events like 'new_event_channel_with', but the
+    /// subscription can be closed without waiting for the dss to answer.
+    fn event_subscription(
+        &self,
+        names: &[&str],
+    ) -> Result<(std::sync::mpsc::Receiver<Result<Event>>, EventSubscription)> {
+        // shareable boolean to stop threads
+        let thread_status = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+
+        // subscribe to the events
+        let id = subscription_id();
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        self.subscribe(&id, &names)?;
+        let subscription = EventSubscription {
+            api: self.clone(),
+            id: id.clone(),
+            names: names.clone(),
+            running: thread_status.clone(),
+        };
+
+        // create a channel to send the event to the receiver
+        let (inp, out) = std::sync::mpsc::channel();
+
+        // this thread is receiving the events and subscribes again, when
+        // the subscription or the session got lost
+        let this = self.clone();
+        let ts = thread_status.clone();
+        std::thread::spawn(move || {
+            // the consecutive failures, to wait longer the longer the dss is unavailable
+            let mut failures = 0;
+
+            'receive: loop {
+                // listen for events at the server
+                let res = this
+                    .generic_request(
+                        "event/get",
+                        Some(vec![("timeout", "3000"), ("subscriptionID", &id)]),
+                        2,
+                    )
+                    .and_then(|mut v| RawApi::extract_events(&mut v));
+
+                // check if the thread should be ended
+                if !ts.load(std::sync::atomic::Ordering::Relaxed) {
+                    break;
+                }
+
+                let events = match res {
+                    Ok(events) => {
+                        failures = 0;
+                        if !events.is_empty() {
+                            this.endpoint.health.event_received();
+                        }
+                        events
+                    }
+                    Err(e) => {
+                        // without a login the subscription can't be restored
+                        let failed = matches!(e, Error::Authentication(_));
+                        let lost = subscription_lost(&e);
+                        if inp.send(Err(e)).is_err() || failed {
+                            break 'receive;
+                        }
+                        this.endpoint.health.retrying();
+
+                        // the subscription is still there, try again after a while
+                        if !lost {
+                            std::thread::sleep(this.endpoint.backoff.delay(failures));
+                            failures += 1;
+                            continue;
+                        }
+
+                        // subscribe again with backoff, until it works or the thread gets ended
+                        let mut attempt = 0;
+                        loop {
+                            match this.subscribe(&id, &names) {
+                                Ok(()) => break,
+                                Err(e @ Error::Authentication(_)) => {
+                                    let _ = inp.send(Err(e));
+                                    break 'receive;
+                                }
+                                Err(_) => {}
+                            }
+
+                            std::thread::sleep(this.endpoint.backoff.delay(attempt));
+                            attempt += 1;
+
+                            if !ts.load(std::sync::atomic::Ordering::Relaxed) {
+                                break 'receive;
+                            }
+                        }
+                        vec![Event::Reconnected]
+                    }
+                };
+
+                for e in events {
+                    if inp.send(Ok(e)).is_err() {
+                        break 'receive;
+                    }
+                }
+            }
+
+            // nobody is listening anymore, close the subscription
+            this.unsubscribe(&id, &names);
+        });
+
+        Ok((out, subscription))
+    }
+
+    /// Subscribe to the events with the given names.
+    fn subscribe(&self, id: &str, names: &[String]) -> Result<()> {
+        for name in names {
+            self.generic_request(
+                "event/subscribe",
+                Some(vec![("name", name), ("subscriptionID", id)]),
+                2,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Close the subscription of the events, the errors are ignored
+    /// because the dss drops old subscriptions anyway.
+    fn unsubscribe(&self, id: &str, names: &[String]) {
+        for name in names {
+            let _ = self.generic_request(
+                "event/unsubscribe",
+                Some(vec![("name", name), ("subscriptionID", id)]),
+                0,
+            );
+        }
+    }
+
+    fn extract_events(json: &mut serde_json::Value) -> Result<Vec<Event>> {
+        let events = json
+            .get_mut("events")
+            .ok_or_else(|| Error::InvalidResponse("No events available".into()))?
+            .as_array_mut()
+            .ok_or_else(|| Error::InvalidResponse("Events not in array".into()))?;
+
+        let mut out = vec![];
+
+        for e in events {
+            let name = e
+                .get("name")
+                .ok_or_else(|| Error::InvalidResponse("No name for event".into()))?
+                .as_str()
+                .ok_or_else(|| Error::InvalidResponse("Event name not a string".into()))?
+                .to_string();
+
+            out.push(Event::from_json(name, e.take()));
+        }
+
+        Ok(out)
+    }
+
+    /// Get the latest sensor values of a zone, like temperature or humidity.
+    pub fn get_zone_sensor_values(&self, zone: usize) -> Result<Vec<SensorValue>> {
+        let res = self.generic_request(
+            "zone/getSensorValues",
+            Some(vec![("id", &zone.to_string())]),
+            2,
+        )?;
+
+        SensorValue::from_zone_values(&res)
+    }
+
+    /// Get the actual value of a sensor from a single device.
+    pub fn get_device_sensor_value<S>(&self, device: S, sensor_index: usize) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self.generic_request(
+            "device/getSensorValue",
+            Some(vec![
+                ("dsid", &device.into()),
+                ("sensorIndex", &sensor_index.to_string()),
+            ]),
+            2,
+        )?;
+
+        // unpack the value
+        res.get("sensorValue")
+            .ok_or_else(|| Error::InvalidResponse("No sensor value returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Sensor value is not a number".into()))
+    }
+
+    /// Extract the raw output value of a 'device/getOutputValue' result.
+    fn unpack_output_value(res: &serde_json::Value, offset: u64) -> Result<u64> {
+        // check for the right offset
+        if res
+            .get("offset")
+            .ok_or_else(|| Error::InvalidResponse("No offset returnes".into()))?
+            .as_u64()
+            .ok_or_else(|| Error::InvalidResponse("The offset is not a number".into()))?
+            != offset
+        {
+            return Err(Error::InvalidResponse("Wrong offset returned".into()));
+        }
+
+        // extract the value
+        let value = res
+            .get("value")
+            .ok_or_else(|| Error::InvalidResponse("No value returnes".into()))?
+            .as_u64()
+            .ok_or_else(|| Error::InvalidResponse("The value is not a number".into()))?;
+
+        Ok(value)
+    }
+
+    /// Receive the appartement name.
+    pub fn get_appartement_name(&self) -> Result<String> {
+        // extract the name
+        Ok(self
+            .generic_request("apartment/getName", None, 2)?
+            .get("result")
+            .ok_or_else(|| Error::InvalidResponse("No result in Json response".into()))?
+            .get("name")
+            .ok_or_else(|| Error::InvalidResponse("No name in Json response".into()))?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("Name is not a String".into()))?
+            .to_string())
+    }
+
+    /// Set the appartement name within the DSS.
+    pub fn set_appartement_name<S>(&self, new_name: S) -> Result<bool>
+    where
+        S: Into<String>,
+    {
+        // extract the name
+        self.generic_request(
+            "apartment/getName",
+            Some(vec![("newName", &new_name.into())]),
+            2,
+        )?
+        .get("ok")
+        .ok_or_else(|| Error::InvalidResponse("No ok in Json response".into()))?
+        .as_bool()
+        .ok_or_else(|| Error::InvalidResponse("No boolean ok code".into()))
+    }
+
+    /// Request all zones from the DSS system.
+    pub fn get_zones(&self) -> Result<Vec<Zone>> {
+        let mut json = self.generic_request("apartment/getReachableGroups", None, 2)?;
+
+        // unpack the zones
+        let json = json
+            .get_mut("zones")
+            .ok_or_else(|| Error::InvalidResponse("No zones in Json response".into()))?
+            .take();
+
+        // transform the data to the zones
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Get the name of a specific zone from the DSS system.
+    pub fn get_zone_name(&self, id: usize) -> Result<String> {
+        let res = self.generic_request("zone/getName", Some(vec![("id", &id.to_string())]), 2)?;
+
+        // unpack the name
+        let name = res
+            .get("name")
+            .ok_or_else(|| Error::InvalidResponse("No name returned".into()))?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("No String value available".into()))?;
+
+        Ok(name.to_string())
+    }
+
+    /// Receive all devices availble in the appartement.
+    pub fn get_devices(&self) -> Result<Vec<Device>> {
+        let res = self.generic_request("apartment/getDevices", None, 2)?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Receive the complete structure of the appartement, with all zones,
+    /// their devices and reachable scenes, in a single request.
+    pub fn get_structure(&self) -> Result<Vec<ZoneStructure>> {
+        let res = self.generic_request("apartment/getStructure", None, 2)?;
+        ZoneStructure::from_structure(res)
+    }
+
+    /// Query values from the property tree of the dss, like
+    /// '/apartment/zones/*(ZoneID,name)'.
+    pub fn property_query<S>(&self, query: S) -> Result<serde_json::Value>
+    where
+        S: Into<String>,
+    {
+        self.generic_request("property/query", Some(vec![("query", &query.into())]), 2)
+    }
+
+    /// Query values from the property tree and deserialize them, the
+    /// wildcard levels of the query are delivered as arrays named like their parent.
+    ///
+    /// ```no_run
+    /// #[derive(serde::Deserialize)]
+    /// struct Zones {
+    ///     zones: Vec<Zone>,
+    /// }
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Zone {
+    ///     #[serde(rename = "ZoneID")]
+    ///     id: usize,
+    ///     name: String,
+    /// }
+    ///
+    /// let api = dss::RawApi::connect("url", "user", "password").unwrap();
+    /// let zones: Zones = api.property_query_as("/apartment/zones/*(ZoneID,name)").unwrap();
+    /// ```
+    pub fn property_query_as<T, S>(&self, query: S) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        S: Into<String>,
+    {
+        Ok(serde_json::from_value(self.property_query(query)?)?)
+    }
+
+    /// Get a string value from the property tree.
+    pub fn get_property_string<S>(&self, path: S) -> Result<String>
+    where
+        S: Into<String>,
+    {
+        Ok(self
+            .get_property("property/getString", path.into())?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a String".into()))?
+            .to_string())
+    }
+
+    /// Get an integer value from the property tree.
+    pub fn get_property_integer<S>(&self, path: S) -> Result<i64>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getInteger", path.into())?
+            .as_i64()
+            .ok_or_else(|| Error::InvalidResponse("Property is not an integer".into()))
+    }
+
+    /// Get a floating point value from the property tree.
+    pub fn get_property_floating<S>(&self, path: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getFloating", path.into())?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a floating point number".into()))
+    }
+
+    /// Get a boolean value from the property tree.
+    pub fn get_property_boolean<S>(&self, path: S) -> Result<bool>
+    where
+        S: Into<String>,
+    {
+        self.get_property("property/getBoolean", path.into())?
+            .as_bool()
+            .ok_or_else(|| Error::InvalidResponse("Property is not a boolean".into()))
+    }
+
+    /// Set a string value in the property tree.
+    pub fn set_property_string<S>(&self, path: S, value: S) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setString", path.into(), value.into())
+    }
+
+    /// Set an integer value in the property tree.
+    pub fn set_property_integer<S>(&self, path: S, value: i64) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setInteger", path.into(), value.to_string())
+    }
+
+    /// Set a floating point value in the property tree.
+    pub fn set_property_floating<S>(&self, path: S, value: f64) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setFloating", path.into(), value.to_string())
+    }
+
+    /// Set a boolean value in the property tree.
+    pub fn set_property_boolean<S>(&self, path: S, value: bool) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.set_property("property/setBoolean", path.into(), value.to_string())
+    }
+
+    /// List the child nodes of a property tree node.
+    pub fn get_property_children<S>(&self, path: S) -> Result<Vec<PropertyNode>>
+    where
+        S: Into<String>,
+    {
+        let res = self.generic_request(
+            "property/getChildren",
+            Some(vec![("path", &path.into())]),
+            2,
+        )?;
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    fn get_property(&self, request: &str, path: String) -> Result<serde_json::Value> {
+        let mut res = self.generic_request(request, Some(vec![("path", &path)]), 2)?;
+
+        Ok(res
+            .get_mut("value")
+            .ok_or_else(|| Error::InvalidResponse("No value in Json response".into()))?
+            .take())
+    }
+
+    fn set_property(&self, request: &str, path: String, value: String) -> Result<()> {
+        self.generic_request(request, Some(vec![("path", &path), ("value", &value)]), 2)?;
+        Ok(())
+    }
+
+    /// Get the raw output values of all devices within a zone with a single request.
+    pub fn get_zone_output_values(&self, zone: usize) -> Result<Vec<DeviceOutputs>> {
+        let res: ZoneOutputs = self.property_query_as(zone_outputs_query(zone))?;
+        Ok(res.devices)
+    }
+
+    /// Get the last called scene of every zone and type, as
+    /// '(zone, type, scene)' with a single request.
+    pub fn get_last_called_scenes(&self) -> Result<Vec<(usize, Type, usize)>> {
+        let res = self.property_query(LAST_CALLED_SCENES_QUERY)?;
+        parse_last_called_scenes(&res)
+    }
+
+    /// Get the scene modes of all devices, as '(device, mode)' with a single request.
+    pub fn get_scene_modes(&self) -> Result<Vec<(String, SceneMode)>> {
+        let res = self.property_query(SCENE_MODES_QUERY)?;
+        parse_scene_modes(&res)
+    }
+
+    /// Request the scene mode for a specific device.
+    pub fn get_device_scene_mode<S>(&self, device: S, scene_id: usize) -> Result<SceneMode>
+    where
+        S: Into<String>,
+    {
+        let json = self.generic_request(
             "device/getSceneMode",
             Some(vec![
                 ("dsid", &device.into()),
-                ("sceneID", &scene_id.to_string()),
+                ("sceneID", &scene_id.to_string()),
+            ]),
+            2,
+        )?;
+
+        // convert to SceneMode
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Get all available circuts
+    pub fn get_circuits(&self) -> Result<Vec<Circut>> {
+        let mut res = self.generic_request("apartment/getCircuits", None, 2)?;
+
+        let res = res
+            .get_mut("circuits")
+            .ok_or_else(|| Error::InvalidResponse("No circuits available".into()))?
+            .take();
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the actual power consumption of a circuit in W.
+    pub fn get_circuit_consumption<S>(&self, circuit: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self.generic_request(
+            "circuit/getConsumption",
+            Some(vec![("id", &circuit.into())]),
+            2,
+        )?;
+
+        // unpack the consumption
+        res.get("consumption")
+            .ok_or_else(|| Error::InvalidResponse("No consumption returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Consumption is not a number".into()))
+    }
+
+    /// Get the actual energy meter value of a circuit in Wh.
+    pub fn get_circuit_energy_meter_value<S>(&self, circuit: S) -> Result<f64>
+    where
+        S: Into<String>,
+    {
+        let res = self.generic_request(
+            "circuit/getEnergyMeterValue",
+            Some(vec![("id", &circuit.into())]),
+            2,
+        )?;
+
+        // unpack the meter value
+        res.get("meterValue")
+            .ok_or_else(|| Error::InvalidResponse("No meter value returned".into()))?
+            .as_f64()
+            .ok_or_else(|| Error::InvalidResponse("Meter value is not a number".into()))
+    }
+
+    /// Get the latest metering values of all meters in the appartment.
+    pub fn get_latest_meter_values(
+        &self,
+        typ: MeteringType,
+        unit: MeteringUnit,
+    ) -> Result<Vec<MeterValue>> {
+        let mut res = self.generic_request(
+            "metering/getLatest",
+            Some(vec![
+                ("from", ".meters(all)"),
+                ("type", typ.as_str()),
+                ("unit", unit.as_str()),
+            ]),
+            2,
+        )?;
+
+        // unpack the values
+        let res = res
+            .get_mut("values")
+            .ok_or_else(|| Error::InvalidResponse("No values returned".into()))?
+            .take();
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get the recorded metering values of a single meter.
+    ///
+    /// The resolution is given in seconds and needs to be supported by
+    /// the dss, like 1, 60, 900 or 86400. When no count is given, all
+    /// available values get returned.
+    pub fn get_meter_values<S>(
+        &self,
+        meter: S,
+        typ: MeteringType,
+        unit: MeteringUnit,
+        resolution: usize,
+        count: Option<usize>,
+    ) -> Result<Vec<MeterSample>>
+    where
+        S: Into<String>,
+    {
+        let meter = meter.into();
+        let resolution = resolution.to_string();
+        let count = count.map(|c| c.to_string());
+
+        let mut para = vec![
+            ("dsid", meter.as_str()),
+            ("type", typ.as_str()),
+            ("unit", unit.as_str()),
+            ("resolution", resolution.as_str()),
+        ];
+        if let Some(c) = &count {
+            para.push(("valueCount", c));
+        }
+
+        let mut res = self.generic_request("metering/getValues", Some(para), 2)?;
+
+        // unpack the values, which are delivered as [timestamp, value] pairs
+        let res = res
+            .get_mut("values")
+            .ok_or_else(|| Error::InvalidResponse("No values returned".into()))?
+            .take();
+        let values: Vec<(u64, f64)> = serde_json::from_value(res)?;
+
+        Ok(values
+            .into_iter()
+            .map(|(timestamp, value)| MeterSample { timestamp, value })
+            .collect())
+    }
+
+    /// Get all available scenes for a specific zone with a type.
+    pub fn get_scenes(&self, zone: usize, typ: Type) -> Result<Vec<usize>> {
+        // convert the enum to usize
+        let typ = typ as usize;
+
+        let mut json = self.generic_request(
+            "zone/getReachableScenes",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &typ.to_string()),
+            ]),
+            2,
+        )?;
+
+        // unpack the scenes
+        let json = json
+            .get_mut("reachableScenes")
+            .ok_or_else(|| Error::InvalidResponse("No scenes returned".into()))?
+            .take();
+
+        // convert to number array
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Return the last called scene for a zone.
+    pub fn get_last_called_scene(&self, zone: usize, typ: Type) -> Result<usize> {
+        // convert the enum to usize
+        let typ = typ as usize;
+
+        let res = self.generic_request(
+            "zone/getLastCalledScene",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &typ.to_string()),
+            ]),
+            2,
+        )?;
+
+        // unpack the scene
+        let number = res
+            .get("scene")
+            .ok_or_else(|| Error::InvalidResponse("No scene returned".into()))?
+            .as_u64()
+            .ok_or_else(|| Error::InvalidResponse("No scene number available".into()))?;
+
+        Ok(number as usize)
+    }
+
+    /// Trigger a scene for a specific zone and type in the dss system.
+    pub fn call_scene(&self, zone: usize, typ: Type, scene: usize) -> Result<()> {
+        // convert the enum to usize
+        let typ = typ as usize;
+
+        self.generic_request(
+            "zone/callScene",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &typ.to_string()),
+                ("sceneNumber", &scene.to_string()),
+            ]),
+            2,
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the name of a scene of a type within the zone.
+    pub fn get_scene_name(&self, zone: usize, typ: Type, scene: usize) -> Result<String> {
+        let res = self.generic_request(
+            "zone/sceneGetName",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("sceneNumber", &scene.to_string()),
+            ]),
+            2,
+        )?;
+
+        Ok(res
+            .get("name")
+            .ok_or_else(|| Error::InvalidResponse("No scene name returned".into()))?
+            .as_str()
+            .ok_or_else(|| Error::InvalidResponse("No scene name available".into()))?
+            .to_string())
+    }
+
+    /// Set the name of a scene of a type within the zone.
+    pub fn set_scene_name(&self, zone: usize, typ: Type, scene: usize, name: &str) -> Result<()> {
+        self.generic_request(
+            "zone/sceneSetName",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("sceneNumber", &scene.to_string()),
+                ("newName", name),
+            ]),
+            2,
+        )?;
+
+        Ok(())
+    }
+
+    /// Transforms a action to a scene call if possible and executes it
+    pub fn call_action(&self, zone: usize, action: Action) -> Result<()> {
+        // transform the action to a typ and scene
+        let (typ, scene) = action.to_scene_type().ok_or_else(|| {
+            Error::InvalidInput("Action can't be transformed to scene command".into())
+        })?;
+        self.call_scene(zone, typ, scene)
+    }
+
+    /// Set the output value of all devices of a type within a zone.
+    pub fn set_zone_value(&self, zone: usize, typ: Type, value: f32) -> Result<()> {
+        // transform to dss range
+        let value = (255.0 * value.clamp(0.0, 1.0)) as usize;
+
+        self.generic_request(
+            "zone/setValue",
+            Some(vec![
+                ("id", &zone.to_string()),
+                ("groupID", &(typ as usize).to_string()),
+                ("value", &value.to_string()),
+            ]),
+            2,
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the brightness of a single light device.
+    pub fn get_light_device_value<S>(&self, device: S) -> Result<f32>
+    where
+        S: Into<String>,
+    {
+        // make the request
+        let res = self.generic_request(
+            "device/getOutputValue",
+            Some(vec![("dsid", &device.into()), ("offset", "0")]),
+            2,
+        )?;
+
+        // get the procentage
+        Ok((RawApi::unpack_output_value(&res, 0)? as f32) / 255.0)
+    }
+
+    /// Set the brightness of a single light device.
+    pub fn set_light_device_value<S>(&self, device: S, value: f32) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        // transform to dss range
+        let value = (255.0 * value.clamp(0.0, 1.0)) as usize;
+
+        // make the request
+        self.generic_request(
+            "device/setOutputValue",
+            Some(vec![
+                ("dsid", &device.into()),
+                ("value", &value.to_string()),
+                ("offset", "0"),
             ]),
             2,
         )?;
 
-        // convert to SceneMode
-        Ok(serde_json::from_value(json)?)
+        Ok(())
     }
 
-    /// Get all available circuts
-    pub fn get_circuits(&self) -> Result<Vec<Circut>> {
-        let mut res = self.generic_request("apartment/getCircuits", None, 2)?;
-
-        let res = res
-            .get_mut("circuits")
-            .ok_or("No circuits available")?
-            .take();
+    /// Get the actual temperature control status of a zone, like the
+    /// operation mode and the nominal temperature.
+    pub fn get_temperature_control_status(&self, zone: usize) -> Result<TemperatureControlStatus> {
+        let res = self.generic_request(
+            "zone/getTemperatureControlStatus",
+            Some(vec![("id", &zone.to_string())]),
+            2,
+        )?;
 
         Ok(serde_json::from_value(res)?)
     }
 
-    /// Get all available scenes for a specific zone with a type.
-    pub fn get_scenes(&self, zone: usize, typ: Type) -> Result<Vec<usize>> {
-        // convert the enum to usize
-        let typ = typ as usize;
-
-        let mut json = self.generic_request(
-            "zone/getReachableScenes",
-            Some(vec![
-                ("id", &zone.to_string()),
-                ("groupID", &typ.to_string()),
-            ]),
+    /// Get the temperature control configuration of a zone.
+    pub fn get_temperature_control_config(&self, zone: usize) -> Result<TemperatureControlConfig> {
+        let res = self.generic_request(
+            "zone/getTemperatureControlConfig",
+            Some(vec![("id", &zone.to_string())]),
             2,
         )?;
 
-        // unpack the scenes
-        let json = json
-            .get_mut("reachableScenes")
-            .ok_or("No scenes returned")?
-            .take();
-
-        // convert to number array
-        Ok(serde_json::from_value(json)?)
+        Ok(serde_json::from_value(res)?)
     }
 
-    /// Return the last called scene for a zone.
-    pub fn get_last_called_scene(&self, zone: usize, typ: Type) -> Result<usize> {
-        // convert the enum to usize
-        let typ = typ as usize;
-
+    /// Get the nominal temperatures of a zone for every operation mode.
+    pub fn get_temperature_control_values(&self, zone: usize) -> Result<TemperatureControlValues> {
         let res = self.generic_request(
-            "zone/getLastCalledScene",
-            Some(vec![
-                ("id", &zone.to_string()),
-                ("groupID", &typ.to_string()),
-            ]),
+            "zone/getTemperatureControlValues",
+            Some(vec![("id", &zone.to_string())]),
             2,
         )?;
 
-        // unpack the scene
-        let number = res
-            .get("scene")
-            .ok_or("No scene returned")?
-            .as_u64()
-            .ok_or("No scene number available")?;
-
-        Ok(number as usize)
+        Ok(serde_json::from_value(res)?)
     }
 
-    /// Trigger a scene for a specific zone and type in the dss system.
-    pub fn call_scene(&self, zone: usize, typ: Type, scene: usize) -> Result<()> {
-        // convert the enum to usize
-        let typ = typ as usize;
+    /// Set the nominal temperatures of a zone for the given operation modes.
+    pub fn set_temperature_control_values(
+        &self,
+        zone: usize,
+        values: &[(HeatingMode, f32)],
+    ) -> Result<()> {
+        let zone = zone.to_string();
+        let values: Vec<(&str, String)> = values
+            .iter()
+            .map(|(mode, v)| (mode.as_key(), v.to_string()))
+            .collect();
 
-        self.generic_request(
-            "zone/callScene",
-            Some(vec![
-                ("id", &zone.to_string()),
-                ("groupID", &typ.to_string()),
-                ("sceneNumber", &scene.to_string()),
-            ]),
-            2,
-        )?;
+        let mut para = vec![("id", zone.as_str())];
+        para.extend(values.iter().map(|(k, v)| (*k, v.as_str())));
 
-        Ok(())
-    }
+        self.generic_request("zone/setTemperatureControlValues", Some(para), 2)?;
 
-    /// Transforms a action to a scene call if possible and executes it
-    pub fn call_action(&self, zone: usize, action: Action) -> Result<()> {
-        // transform the action to a typ and scene
-        let (typ, scene) = action
-            .to_scene_type()
-            .ok_or("Action can't be transformed to scene command")?;
-        self.call_scene(zone, typ, scene)
+        Ok(())
     }
 
     /// Get the opening status of a single shadow device and resturns it.
@@ -965,26 +3269,8 @@ impl RawApi {
             2,
         )?;
 
-        // check for the right offset
-        if res
-            .get("offset")
-            .ok_or("No offset returnes")?
-            .as_u64()
-            .ok_or("The offset is not a number")?
-            != 2
-        {
-            return Err(Error::from("Wrong offset returned"));
-        }
-
-        // extract the value
-        let value = res
-            .get("value")
-            .ok_or("No value returnes")?
-            .as_u64()
-            .ok_or("The value is not a number")?;
-
         // get the procentage
-        let value = (value as f32) / 65535.0;
+        let value = (RawApi::unpack_output_value(&res, 2)? as f32) / 65535.0;
 
         // turn the value around
         Ok(1.0 - value)
@@ -1033,26 +3319,8 @@ impl RawApi {
             2,
         )?;
 
-        // check for the right offset
-        if res
-            .get("offset")
-            .ok_or("No offset returnes")?
-            .as_u64()
-            .ok_or("The offset is not a number")?
-            != 4
-        {
-            return Err(Error::from("Wrong offset returned"));
-        }
-
-        // extract the value
-        let value = res
-            .get("value")
-            .ok_or("No value returnes")?
-            .as_u64()
-            .ok_or("The value is not a number")?;
-
         // get the procentage
-        let value = (value as f32) / 65535.0;
+        let value = (RawApi::unpack_output_value(&res, 4)? as f32) / 65535.0;
 
         Ok(value)
     }
@@ -1086,6 +3354,59 @@ impl RawApi {
     }
 }
 
+/// Create a new id for an event subscription.
+///
+/// The ids are unique within the process and start at a random
+/// offset, so they don't clash with the ones of other processes.
+pub(crate) fn subscription_id() -> String {
+    static OFFSET: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    static NEXT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+    let offset = OFFSET.get_or_init(|| {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        std::process::id().wrapping_mul(2_654_435_761) ^ nanos
+    });
+    let next = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    // the dss expects a positive signed integer
+    (offset.wrapping_add(next) & 0x7fff_ffff).max(1).to_string()
+}
+
+/// Build the base url of the dss json api for the given host.
+///
+/// A plain host is reached over https at the default port 8080, unless it
+/// contains a port. A host which already contains a scheme, like
+/// 'http://127.0.0.1:8000', is taken as it is.
+fn base_url(host: &str) -> String {
+    if host.contains("://") {
+        return host.trim_end_matches('/').to_string();
+    }
+
+    let host = host.trim_end_matches('/');
+    let (authority, path) = host.split_at(host.find('/').unwrap_or(host.len()));
+
+    // an ipv6 address needs brackets, the port follows behind them
+    let authority = if authority.matches(':').count() > 1 && !authority.starts_with('[') {
+        format!("[{}]", authority)
+    } else {
+        authority.to_string()
+    };
+    let has_port = authority.rsplit_once(':').is_some_and(|(addr, port)| {
+        !port.is_empty()
+            && port.chars().all(|c| c.is_ascii_digit())
+            && (!addr.contains(':') || addr.ends_with(']'))
+    });
+
+    if has_port {
+        format!("https://{}{}", authority, path)
+    } else {
+        format!("https://{}:8080{}", authority, path)
+    }
+}
+
 /// Takes a String input and tries to convert it to the needed
 /// format requested by the struct.
 fn from_str<'de, T, D>(deserializer: D) -> std::result::Result<T, D::Error>
@@ -1096,21 +3417,381 @@ where
 {
     use serde::Deserialize;
 
-    let s = String::deserialize(deserializer)?;
+    // the dss delivers the values as strings, serialized events contain numbers
+    let s = match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        v => v.to_string(),
+    };
     T::from_str(&s).map_err(serde::de::Error::custom)
 }
 
+/// The events the event channel subscribes to, when no other names are given.
+pub const DEFAULT_EVENT_NAMES: &[&str] = &[
+    "callScene",
+    "undoScene",
+    "buttonClick",
+    "deviceBinaryInputEvent",
+    "stateChange",
+    "zoneSensorValue",
+    "deviceSensorEvent",
+    "running",
+    "model_ready",
+];
+
+/// The event get fired by the digital strom server, whenever something
+/// happens within the appartment.
+///
+/// Every event kind of the dss has it's own variant. Events which are
+/// not known by this crate are delivered as 'Other' with the name and
+/// the raw json of the event, they are serialized as this json.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(remote = "Self", tag = "name", content = "properties")]
+pub enum Event {
+    #[serde(rename = "callScene")]
+    CallScene(SceneEvent),
+    #[serde(rename = "undoScene")]
+    UndoScene(SceneEvent),
+    #[serde(rename = "buttonClick")]
+    ButtonClick(ButtonClickEvent),
+    #[serde(rename = "deviceBinaryInputEvent")]
+    DeviceBinaryInput(BinaryInputEvent),
+    #[serde(rename = "stateChange")]
+    StateChange(StateChangeEvent),
+    #[serde(rename = "zoneSensorValue")]
+    ZoneSensorValue(ZoneSensorEvent),
+    #[serde(rename = "deviceSensorEvent")]
+    DeviceSensor(DeviceSensorEvent),
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "model_ready")]
+    ModelReady,
+    /// The subscription was lost, like after a restart of the dss and
+    /// has been restored. The events in between are missing.
+    #[serde(rename = "reconnected")]
+    Reconnected,
+    Other(String, serde_json::Value),
+}
+
+impl serde::Serialize for Event {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        match self {
+            // the unknown events keep their dss name and raw json
+            Event::Other(name, json) => match json.as_object() {
+                Some(obj) => {
+                    let mut obj = obj.clone();
+                    obj.insert("name".into(), name.as_str().into());
+                    obj.serialize(serializer)
+                }
+                None => {
+                    let mut s = serializer.serialize_struct("Event", 2)?;
+                    s.serialize_field("name", name)?;
+                    s.serialize_field("properties", json)?;
+                    s.end()
+                }
+            },
+            event => Event::serialize(event, serializer),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Event, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        let name = json.get("name").and_then(|n| n.as_str()).map(String::from);
+
+        match name {
+            Some(name)
+                if !DEFAULT_EVENT_NAMES.contains(&name.as_str()) && name != "reconnected" =>
+            {
+                Ok(Event::Other(name, json))
+            }
+            _ => Event::deserialize(json).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl Event {
+    /// Create an event from the json representation of the dss.
+    ///
+    /// When the event is unknown or the properties can't be read,
+    /// an 'Other' event is returned.
+    fn from_json(name: String, json: serde_json::Value) -> Event {
+        let props = json.get("properties").unwrap_or(&serde_json::Value::Null);
+        let source = json.get("source").unwrap_or(&serde_json::Value::Null);
+
+        let event = match name.as_ref() {
+            "callScene" => SceneEvent::from_props(props).map(Event::CallScene),
+            "undoScene" => SceneEvent::from_props(props).map(Event::UndoScene),
+            "buttonClick" => Some(Event::ButtonClick(ButtonClickEvent {
+                zone: source_zone(source),
+                device: source_device(source),
+                button_index: prop(props, "buttonIndex").unwrap_or_default(),
+                click_type: prop(props, "clickType").unwrap_or_default(),
+            })),
+            "deviceBinaryInputEvent" => Some(Event::DeviceBinaryInput(BinaryInputEvent {
+                zone: source_zone(source),
+                device: source_device(source),
+                index: prop(props, "inputIndex").unwrap_or_default(),
+                input_type: prop(props, "inputType").unwrap_or_default(),
+                state: prop(props, "inputState").unwrap_or_default(),
+            })),
+            "stateChange" => Some(Event::StateChange(StateChangeEvent {
+                name: prop(props, "statename").unwrap_or_default(),
+                state: prop(props, "state").unwrap_or_default(),
+                value: prop(props, "value").unwrap_or_default(),
+                old_value: prop(props, "oldvalue").unwrap_or_default(),
+            })),
+            "zoneSensorValue" => SensorValue::from_event(props).map(|sensor| {
+                Event::ZoneSensorValue(ZoneSensorEvent {
+                    zone: prop(props, "zoneID").unwrap_or_else(|| source_zone(source)),
+                    typ: prop(props, "groupID").unwrap_or_default(),
+                    device: prop(props, "originDSUID").unwrap_or_default(),
+                    sensor,
+                })
+            }),
+            "deviceSensorEvent" => Some(Event::DeviceSensor(DeviceSensorEvent {
+                zone: source_zone(source),
+                device: source_device(source),
+                event: prop(props, "sensorEvent").unwrap_or_default(),
+                index: prop(props, "sensorIndex"),
+            })),
+            "running" => Some(Event::Running),
+            "model_ready" => Some(Event::ModelReady),
+            _ => None,
+        };
+
+        event.unwrap_or(Event::Other(name, json))
+    }
+
+    /// The name of the event, as it's used by the dss.
+    pub fn name(&self) -> &str {
+        match self {
+            Event::CallScene(_) => "callScene",
+            Event::UndoScene(_) => "undoScene",
+            Event::ButtonClick(_) => "buttonClick",
+            Event::DeviceBinaryInput(_) => "deviceBinaryInputEvent",
+            Event::StateChange(_) => "stateChange",
+            Event::ZoneSensorValue(_) => "zoneSensorValue",
+            Event::DeviceSensor(_) => "deviceSensorEvent",
+            Event::Running => "running",
+            Event::ModelReady => "model_ready",
+            Event::Reconnected => "reconnected",
+            Event::Other(name, _) => name,
+        }
+    }
+
+    /// The zone the event belongs to, if it belongs to one.
+    pub fn zone(&self) -> Option<usize> {
+        match self {
+            Event::CallScene(e) | Event::UndoScene(e) => Some(e.zone),
+            Event::ButtonClick(e) => Some(e.zone),
+            Event::DeviceBinaryInput(e) => Some(e.zone),
+            Event::ZoneSensorValue(e) => Some(e.zone),
+            Event::DeviceSensor(e) => Some(e.zone),
+            _ => None,
+        }
+    }
+
+    /// The type the event belongs to, if it belongs to one.
+    pub fn typ(&self) -> Option<&Type> {
+        match self {
+            Event::CallScene(e) | Event::UndoScene(e) => Some(&e.typ),
+            Event::ZoneSensorValue(e) => Some(&e.typ),
+            _ => None,
+        }
+    }
+}
+
+/// The number of events, an event channel buffers by default.
+pub const EVENT_BUFFER: usize = 1024;
+
+/// Selects the events a channel receives, by their zone, type and name.
+///
+/// The 'Event::Reconnected' passes every filter.
+/// ```
+/// let filter = dss::EventFilter::zone(2).typ(dss::Type::Light).name("callScene");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    zone: Option<usize>,
+    typ: Option<Type>,
+    names: Vec<String>,
+}
+
+impl EventFilter {
+    /// A filter which passes all events.
+    pub fn all() -> Self {
+        EventFilter::default()
+    }
+
+    /// A filter which passes the events of the zone.
+    pub fn zone(zone: usize) -> Self {
+        EventFilter {
+            zone: Some(zone),
+            ..EventFilter::default()
+        }
+    }
+
+    /// Only pass the events of the type, like scene calls for the light.
+    pub fn typ(mut self, typ: Type) -> Self {
+        self.typ = Some(typ);
+        self
+    }
+
+    /// Only pass the events with this name, like 'callScene'.
+    ///
+    /// When called multiple times, the events with any of the names pass.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.names.push(name.into());
+        self
+    }
+
+    /// Check if the event passes the filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Event::Reconnected = event {
+            return true;
+        }
+
+        self.zone.is_none_or(|z| event.zone() == Some(z))
+            && self.typ.as_ref().is_none_or(|t| event.typ() == Some(t))
+            && (self.names.is_empty() || self.names.iter().any(|n| n == event.name()))
+    }
+}
+
+/// The receiving side of an event channel of the appartment.
+///
+/// It dereferences to the 'Receiver' of the events and unsubscribes
+/// the channel, when it gets dropped.
+///
+/// Failures of the subscription are received as 'Error::Event', the ones while
+/// updating the appartment as 'Error::EventApply', the channel keeps receiving
+/// afterwards. Only when the subscription can't be restored, the channel is
+/// closed after the error.
+#[derive(Debug)]
+pub struct EventReceiver {
+    recv: std::sync::mpsc::Receiver<Result<Event>>,
+    id: usize,
+    state: std::sync::Arc<SubscriberState>,
+    hub: std::sync::Arc<std::sync::Mutex<EventHub>>,
+}
+
+impl EventReceiver {
+    /// The number of events and failures, which were dropped so far
+    /// because the buffer of the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.state.dropped()
+    }
+
+    /// The failure, which closed the channel, like a failed login.
+    ///
+    /// It's kept even when it didn't fit into the buffer of the channel anymore.
+    pub fn error(&self) -> Option<Error> {
+        self.state.error()
+    }
+}
+
+impl std::ops::Deref for EventReceiver {
+    type Target = std::sync::mpsc::Receiver<Result<Event>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.recv
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        let mut hub = match self.hub.lock() {
+            Ok(hub) => hub,
+            Err(e) => e.into_inner(),
+        };
+        hub.remove(self.id);
+    }
+}
+
+/// A handler registered with 'Appartement::on()', which is unregistered
+/// when the subscription gets dropped.
+#[derive(Debug)]
+pub struct Subscription {
+    id: usize,
+    state: std::sync::Arc<SubscriberState>,
+    hub: std::sync::Arc<std::sync::Mutex<EventHub>>,
+}
+
+impl Subscription {
+    /// The number of events, which were dropped so far because the
+    /// handlers didn't keep up with them.
+    pub fn dropped(&self) -> usize {
+        self.state.dropped()
+    }
+
+    /// The number of events, for which the handler panicked. The panic
+    /// is printed by the panic hook and the handler keeps being called.
+    pub fn panics(&self) -> usize {
+        self.state.panics()
+    }
+
+    /// The failure, which closed the subscription and unregistered the handler.
+    ///
+    /// Once it's set, the handler isn't called anymore.
+    pub fn error(&self) -> Option<Error> {
+        self.state.error()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut hub = match self.hub.lock() {
+            Ok(hub) => hub,
+            Err(e) => e.into_inner(),
+        };
+        hub.remove(self.id);
+    }
+}
+
+/// Read a property of an event, the dss delivers them mostly as strings.
+fn prop<T>(props: &serde_json::Value, key: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+{
+    match props.get(key)? {
+        serde_json::Value::String(s) => s.parse().ok(),
+        v => v.to_string().parse().ok(),
+    }
+}
+
+/// Read the zone id from the source of an event.
+fn source_zone(source: &serde_json::Value) -> usize {
+    source
+        .get("zoneID")
+        .and_then(|z| z.as_u64())
+        .unwrap_or_default() as usize
+}
+
+/// Read the device id from the source of an event.
+fn source_device(source: &serde_json::Value) -> String {
+    source
+        .get("dsid")
+        .and_then(|d| d.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
 /// The event get fired by the digital strom server, whenever
-/// a scene was called.
+/// a scene was called or undone.
 ///
 /// A scene get's called when a switch is pressed in the appartment or
 /// a similar action get triggered. The direct set of
 /// shadow opennings or angles are getting not received.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct Event {
-    #[serde(default)]
-    pub name: String,
-
+pub struct SceneEvent {
     #[serde(rename = "zoneID", deserialize_with = "from_str")]
     pub zone: usize,
 
@@ -1120,13 +3801,13 @@ pub struct Event {
     #[serde(rename = "sceneID", deserialize_with = "from_str")]
     pub scene: usize,
 
-    #[serde(rename = "originToken")]
+    #[serde(default, rename = "originToken")]
     pub token: String,
 
-    #[serde(rename = "originDSUID")]
+    #[serde(default, rename = "originDSUID")]
     pub dsuid: String,
 
-    #[serde(rename = "callOrigin")]
+    #[serde(default, rename = "callOrigin")]
     pub origin: String,
 
     #[serde(default)]
@@ -1139,6 +3820,71 @@ pub struct Event {
     pub group: usize,
 }
 
+impl SceneEvent {
+    /// Create the scene event from the event properties and
+    /// calculate the action, group and value of it.
+    fn from_props(props: &serde_json::Value) -> Option<SceneEvent> {
+        let mut event: SceneEvent = serde_json::from_value(props.clone()).ok()?;
+
+        event.action = Action::new(event.typ.clone(), event.scene);
+        // the temperature control scenes are operation modes and not scene groups
+        event.group = if event.typ == Type::TemperatureControl {
+            0
+        } else {
+            Group::group_id_from_scene_id(event.scene)
+        };
+        event.value = Value::from_action(event.action.clone(), event.group);
+
+        Some(event)
+    }
+}
+
+/// A button of a device was clicked.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ButtonClickEvent {
+    pub zone: usize,
+    pub device: String,
+    pub button_index: usize,
+    pub click_type: usize,
+}
+
+/// A binary input of a device, like a window contact, changed it's state.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BinaryInputEvent {
+    pub zone: usize,
+    pub device: String,
+    pub index: usize,
+    pub input_type: usize,
+    pub state: usize,
+}
+
+/// A system or user defined state of the dss changed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StateChangeEvent {
+    pub name: String,
+    pub state: String,
+    pub value: String,
+    pub old_value: String,
+}
+
+/// A sensor value of a zone changed, like the temperature.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ZoneSensorEvent {
+    pub zone: usize,
+    pub typ: Type,
+    pub device: String,
+    pub sensor: SensorValue,
+}
+
+/// A sensor of a device raised an event.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceSensorEvent {
+    pub zone: usize,
+    pub device: String,
+    pub event: String,
+    pub index: Option<usize>,
+}
+
 /// A zone is like a room or sub-room in an appartment.
 /// It has a definable name and groups.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1150,12 +3896,29 @@ pub struct Zone {
     pub types: Vec<Type>,
     #[serde(default, rename = "dssGroups")]
     pub groups: Vec<Group>,
+    #[serde(default)]
+    pub sensors: Vec<SensorValue>,
+}
+
+impl Zone {
+    /// Get the latest value of a sensor type in this zone.
+    pub fn sensor(&self, typ: SensorType) -> Option<&SensorValue> {
+        self.sensors.iter().find(|s| s.typ == typ)
+    }
+
+    /// Set the latest value of a sensor, an older value of the same type is replaced.
+    fn update_sensor(&mut self, value: SensorValue) {
+        self.sensors.retain(|s| s.typ != value.typ);
+        self.sensors.push(value);
+    }
 }
 
 /// The type definition is used for a group to determine what it controlls
-#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, PartialEq, Debug, Clone)]
+#[derive(serde_repr::Serialize_repr, serde::Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(from = "u8")]
 #[repr(u8)]
 pub enum Type {
+    #[default]
     Unknown = 0,
     Light = 1,
     Shadow = 2,
@@ -1222,11 +3985,472 @@ impl std::fmt::Display for Type {
     }
 }
 
+/// The scenes of a zone or the whole apartment, with the numbers of the dss.
+///
+/// The numbers 0 to 63 are the presets and area scenes of a zone and type,
+/// the numbers above are the apartment wide scenes.
+#[derive(
+    serde_repr::Serialize_repr, serde::Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default,
+)]
+#[serde(from = "u8")]
+#[repr(u8)]
+pub enum Scene {
+    // the presets of the zone and the areas 1 to 4
+    Preset0 = 0,
+    Area1Off = 1,
+    Area2Off = 2,
+    Area3Off = 3,
+    Area4Off = 4,
+    Preset1 = 5,
+    Area1On = 6,
+    Area2On = 7,
+    Area3On = 8,
+    Area4On = 9,
+
+    // the stepping and special scenes
+    AreaStepContinue = 10,
+    Decrement = 11,
+    Increment = 12,
+    Minimum = 13,
+    Maximum = 14,
+    Stop = 15,
+
+    // the additional presets
+    Preset2 = 17,
+    Preset3 = 18,
+    Preset4 = 19,
+    Preset12 = 20,
+    Preset13 = 21,
+    Preset14 = 22,
+    Preset22 = 23,
+    Preset23 = 24,
+    Preset24 = 25,
+    Preset32 = 26,
+    Preset33 = 27,
+    Preset34 = 28,
+    Preset42 = 29,
+    Preset43 = 30,
+    Preset44 = 31,
+    Preset10 = 32,
+    Preset11 = 33,
+    Preset20 = 34,
+    Preset21 = 35,
+    Preset30 = 36,
+    Preset31 = 37,
+    Preset40 = 38,
+    Preset41 = 39,
+    AutoOff = 40,
+    Impulse = 41,
+
+    // the stepping of the areas
+    Area1Decrement = 42,
+    Area1Increment = 43,
+    Area2Decrement = 44,
+    Area2Increment = 45,
+    Area3Decrement = 46,
+    Area3Increment = 47,
+    Area4Decrement = 48,
+    Area4Increment = 49,
+
+    // the local push button and the stop of the areas
+    LocalOff = 50,
+    LocalOn = 51,
+    Area1Stop = 52,
+    Area2Stop = 53,
+    Area3Stop = 54,
+    Area4Stop = 55,
+    SunProtection = 56,
+
+    // the apartment wide scenes
+    AutoStandby = 64,
+    Panic = 65,
+    EnergyOverload = 66,
+    Standby = 67,
+    DeepOff = 68,
+    Sleeping = 69,
+    WakeUp = 70,
+    Present = 71,
+    Absent = 72,
+    DoorBell = 73,
+    Alarm = 74,
+    ZoneActive = 75,
+    Fire = 76,
+    Smoke = 77,
+    Water = 78,
+    Gas = 79,
+    #[default]
+    Unknown = 255,
+}
+
+impl Scene {
+    /// The area 1 to 4 of the scene, when it's an area scene.
+    pub fn area(&self) -> Option<usize> {
+        match self {
+            Scene::Area1Off
+            | Scene::Area1On
+            | Scene::Area1Decrement
+            | Scene::Area1Increment
+            | Scene::Area1Stop => Some(1),
+            Scene::Area2Off
+            | Scene::Area2On
+            | Scene::Area2Decrement
+            | Scene::Area2Increment
+            | Scene::Area2Stop => Some(2),
+            Scene::Area3Off
+            | Scene::Area3On
+            | Scene::Area3Decrement
+            | Scene::Area3Increment
+            | Scene::Area3Stop => Some(3),
+            Scene::Area4Off
+            | Scene::Area4On
+            | Scene::Area4Decrement
+            | Scene::Area4Increment
+            | Scene::Area4Stop => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Check if the scene is called for the whole apartment.
+    pub fn is_apartment(&self) -> bool {
+        *self != Scene::Unknown && *self as u8 >= 64
+    }
+}
+
+impl From<u8> for Scene {
+    /// Transform the scene number of the dss towards a Scene.
+    fn from(u: u8) -> Self {
+        match u {
+            0 => Scene::Preset0,
+            1 => Scene::Area1Off,
+            2 => Scene::Area2Off,
+            3 => Scene::Area3Off,
+            4 => Scene::Area4Off,
+            5 => Scene::Preset1,
+            6 => Scene::Area1On,
+            7 => Scene::Area2On,
+            8 => Scene::Area3On,
+            9 => Scene::Area4On,
+            10 => Scene::AreaStepContinue,
+            11 => Scene::Decrement,
+            12 => Scene::Increment,
+            13 => Scene::Minimum,
+            14 => Scene::Maximum,
+            15 => Scene::Stop,
+            17 => Scene::Preset2,
+            18 => Scene::Preset3,
+            19 => Scene::Preset4,
+            20 => Scene::Preset12,
+            21 => Scene::Preset13,
+            22 => Scene::Preset14,
+            23 => Scene::Preset22,
+            24 => Scene::Preset23,
+            25 => Scene::Preset24,
+            26 => Scene::Preset32,
+            27 => Scene::Preset33,
+            28 => Scene::Preset34,
+            29 => Scene::Preset42,
+            30 => Scene::Preset43,
+            31 => Scene::Preset44,
+            32 => Scene::Preset10,
+            33 => Scene::Preset11,
+            34 => Scene::Preset20,
+            35 => Scene::Preset21,
+            36 => Scene::Preset30,
+            37 => Scene::Preset31,
+            38 => Scene::Preset40,
+            39 => Scene::Preset41,
+            40 => Scene::AutoOff,
+            41 => Scene::Impulse,
+            42 => Scene::Area1Decrement,
+            43 => Scene::Area1Increment,
+            44 => Scene::Area2Decrement,
+            45 => Scene::Area2Increment,
+            46 => Scene::Area3Decrement,
+            47 => Scene::Area3Increment,
+            48 => Scene::Area4Decrement,
+            49 => Scene::Area4Increment,
+            50 => Scene::LocalOff,
+            51 => Scene::LocalOn,
+            52 => Scene::Area1Stop,
+            53 => Scene::Area2Stop,
+            54 => Scene::Area3Stop,
+            55 => Scene::Area4Stop,
+            56 => Scene::SunProtection,
+            64 => Scene::AutoStandby,
+            65 => Scene::Panic,
+            66 => Scene::EnergyOverload,
+            67 => Scene::Standby,
+            68 => Scene::DeepOff,
+            69 => Scene::Sleeping,
+            70 => Scene::WakeUp,
+            71 => Scene::Present,
+            72 => Scene::Absent,
+            73 => Scene::DoorBell,
+            74 => Scene::Alarm,
+            75 => Scene::ZoneActive,
+            76 => Scene::Fire,
+            77 => Scene::Smoke,
+            78 => Scene::Water,
+            79 => Scene::Gas,
+            _ => Scene::Unknown,
+        }
+    }
+}
+
+/// The scene is parsed from it's number or name, like '17', 'Preset 2' or 'deep-off'.
+impl std::str::FromStr for Scene {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Ok(u) = u8::from_str(s.trim()) {
+            return match Scene::from(u) {
+                Scene::Unknown => Err(Error::UnknownScene(s.to_string())),
+                scene => Ok(scene),
+            };
+        }
+
+        // compare the names without case, spaces and dashes
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let name = normalize(s);
+
+        (0..u8::MAX)
+            .map(Scene::from)
+            .find(|scene| *scene != Scene::Unknown && normalize(&scene.to_string()) == name)
+            .ok_or_else(|| Error::UnknownScene(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for Scene {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scene::Preset0 => write!(f, "Preset 0"),
+            Scene::Area1Off => write!(f, "Area 1 Off"),
+            Scene::Area2Off => write!(f, "Area 2 Off"),
+            Scene::Area3Off => write!(f, "Area 3 Off"),
+            Scene::Area4Off => write!(f, "Area 4 Off"),
+            Scene::Preset1 => write!(f, "Preset 1"),
+            Scene::Area1On => write!(f, "Area 1 On"),
+            Scene::Area2On => write!(f, "Area 2 On"),
+            Scene::Area3On => write!(f, "Area 3 On"),
+            Scene::Area4On => write!(f, "Area 4 On"),
+            Scene::AreaStepContinue => write!(f, "Area Step Continue"),
+            Scene::Decrement => write!(f, "Decrement"),
+            Scene::Increment => write!(f, "Increment"),
+            Scene::Minimum => write!(f, "Minimum"),
+            Scene::Maximum => write!(f, "Maximum"),
+            Scene::Stop => write!(f, "Stop"),
+            Scene::Preset2 => write!(f, "Preset 2"),
+            Scene::Preset3 => write!(f, "Preset 3"),
+            Scene::Preset4 => write!(f, "Preset 4"),
+            Scene::Preset12 => write!(f, "Preset 12"),
+            Scene::Preset13 => write!(f, "Preset 13"),
+            Scene::Preset14 => write!(f, "Preset 14"),
+            Scene::Preset22 => write!(f, "Preset 22"),
+            Scene::Preset23 => write!(f, "Preset 23"),
+            Scene::Preset24 => write!(f, "Preset 24"),
+            Scene::Preset32 => write!(f, "Preset 32"),
+            Scene::Preset33 => write!(f, "Preset 33"),
+            Scene::Preset34 => write!(f, "Preset 34"),
+            Scene::Preset42 => write!(f, "Preset 42"),
+            Scene::Preset43 => write!(f, "Preset 43"),
+            Scene::Preset44 => write!(f, "Preset 44"),
+            Scene::Preset10 => write!(f, "Preset 10"),
+            Scene::Preset11 => write!(f, "Preset 11"),
+            Scene::Preset20 => write!(f, "Preset 20"),
+            Scene::Preset21 => write!(f, "Preset 21"),
+            Scene::Preset30 => write!(f, "Preset 30"),
+            Scene::Preset31 => write!(f, "Preset 31"),
+            Scene::Preset40 => write!(f, "Preset 40"),
+            Scene::Preset41 => write!(f, "Preset 41"),
+            Scene::AutoOff => write!(f, "Auto-Off"),
+            Scene::Impulse => write!(f, "Impulse"),
+            Scene::Area1Decrement => write!(f, "Area 1 Decrement"),
+            Scene::Area1Increment => write!(f, "Area 1 Increment"),
+            Scene::Area2Decrement => write!(f, "Area 2 Decrement"),
+            Scene::Area2Increment => write!(f, "Area 2 Increment"),
+            Scene::Area3Decrement => write!(f, "Area 3 Decrement"),
+            Scene::Area3Increment => write!(f, "Area 3 Increment"),
+            Scene::Area4Decrement => write!(f, "Area 4 Decrement"),
+            Scene::Area4Increment => write!(f, "Area 4 Increment"),
+            Scene::LocalOff => write!(f, "Local Off"),
+            Scene::LocalOn => write!(f, "Local On"),
+            Scene::Area1Stop => write!(f, "Area 1 Stop"),
+            Scene::Area2Stop => write!(f, "Area 2 Stop"),
+            Scene::Area3Stop => write!(f, "Area 3 Stop"),
+            Scene::Area4Stop => write!(f, "Area 4 Stop"),
+            Scene::SunProtection => write!(f, "Sun Protection"),
+            Scene::AutoStandby => write!(f, "Auto-Standby"),
+            Scene::Panic => write!(f, "Panic"),
+            Scene::EnergyOverload => write!(f, "Energy Overload"),
+            Scene::Standby => write!(f, "Standby"),
+            Scene::DeepOff => write!(f, "Deep Off"),
+            Scene::Sleeping => write!(f, "Sleeping"),
+            Scene::WakeUp => write!(f, "Wake Up"),
+            Scene::Present => write!(f, "Present"),
+            Scene::Absent => write!(f, "Absent"),
+            Scene::DoorBell => write!(f, "Door Bell"),
+            Scene::Alarm => write!(f, "Alarm"),
+            Scene::ZoneActive => write!(f, "Zone Active"),
+            Scene::Fire => write!(f, "Fire"),
+            Scene::Smoke => write!(f, "Smoke"),
+            Scene::Water => write!(f, "Water"),
+            Scene::Gas => write!(f, "Gas"),
+            Scene::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// The type of a sensor value, like the indoor temperature or humidity.
+#[derive(serde_repr::Serialize_repr, serde::Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(from = "u8")]
+#[repr(u8)]
+pub enum SensorType {
+    Unknown = 0,
+    TemperatureIndoors = 9,
+    TemperatureOutdoors = 10,
+    BrightnessIndoors = 11,
+    BrightnessOutdoors = 12,
+    HumidityIndoors = 13,
+    HumidityOutdoors = 14,
+    AirPressure = 15,
+    WindSpeed = 18,
+    WindDirection = 19,
+    Precipitation = 20,
+    CO2Concentration = 21,
+    GustSpeed = 25,
+    RoomTemperatureSetpoint = 50,
+    RoomTemperatureControlVariable = 51,
+}
+
+impl From<u8> for SensorType {
+    /// Transform a unsigned integer representation towards a DSS SensorType.
+    fn from(u: u8) -> Self {
+        match u {
+            9 => SensorType::TemperatureIndoors,
+            10 => SensorType::TemperatureOutdoors,
+            11 => SensorType::BrightnessIndoors,
+            12 => SensorType::BrightnessOutdoors,
+            13 => SensorType::HumidityIndoors,
+            14 => SensorType::HumidityOutdoors,
+            15 => SensorType::AirPressure,
+            18 => SensorType::WindSpeed,
+            19 => SensorType::WindDirection,
+            20 => SensorType::Precipitation,
+            21 => SensorType::CO2Concentration,
+            25 => SensorType::GustSpeed,
+            50 => SensorType::RoomTemperatureSetpoint,
+            51 => SensorType::RoomTemperatureControlVariable,
+            _ => SensorType::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for SensorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SensorType::Unknown => write!(f, "Unknown"),
+            SensorType::TemperatureIndoors => write!(f, "Temperature indoors (°C)"),
+            SensorType::TemperatureOutdoors => write!(f, "Temperature outdoors (°C)"),
+            SensorType::BrightnessIndoors => write!(f, "Brightness indoors (lx)"),
+            SensorType::BrightnessOutdoors => write!(f, "Brightness outdoors (lx)"),
+            SensorType::HumidityIndoors => write!(f, "Humidity indoors (%)"),
+            SensorType::HumidityOutdoors => write!(f, "Humidity outdoors (%)"),
+            SensorType::AirPressure => write!(f, "Air pressure (hPa)"),
+            SensorType::WindSpeed => write!(f, "Wind speed (m/s)"),
+            SensorType::WindDirection => write!(f, "Wind direction (°)"),
+            SensorType::Precipitation => write!(f, "Precipitation (mm/m2)"),
+            SensorType::CO2Concentration => write!(f, "CO2 concentration (ppm)"),
+            SensorType::GustSpeed => write!(f, "Gust speed (m/s)"),
+            SensorType::RoomTemperatureSetpoint => write!(f, "Temperature setpoint (°C)"),
+            SensorType::RoomTemperatureControlVariable => {
+                write!(f, "Temperature control variable (%)")
+            }
+        }
+    }
+}
+
+/// A single sensor value, like the temperature of a zone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct SensorValue {
+    #[serde(rename = "type")]
+    pub typ: SensorType,
+    pub value: f64,
+    #[serde(default)]
+    pub time: String,
+}
+
+impl SensorValue {
+    /// Extract the sensor value from the properties of a sensor event.
+    fn from_event(props: &serde_json::Value) -> Option<SensorValue> {
+        // the properties are all delivered as strings
+        let typ = props.get("sensorType")?.as_str()?.parse::<u8>().ok()?;
+        let value = props
+            .get("sensorValueFloat")
+            .or_else(|| props.get("sensorValue"))?
+            .as_str()?
+            .parse::<f64>()
+            .ok()?;
+
+        Some(SensorValue {
+            typ: SensorType::from(typ),
+            value,
+            time: String::new(),
+        })
+    }
+
+    /// Extract the sensor values from a 'zone/getSensorValues' result.
+    ///
+    /// The values are delivered as objects like '{"TemperatureValue": 21.5,
+    /// "TemperatureValueTime": "..."}'.
+    fn from_zone_values(res: &serde_json::Value) -> Result<Vec<SensorValue>> {
+        let values = res
+            .get("values")
+            .ok_or_else(|| Error::InvalidResponse("No values returned".into()))?
+            .as_array()
+            .ok_or_else(|| Error::InvalidResponse("Values not in array".into()))?;
+
+        let mut out = vec![];
+        for value in values {
+            let value = value
+                .as_object()
+                .ok_or_else(|| Error::InvalidResponse("Value is not an object".into()))?;
+
+            for (key, typ) in &[
+                ("TemperatureValue", SensorType::TemperatureIndoors),
+                ("HumidityValue", SensorType::HumidityIndoors),
+                ("BrightnessValue", SensorType::BrightnessIndoors),
+                ("CO2concentrationValue", SensorType::CO2Concentration),
+            ] {
+                if let Some(v) = value.get(*key).and_then(|v| v.as_f64()) {
+                    out.push(SensorValue {
+                        typ: *typ,
+                        value: v,
+                        time: value
+                            .get(&format!("{}Time", key))
+                            .and_then(|t| t.as_str())
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
 /// An action defines what has happend to a specific group or what should happen.
-#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone, Default)]
 pub enum Action {
     AllLightOn,
     AllLightOff,
+    AllLightIncrement,
+    AllLightDecrement,
     LightOn(usize),
     LightOff(usize),
     AllShadowUp,
@@ -1239,6 +4463,8 @@ pub enum Action {
     ShadowStepClose,
     AllShadowSpecial1,
     AllShadowSpecial2,
+    HeatingMode(HeatingMode),
+    #[default]
     Unknown,
 }
 
@@ -1252,6 +4478,18 @@ impl Action {
             return Action::AllLightOn;
         }
 
+        if typ == Type::Light && scene == 11 {
+            return Action::AllLightDecrement;
+        }
+
+        if typ == Type::Light && scene == 12 {
+            return Action::AllLightIncrement;
+        }
+
+        if typ == Type::TemperatureControl && scene < 8 {
+            return Action::HeatingMode(HeatingMode::from(scene as u8));
+        }
+
         if typ == Type::Shadow && scene == 0 {
             return Action::AllShadowDown;
         }
@@ -1311,6 +4549,8 @@ impl Action {
         match self {
             Action::AllLightOff => Some((Type::Light, 0)),
             Action::AllLightOn => Some((Type::Light, 5)),
+            Action::AllLightDecrement => Some((Type::Light, 11)),
+            Action::AllLightIncrement => Some((Type::Light, 12)),
             Action::LightOff(v) => Some((Type::Light, *v)),
             Action::LightOn(v) => Some((Type::Light, v + 5)),
 
@@ -1326,29 +4566,71 @@ impl Action {
             Action::ShadowStepClose => Some((Type::Shadow, 42)),
             Action::ShadowStepOpen => Some((Type::Shadow, 43)),
 
+            Action::HeatingMode(m) => Some((Type::TemperatureControl, *m as usize)),
+
             Action::Unknown => None,
         }
     }
+
+    /// The name of the action without it's parameter, like 'light_on'.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::AllLightOn => "all_light_on",
+            Action::AllLightOff => "all_light_off",
+            Action::AllLightIncrement => "all_light_increment",
+            Action::AllLightDecrement => "all_light_decrement",
+            Action::LightOn(_) => "light_on",
+            Action::LightOff(_) => "light_off",
+            Action::AllShadowUp => "all_shadow_up",
+            Action::AllShadowDown => "all_shadow_down",
+            Action::ShadowUp(_) => "shadow_up",
+            Action::ShadowDown(_) => "shadow_down",
+            Action::AllShadowStop => "all_shadow_stop",
+            Action::ShadowStop(_) => "shadow_stop",
+            Action::ShadowStepOpen => "shadow_step_open",
+            Action::ShadowStepClose => "shadow_step_close",
+            Action::AllShadowSpecial1 => "all_shadow_special1",
+            Action::AllShadowSpecial2 => "all_shadow_special2",
+            Action::HeatingMode(_) => "heating_mode",
+            Action::Unknown => "unknown",
+        }
+    }
 }
 
-impl Default for Action {
-    fn default() -> Self {
-        Action::Unknown
+impl std::fmt::Display for Action {
+    /// The name with the parameter, like 'light_on(1)' or 'heating_mode(night)'.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::LightOn(v)
+            | Action::LightOff(v)
+            | Action::ShadowUp(v)
+            | Action::ShadowDown(v)
+            | Action::ShadowStop(v) => write!(f, "{}({})", self.name(), v),
+            Action::HeatingMode(m) => {
+                write!(f, "{}({})", self.name(), m.as_key().to_lowercase())
+            }
+            _ => write!(f, "{}", self.name()),
+        }
     }
 }
 
-impl From<Event> for Action {
-    fn from(e: Event) -> Self {
+impl From<SceneEvent> for Action {
+    fn from(e: SceneEvent) -> Self {
         Action::new(e.typ, e.scene)
     }
 }
 
 /// The Value objects describes which status a group has. It is also used to
 /// set the new status of a group.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Default)]
 pub enum Value {
     Light(f32),
     Shadow(f32, f32),
+    /// The nominal temperature of a zone in °C, the status of the temperature control group
+    Temperature(f32),
+    /// The operation mode of the zone temperature control, the status of the heating group
+    HeatingMode(HeatingMode),
+    #[default]
     Unknown,
 }
 
@@ -1368,9 +4650,8 @@ impl Value {
     }
 
     pub fn as_bool(&self) -> bool {
-        #[allow(clippy::neg_cmp_op_on_partial_ord)]
         match self {
-            Value::Light(v) => !(v < &0.5),
+            Value::Light(v) => v > &0.0,
             _ => false,
         }
     }
@@ -1378,14 +4659,255 @@ impl Value {
     pub fn get_shadow_open(&self) -> Result<f32> {
         match self {
             Value::Shadow(open, _) => Ok(*open),
-            _ => Err("No shadow value".into()),
+            _ => Err(Error::InvalidInput("No shadow value".into())),
         }
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Value::Unknown
+/// The operation mode of the temperature control of a zone.
+#[derive(
+    serde_repr::Serialize_repr, serde::Deserialize, PartialEq, Debug, Clone, Copy, Default,
+)]
+#[serde(from = "u8")]
+#[repr(u8)]
+pub enum HeatingMode {
+    #[default]
+    Off = 0,
+    Comfort = 1,
+    Economy = 2,
+    NotUsed = 3,
+    Night = 4,
+    Holiday = 5,
+    Cooling = 6,
+    CoolingOff = 7,
+}
+
+impl HeatingMode {
+    /// The parameter name of the mode, used to set the nominal temperature.
+    fn as_key(&self) -> &'static str {
+        match self {
+            HeatingMode::Off => "Off",
+            HeatingMode::Comfort => "Comfort",
+            HeatingMode::Economy => "Economy",
+            HeatingMode::NotUsed => "NotUsed",
+            HeatingMode::Night => "Night",
+            HeatingMode::Holiday => "Holiday",
+            HeatingMode::Cooling => "Cooling",
+            HeatingMode::CoolingOff => "CoolingOff",
+        }
+    }
+}
+
+impl From<u8> for HeatingMode {
+    /// Transform a unsigned integer representation towards a DSS HeatingMode.
+    fn from(u: u8) -> Self {
+        match u {
+            1 => HeatingMode::Comfort,
+            2 => HeatingMode::Economy,
+            3 => HeatingMode::NotUsed,
+            4 => HeatingMode::Night,
+            5 => HeatingMode::Holiday,
+            6 => HeatingMode::Cooling,
+            7 => HeatingMode::CoolingOff,
+            _ => HeatingMode::Off,
+        }
+    }
+}
+
+impl std::str::FromStr for HeatingMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_ref() {
+            "off" | "aus" => Ok(HeatingMode::Off),
+            "comfort" | "komfort" => Ok(HeatingMode::Comfort),
+            "economy" | "eco" | "sparen" => Ok(HeatingMode::Economy),
+            "notused" => Ok(HeatingMode::NotUsed),
+            "night" | "nacht" => Ok(HeatingMode::Night),
+            "holiday" | "urlaub" => Ok(HeatingMode::Holiday),
+            "cooling" | "kühlen" => Ok(HeatingMode::Cooling),
+            "coolingoff" => Ok(HeatingMode::CoolingOff),
+            _ => Err(Error::InvalidInput(format!("Unknown heating mode {}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for HeatingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_key())
+    }
+}
+
+/// The temperature control status of a zone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TemperatureControlStatus {
+    #[serde(rename = "ControlMode")]
+    pub control_mode: usize,
+    #[serde(default, rename = "ControlState")]
+    pub control_state: usize,
+    #[serde(default, rename = "OperationMode")]
+    pub operation_mode: HeatingMode,
+    /// The actual temperature in °C
+    #[serde(default, rename = "TemperatureValue")]
+    pub temperature: Option<f32>,
+    /// The nominal temperature in °C
+    #[serde(default, rename = "NominalValue")]
+    pub nominal: Option<f32>,
+    /// The control value of the heating in %
+    #[serde(default, rename = "ControlValue")]
+    pub control_value: Option<f32>,
+}
+
+/// The temperature control configuration of a zone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TemperatureControlConfig {
+    #[serde(rename = "ControlMode")]
+    pub control_mode: usize,
+    #[serde(default, rename = "ReferenceZone")]
+    pub reference_zone: Option<usize>,
+    #[serde(default, rename = "CtrlOffset")]
+    pub offset: Option<f32>,
+    #[serde(default, rename = "EmergencyValue")]
+    pub emergency_value: Option<f32>,
+    #[serde(default, rename = "CtrlKp")]
+    pub kp: Option<f32>,
+    #[serde(default, rename = "CtrlTs")]
+    pub ts: Option<f32>,
+    #[serde(default, rename = "CtrlTi")]
+    pub ti: Option<f32>,
+    #[serde(default, rename = "CtrlKd")]
+    pub kd: Option<f32>,
+    #[serde(default, rename = "CtrlImin")]
+    pub i_min: Option<f32>,
+    #[serde(default, rename = "CtrlImax")]
+    pub i_max: Option<f32>,
+    #[serde(default, rename = "CtrlYmin")]
+    pub y_min: Option<f32>,
+    #[serde(default, rename = "CtrlYmax")]
+    pub y_max: Option<f32>,
+    #[serde(default, rename = "CtrlAntiWindUp")]
+    pub anti_wind_up: Option<bool>,
+    #[serde(default, rename = "CtrlKeepFloorWarm")]
+    pub keep_floor_warm: Option<bool>,
+}
+
+/// The nominal temperatures in °C of a zone for every operation mode.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TemperatureControlValues {
+    #[serde(default, rename = "Off")]
+    pub off: Option<f32>,
+    #[serde(default, rename = "Comfort")]
+    pub comfort: Option<f32>,
+    #[serde(default, rename = "Economy")]
+    pub economy: Option<f32>,
+    #[serde(default, rename = "NotUsed")]
+    pub not_used: Option<f32>,
+    #[serde(default, rename = "Night")]
+    pub night: Option<f32>,
+    #[serde(default, rename = "Holiday")]
+    pub holiday: Option<f32>,
+    #[serde(default, rename = "Cooling")]
+    pub cooling: Option<f32>,
+    #[serde(default, rename = "CoolingOff")]
+    pub cooling_off: Option<f32>,
+}
+
+/// A node of the property tree.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PropertyNode {
+    pub name: String,
+    /// The value type of the node, like 'string', 'integer' or 'none' for inner nodes.
+    #[serde(rename = "type")]
+    pub typ: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ZoneOutputs {
+    #[serde(default)]
+    devices: Vec<DeviceOutputs>,
+}
+
+/// The raw output values of a device, read from the property tree.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceOutputs {
+    #[serde(rename = "dSID")]
+    pub device: String,
+    #[serde(default, rename = "outputValues")]
+    pub values: Vec<OutputValue>,
+}
+
+impl DeviceOutputs {
+    /// The raw value at the given offset.
+    pub fn value(&self, offset: u64) -> Option<u64> {
+        self.values
+            .iter()
+            .find(|v| v.offset == offset)
+            .map(|v| v.value)
+    }
+}
+
+/// A single raw output value of a device.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OutputValue {
+    pub offset: u64,
+    pub value: u64,
+}
+
+/// The structure of a zone, as delivered by 'apartment/getStructure'.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ZoneStructure {
+    pub id: usize,
+    pub name: String,
+    #[serde(default)]
+    pub devices: Vec<Device>,
+    #[serde(default)]
+    pub groups: Vec<GroupStructure>,
+}
+
+impl ZoneStructure {
+    fn from_structure(mut res: serde_json::Value) -> Result<Vec<ZoneStructure>> {
+        let zones = res
+            .get_mut("apartment")
+            .and_then(|a| a.get_mut("zones"))
+            .ok_or_else(|| Error::InvalidResponse("No zones in Json response".into()))?
+            .take();
+
+        let mut zones: Vec<ZoneStructure> = serde_json::from_value(zones)?;
+
+        // the devices are located in the zone they are listed in
+        for zone in &mut zones {
+            let id = zone.id;
+            zone.devices.iter_mut().for_each(|d| d.zone_id = id);
+        }
+
+        Ok(zones)
+    }
+}
+
+/// A group of a zone, as delivered by 'apartment/getStructure'.
+///
+/// The id of the standard groups is the 'Type' they control.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GroupStructure {
+    pub id: usize,
+    #[serde(default)]
+    pub name: String,
+    /// The scenes which are reachable within the group.
+    #[serde(default, rename = "activeBasicScenes")]
+    pub scenes: Vec<usize>,
+    /// The ids of the devices within the group.
+    #[serde(default)]
+    pub devices: Vec<String>,
+}
+
+impl GroupStructure {
+    /// The type the group controls.
+    pub fn typ(&self) -> Type {
+        if self.id > u8::MAX as usize {
+            return Type::Unknown;
+        }
+
+        Type::from(self.id as u8)
     }
 }
 
@@ -1394,7 +4916,7 @@ impl Default for Value {
 pub struct Device {
     pub id: String,
     pub name: String,
-    #[serde(rename = "zoneID")]
+    #[serde(default, rename = "zoneID")]
     pub zone_id: usize,
     #[serde(rename = "isPresent")]
     pub present: bool,
@@ -1487,6 +5009,104 @@ pub struct Circut {
     pub valid: bool,
 }
 
+/// The kind of values a meter can deliver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeteringType {
+    /// The actual power consumption.
+    Consumption,
+    /// The summed up energy.
+    Energy,
+}
+
+impl MeteringType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MeteringType::Consumption => "consumption",
+            MeteringType::Energy => "energy",
+        }
+    }
+}
+
+/// The unit the metering values get delivered in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeteringUnit {
+    W,
+    Wh,
+}
+
+impl MeteringUnit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MeteringUnit::W => "W",
+            MeteringUnit::Wh => "Wh",
+        }
+    }
+}
+
+/// The latest value of a single meter.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "MeterValueJson")]
+pub struct MeterValue {
+    #[serde(rename = "dsid")]
+    pub id: String,
+    pub value: f64,
+    pub date: String,
+}
+
+/// The meter value as delivered by the dss, which identifies the meter
+/// by the 'dsid', the 'dSUID' or both of them.
+#[derive(serde::Deserialize)]
+struct MeterValueJson {
+    dsid: Option<String>,
+    #[serde(rename = "dSUID")]
+    dsuid: Option<String>,
+    value: f64,
+    #[serde(default)]
+    date: String,
+}
+
+impl std::convert::TryFrom<MeterValueJson> for MeterValue {
+    type Error = String;
+
+    fn try_from(json: MeterValueJson) -> std::result::Result<Self, Self::Error> {
+        Ok(MeterValue {
+            id: json.dsid.or(json.dsuid).ok_or("Meter value without dsid")?,
+            value: json.value,
+            date: json.date,
+        })
+    }
+}
+
+/// A single recorded value of a meter.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MeterSample {
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    pub value: f64,
+}
+
+/// The consumption of the whole appartment and each circuit.
+///
+/// The sums are none, when a circuit didn't deliver it's value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Consumption {
+    /// Power consumption in W
+    pub power: Option<f64>,
+    /// Energy meter value in Wh
+    pub energy: Option<f64>,
+    pub circuits: Vec<CircuitConsumption>,
+}
+
+/// The consumption of a single circuit, none when the meter has no value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CircuitConsumption {
+    pub circuit: Circut,
+    /// Power consumption in W
+    pub power: Option<f64>,
+    /// Energy meter value in Wh
+    pub energy: Option<f64>,
+}
+
 /// Represents all special scene stats.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SceneMode {
@@ -1542,26 +5162,25 @@ impl Group {
     }
 
     pub fn from_scene(scene: usize, zone_id: usize, typ: &Type) -> Option<Group> {
-        // add the different scene groups if they exist
-        for x in 1..4 {
-            if scene == x {
-                return Some(Group::new(x, zone_id, typ.clone()));
-            }
-        }
-
-        // if no different scene groups availabe, we take the general one
-        if scene == 0 {
-            return Some(Group::new(0, zone_id, typ.clone()));
+        match Scene::from(scene.min(255) as u8) {
+            // if no different scene groups availabe, we take the general one
+            Scene::Preset0 => Some(Group::new(0, zone_id, typ.clone())),
+            // add the different scene groups if they exist
+            scene => scene.area().map(|a| Group::new(a, zone_id, typ.clone())),
         }
-
-        None
     }
 
     pub fn from_scenes(scenes: &[usize], zone_id: usize, typ: &Type) -> Vec<Group> {
-        let groups: Vec<Group> = scenes
+        // the on and off scenes of an area belong to the same group
+        let mut groups: Vec<Group> = vec![];
+        for group in scenes
             .iter()
             .filter_map(|s| Group::from_scene(*s, zone_id, typ))
-            .collect();
+        {
+            if !groups.iter().any(|g| g.id == group.id) {
+                groups.push(group);
+            }
+        }
 
         if groups.len() > 1 {
             return groups.into_iter().filter(|g| g.id > 0).collect();
@@ -1585,11 +5204,43 @@ impl Default for Group {
 
 /// DSS error type to collect all the avilable errors which can occour.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    Error(String),
-    SerdeJson(serde_json::Error),
-    Reqwest(reqwest::Error),
+    /// The login with the user and password or the application token failed.
+    Authentication(String),
+    /// The dss rejected the call, with the message of the server.
+    Rejected(String),
+    /// No zone with this id is known.
+    UnknownZone(usize),
+    /// No group with this id is known within the zone.
+    UnknownGroup {
+        zone: usize,
+        group: usize,
+    },
+    /// The dss doesn't know a device with this id.
+    UnknownDevice(String),
+    /// No scene with this name is known within the zone.
+    UnknownScene(String),
+    /// The group has no device, which could be asked for its value.
+    NoDevices {
+        zone: usize,
+        group: usize,
+    },
+    /// The response of the dss had not the expected shape.
+    InvalidResponse(String),
+    /// The given value or configuration can't be used.
+    InvalidInput(String),
+    /// The request couldn't be sent or no response was received in time.
+    Transport(reqwest::Error),
+    Json(serde_json::Error),
     Io(std::io::Error),
+    /// A thread panicked while holding the lock of the appartement.
+    Poisoned,
+    /// The event subscription failed, the error is shared by all event channels.
+    Event(std::sync::Arc<Error>),
+    /// An event couldn't be applied to the appartment, the error is shared
+    /// by all event channels.
+    EventApply(std::sync::Arc<Error>),
 }
 
 /// Short return type for the DSS Error
@@ -1598,44 +5249,106 @@ type Result<T> = std::result::Result<T, Error>;
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Error::Error(s) => write!(f, "{}", s),
-            Error::SerdeJson(ref e) => e.fmt(f),
-            Error::Reqwest(ref e) => e.fmt(f),
+            Error::Authentication(s) => write!(f, "Authentication failed: {}", s),
+            Error::Rejected(s) => write!(f, "Request rejected: {}", s),
+            Error::UnknownZone(zone) => write!(f, "Unknown zone {}", zone),
+            Error::UnknownGroup { zone, group } => {
+                write!(f, "Unknown group {} in zone {}", group, zone)
+            }
+            Error::UnknownDevice(device) => write!(f, "Unknown device {}", device),
+            Error::UnknownScene(scene) => write!(f, "Unknown scene {}", scene),
+            Error::NoDevices { zone, group } => {
+                write!(f, "No devices available in zone {} group {}", zone, group)
+            }
+            Error::InvalidResponse(s) => write!(f, "Invalid response: {}", s),
+            Error::InvalidInput(s) => write!(f, "{}", s),
+            Error::Transport(ref e) => e.fmt(f),
+            Error::Json(ref e) => e.fmt(f),
             Error::Io(ref e) => e.fmt(f),
+            Error::Poisoned => write!(f, "Poison error"),
+            Error::Event(ref e) => write!(f, "Event subscription failed: {}", e),
+            Error::EventApply(ref e) => write!(f, "Event couldn't be applied: {}", e),
         }
     }
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
+impl Error {
+    /// Check if the error is temporary, like a timeout or an unavailable dss,
+    /// so the same request may succeed when it's sent again.
+    pub fn is_transient(&self) -> bool {
         match self {
-            Error::Error(s) => &s,
-            Error::SerdeJson(ref e) => e.description(),
-            Error::Reqwest(ref e) => e.description(),
-            Error::Io(ref e) => e.description(),
+            Error::Transport(e) => {
+                e.is_timeout()
+                    || (e.is_connect() && !is_tls_error(e))
+                    || e.status().is_some_and(|s| s.is_server_error())
+            }
+            Error::Event(e) | Error::EventApply(e) => e.is_transient(),
+            _ => false,
         }
     }
-    fn cause(&self) -> Option<&dyn std::error::Error> {
-        match self {
-            Error::Error(_) => None,
-            Error::SerdeJson(ref e) => Some(e),
-            Error::Reqwest(ref e) => Some(e),
-            Error::Io(ref e) => Some(e),
+}
+
+/// Check if the request failed before it reached the dss, so it's safe to
+/// send it again.
+fn not_sent(err: &Error) -> bool {
+    match err {
+        Error::Transport(e) => e.is_connect() && !is_tls_error(e),
+        _ => false,
+    }
+}
+
+/// Check if the request only reads from the dss, so sending it twice
+/// doesn't change anything.
+///
+/// The 'event/get' request takes the events from the dss, a failed one is
+/// handled by the backoff of the subscription.
+fn is_idempotent(request: &str) -> bool {
+    let action = request.rsplit('/').next().unwrap_or(request);
+    request != "event/get"
+        && (action.starts_with("get")
+            || action.contains("Get")
+            || action == "query"
+            || action.starts_with("login"))
+}
+
+/// Check if the connection failed, because the certificate was rejected.
+fn is_tls_error(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+
+    while let Some(mut e) = source {
+        // io errors don't deliver the wrapped error as source
+        while let Some(inner) = e
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+        {
+            e = inner;
+        }
+
+        if e.downcast_ref::<rustls::Error>().is_some() {
+            return true;
         }
+        source = e.source();
     }
+
+    false
 }
 
-// immplement error from string
-impl From<&str> for Error {
-    fn from(err: &str) -> Self {
-        Error::Error(err.into())
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(ref e) => Some(e),
+            Error::Json(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Event(ref e) | Error::EventApply(ref e) => Some(&**e),
+            _ => None,
+        }
     }
 }
 
 // immplement Serde Json
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
-        Error::SerdeJson(err)
+        Error::Json(err)
     }
 }
 
@@ -1645,16 +5358,85 @@ impl From<std::io::Error> for Error {
     }
 }
 
-// immplement Serde Json
+// immplement reqwest
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
-        Error::Reqwest(err)
+        Error::Transport(err)
     }
 }
 
 // implement mutex poison error
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_err: std::sync::PoisonError<T>) -> Self {
-        Error::from("Poison error")
+        Error::Poisoned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_reading_requests_are_idempotent() {
+        assert!(is_idempotent("zone/getSensorValues"));
+        assert!(is_idempotent("device/getOutputValue"));
+        assert!(is_idempotent("property/query"));
+        assert!(!is_idempotent("zone/callScene"));
+        assert!(!is_idempotent("property/setString"));
+        assert!(!is_idempotent("event/get"));
+    }
+
+    #[test]
+    fn scenes_have_numbers_names_and_areas() {
+        assert_eq!(Scene::from(17), Scene::Preset2);
+        assert_eq!(Scene::from(32), Scene::Preset10);
+        assert_eq!(Scene::from(16), Scene::Unknown);
+        assert_eq!(Scene::DeepOff as u8, 68);
+        assert_eq!("deep-off".parse::<Scene>().unwrap(), Scene::DeepOff);
+        assert_eq!("Preset 12".parse::<Scene>().unwrap(), Scene::Preset12);
+        assert_eq!("72".parse::<Scene>().unwrap(), Scene::Absent);
+        assert!("16".parse::<Scene>().is_err());
+        assert_eq!(Scene::AutoOff.to_string(), "Auto-Off");
+        assert_eq!(Scene::Area3Stop.area(), Some(3));
+        assert_eq!(Scene::Preset1.area(), None);
+        assert!(Scene::Panic.is_apartment());
+        assert!(!Scene::SunProtection.is_apartment());
+    }
+
+    #[test]
+    fn other_events_are_serialized_with_their_name() {
+        let raw = serde_json::json!({"name": "custom", "properties": {"a": "1"}});
+        let event = Event::from_json("custom".into(), raw.clone());
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json, raw);
+        match serde_json::from_value(json).unwrap() {
+            Event::Other(name, json) => {
+                assert_eq!(name, "custom");
+                assert_eq!(json, raw);
+            }
+            e => panic!("Unexpected event {:?}", e),
+        }
+
+        let json = serde_json::to_value(Event::Running).unwrap();
+        assert_eq!(json, serde_json::json!({"name": "running"}));
+        assert!(matches!(serde_json::from_value(json), Ok(Event::Running)));
+    }
+
+    #[test]
+    fn base_url_adds_the_default_port() {
+        assert_eq!(base_url("dss.local"), "https://dss.local:8080");
+        assert_eq!(base_url("192.168.1.10"), "https://192.168.1.10:8080");
+        assert_eq!(base_url("dss.local/dss/"), "https://dss.local:8080/dss");
+        assert_eq!(base_url("fe80::1"), "https://[fe80::1]:8080");
+        assert_eq!(base_url("[fe80::1]"), "https://[fe80::1]:8080");
+    }
+
+    #[test]
+    fn base_url_keeps_a_given_port_or_scheme() {
+        assert_eq!(base_url("dss.local:443"), "https://dss.local:443");
+        assert_eq!(base_url("dss.local:443/dss"), "https://dss.local:443/dss");
+        assert_eq!(base_url("[fe80::1]:8443"), "https://[fe80::1]:8443");
+        assert_eq!(base_url("http://127.0.0.1:8000/"), "http://127.0.0.1:8000");
     }
 }
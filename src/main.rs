@@ -1,213 +1,1047 @@
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::str::FromStr;
 
-fn main() -> Result<(), dss::Error> {
-    // shown welcome screen
-    println!();
-    println!("**************************************************************");
-    println!("*                 Digital Strom Server - CLI                 *");
-    println!("**************************************************************");
+/// The exit codes of the CLI, so scripts can react on the failure.
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_AUTHENTICATION: i32 = 3;
+const EXIT_UNKNOWN: i32 = 4;
+const EXIT_CONNECTION: i32 = 5;
 
-    // login
-    let appt = login()?;
-    println!("*> Success");
-    println!("*>");
+/// A command of the CLI and the shell.
+struct Command {
+    /// The name and the german aliases
+    names: &'static [&'static str],
+    args: &'static [&'static str],
+    help: &'static str,
+    example: &'static str,
+    /// The filters with their usage and help, like '--zone <zone>'.
+    filters: &'static [(&'static str, &'static str)],
+}
 
-    // get the event channel and display the messages when they get received
-    let events = appt.event_channel()?;
-    std::thread::spawn(move || loop {
-        let res = events.recv();
-
-        match res {
-            Ok(v) => {
-                println!("\n{:#?}\n", v);
-                print!("*dss> ");
-                std::io::stdout()
-                    .flush()
-                    .expect("Output flush failed - internal error");
-            }
-            Err(_) => {
-                println!("Channel Closed");
-                break;
+/// All commands, the usage, help and completion are generated from it.
+const COMMANDS: &[Command] = &[
+    Command {
+        names: &["zones"],
+        args: &[],
+        help: "Show all zones and their groups",
+        example: "zones",
+        filters: &[],
+    },
+    Command {
+        names: &["zone"],
+        args: &["<zone>"],
+        help: "Show a single zone",
+        example: "zone office",
+        filters: &[],
+    },
+    Command {
+        names: &["light", "licht"],
+        args: &["<zone>", "<value>", "[group]"],
+        help: "Set the light: on, off, up, down or 0.0-1.0",
+        example: "light office on",
+        filters: &[],
+    },
+    Command {
+        names: &["shadow", "schatten"],
+        args: &["<zone>", "<open>", "<angle>", "[group]"],
+        help: "Set the shadow position and angle",
+        example: "shadow office 0.5 close",
+        filters: &[],
+    },
+    Command {
+        names: &["scene", "szene"],
+        args: &["<zone>", "<type>", "<scene>"],
+        help: "Call a scene of a type by it's number or name",
+        example: "scene office light preset 2",
+        filters: &[],
+    },
+    Command {
+        names: &["temperature", "temperatur"],
+        args: &["<zone>", "<celsius>"],
+        help: "Set the nominal temperature",
+        example: "temperature office 21.5",
+        filters: &[],
+    },
+    Command {
+        names: &["heating", "heizung"],
+        args: &["<zone>", "<mode>"],
+        help: "Set the heating mode",
+        example: "heating office night",
+        filters: &[],
+    },
+    Command {
+        names: &["update"],
+        args: &[],
+        help: "Fetch the structure again and update the cache",
+        example: "update",
+        filters: &[],
+    },
+    Command {
+        names: &["token"],
+        args: &["[name]"],
+        help: "Request an application token and save it in the profile",
+        example: "token laptop",
+        filters: &[],
+    },
+    Command {
+        names: &["watch"],
+        args: &["[filters]"],
+        help: "Print the events of the dss, one per line",
+        example: "watch --zone office --count 5",
+        filters: &[
+            (
+                "--zone <zone>",
+                "Only the events of the zone, by name or id",
+            ),
+            (
+                "--type <type>",
+                "Only the events of the type, like light or shadow",
+            ),
+            (
+                "--action <action>",
+                "Only the scene calls of the action, like all_light_on",
+            ),
+            (
+                "--origin <origin>",
+                "Only the events of the origin, like a dsuid or device",
+            ),
+            ("-n, --count <n>", "Exit after n events"),
+            (
+                "--timeout <duration>",
+                "Exit after the duration, like 30s, 5m or 1h",
+            ),
+        ],
+    },
+    Command {
+        names: &["help", "hilfe"],
+        args: &["[command]"],
+        help: "Show the help of all or a single command",
+        example: "help light",
+        filters: &[],
+    },
+];
+
+impl Command {
+    /// The name with the arguments, like 'zone <zone>'.
+    fn usage(&self) -> String {
+        let mut usage = vec![self.names[0]];
+        usage.extend(self.args);
+        usage.join(" ")
+    }
+
+    /// The long names of the filters, like '--zone'.
+    fn filter_names(&self) -> Vec<&'static str> {
+        self.filters
+            .iter()
+            .filter_map(|(usage, _)| usage.split([' ', ',']).find(|w| w.starts_with("--")))
+            .collect()
+    }
+
+    /// Print the filters with their help.
+    fn print_filters(&self) {
+        for (usage, help) in self.filters {
+            println!("  {:<23}{}", usage, help);
+        }
+    }
+
+    /// Print the detailed help of the command.
+    fn print_help(&self) {
+        println!("Usage: {}", self.usage());
+        println!();
+        println!("{}", self.help);
+        if self.names.len() > 1 {
+            println!("Alias: {}", self.names[1..].join(", "));
+        }
+        println!("Example: {}", self.example);
+        if !self.filters.is_empty() {
+            println!();
+            println!("Filters:");
+            self.print_filters();
+        }
+    }
+}
+
+/// Find the command by it's name or alias.
+fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.names.contains(&name))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let code = match run(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code(&e)
+        }
+    };
+
+    std::process::exit(code);
+}
+
+/// The exit code for an error.
+fn exit_code(err: &dss::Error) -> i32 {
+    match err {
+        dss::Error::InvalidInput(_) => EXIT_USAGE,
+        dss::Error::Authentication(_) => EXIT_AUTHENTICATION,
+        dss::Error::UnknownZone(_)
+        | dss::Error::UnknownGroup { .. }
+        | dss::Error::UnknownDevice(_)
+        | dss::Error::UnknownScene(_) => EXIT_UNKNOWN,
+        dss::Error::Transport(_) => EXIT_CONNECTION,
+        dss::Error::Event(e) | dss::Error::EventApply(e) => exit_code(e),
+        _ => EXIT_ERROR,
+    }
+}
+
+/// The options and command given as arguments.
+#[derive(Debug, Default)]
+struct Options {
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+    config: Option<String>,
+    profile: Option<String>,
+    output: Output,
+    help: bool,
+    version: bool,
+    command: Vec<String>,
+}
+
+/// How the results get printed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Output {
+    /// Human readable lines
+    #[default]
+    Plain,
+    /// Aligned columns
+    Table,
+    /// The serde representation, events are printed one per line
+    Json,
+}
+
+impl std::str::FromStr for Output {
+    type Err = dss::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Output::Plain),
+            "table" => Ok(Output::Table),
+            "json" => Ok(Output::Json),
+            _ => Err(dss::Error::InvalidInput(format!(
+                "Unknown output {}, use json, table or plain",
+                s
+            ))),
+        }
+    }
+}
+
+/// The config file, with the login of one or more installations.
+///
+/// The values at the top level are used, when no profile is selected.
+#[derive(Debug, Default, serde::Deserialize)]
+struct Config {
+    default_profile: Option<String>,
+    #[serde(flatten)]
+    login: Profile,
+    #[serde(default)]
+    profiles: std::collections::HashMap<String, Profile>,
+}
+
+/// The login values of a single installation.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+struct Profile {
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    /// Environment variable which holds the password
+    password_env: Option<String>,
+    /// Command which prints the password, like 'pass show dss'
+    password_command: Option<String>,
+    application_token: Option<String>,
+    /// File to cache the structure of the appartement
+    cache: Option<String>,
+}
+
+impl Config {
+    /// Get the selected profile or the top level values.
+    fn profile(&self, name: Option<String>) -> Result<Profile, dss::Error> {
+        match name.or_else(|| self.default_profile.clone()) {
+            Some(name) => self
+                .profiles
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| dss::Error::InvalidInput(format!("Unknown profile {}", name))),
+            None => Ok(self.login.clone()),
+        }
+    }
+}
+
+impl Profile {
+    /// Resolve the password, directly or from it's reference.
+    fn password(&self) -> Result<Option<String>, dss::Error> {
+        if let Some(password) = &self.password {
+            return Ok(Some(password.clone()));
+        }
+
+        if let Some(env) = &self.password_env {
+            return std::env::var(env).map(Some).map_err(|_| {
+                dss::Error::InvalidInput(format!("The password variable {} is not set", env))
+            });
+        }
+
+        if let Some(cmd) = &self.password_command {
+            let out = std::process::Command::new("sh")
+                .args(["-c", cmd])
+                .stderr(std::process::Stdio::inherit())
+                .output()?;
+
+            if !out.status.success() {
+                return Err(dss::Error::InvalidInput(format!(
+                    "The password command '{}' failed",
+                    cmd
+                )));
             }
+            return Ok(Some(
+                String::from_utf8_lossy(&out.stdout).trim().to_string(),
+            ));
         }
-    });
 
-    // CLI loop
-    loop {
-        let mut cmd = String::from("");
-        print!("*dss> ");
-        std::io::stdout()
-            .flush()
-            .expect("Output flush failed - internal error");
-        std::io::stdin()
-            .read_line(&mut cmd)
-            .expect("Please enter a valid String");
-
-        // trim the command
-        cmd = cmd.trim().to_lowercase();
-        // split the command at white spaces
-        let cmds: Vec<&str> = cmd.split_whitespace().collect();
-
-        // show all zones
-        if cmd == "zones" {
-            println!("{:#?}\n", appt.get_zones());
-        // show a specific zone
-        } else if cmd.starts_with("zone") {
-            // start again if not enough parameter where subimitted
-            if cmds.len() < 2 {
-                print_zone_help();
-                continue;
+        Ok(None)
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), dss::Error> {
+    let mut options = parse_options(args)?;
+
+    if options.help {
+        print_usage();
+        return Ok(());
+    }
+    if options.version {
+        println!("dss {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    // the old way to login, with the host, user and password as arguments,
+    // only when none of them is a command, like in 'office light on'
+    if options.command.len() == 3 && options.command.iter().all(|a| find_command(a).is_none()) {
+        eprintln!("Hint: the host, user and password are taken from the arguments, use --host, --user and --password instead");
+        let mut args = options.command.drain(..);
+        options.host = args.next();
+        options.user = args.next();
+        options.password = args.next();
+    }
+
+    // without a command the interactive shell is started
+    if options.command.is_empty() {
+        return shell(&options);
+    }
+
+    // the token is requested with the password, before the appartment is connected
+    if options.command[0].to_lowercase() == "token" {
+        return request_token(&options);
+    }
+
+    let appt = connect(&options, false)?;
+    execute(&appt, &options.command, options.output)
+}
+
+/// Split the arguments into the options and the command.
+fn parse_options(args: Vec<String>) -> Result<Options, dss::Error> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // the values can be given as '--host=dss' or '--host dss'
+        let (name, value) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| dss::Error::InvalidInput(format!("Missing value for {}", name)))
+        };
+
+        match name.as_str() {
+            "--host" => options.host = Some(value()?),
+            "--user" => options.user = Some(value()?),
+            "--password" => options.password = Some(value()?),
+            "--token" => options.token = Some(value()?),
+            "--config" => options.config = Some(value()?),
+            "-p" | "--profile" => options.profile = Some(value()?),
+            "-o" | "--output" => options.output = Output::from_str(&value()?)?,
+            "-h" | "--help" => options.help = true,
+            "-V" | "--version" => options.version = true,
+            // the options after the command belong to it, like the watch filters
+            n if n.starts_with("--") && options.command.is_empty() => {
+                return Err(dss::Error::InvalidInput(format!("Unknown option {}", n)))
             }
+            _ => options.command.push(arg),
+        }
+    }
 
-            // When a conversion to usize works, filter the zones
-            if let Ok(zone) = get_room_id(&appt, cmds[1]) {
-                println!("{:#?}\n", appt.get_zones()?.iter().find(|z| z.id == zone));
-            } else {
-                print_zone_help();
+    Ok(options)
+}
+
+/// The default location of the config file.
+fn default_config_file() -> Option<std::path::PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(dir.join("dss").join("config.toml"))
+}
+
+/// The config file of the arguments or the enviroment, otherwise the default one.
+fn config_file(options: &Options) -> Option<std::path::PathBuf> {
+    options
+        .config
+        .clone()
+        .or_else(|| std::env::var("DSS_CONFIG").ok())
+        .map(std::path::PathBuf::from)
+        .or_else(default_config_file)
+}
+
+/// Load the config file, a missing default config file is no error.
+fn load_config(options: &Options) -> Result<Config, dss::Error> {
+    let file = match config_file(options) {
+        Some(file) => file,
+        None => return Ok(Config::default()),
+    };
+    let explicit = options.config.is_some() || std::env::var_os("DSS_CONFIG").is_some();
+    if !explicit && !file.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = std::fs::read_to_string(&file)?;
+    toml::from_str(&content).map_err(|e| {
+        dss::Error::InvalidInput(format!("Invalid config file {}: {}", file.display(), e))
+    })
+}
+
+/// The name of the selected profile, none for the top level values.
+fn profile_name(options: &Options, config: &Config) -> Option<String> {
+    options
+        .profile
+        .clone()
+        .or_else(|| std::env::var("DSS_PROFILE").ok())
+        .or_else(|| config.default_profile.clone())
+}
+
+/// The login values of the arguments, the enviroment and the config profile.
+struct Login {
+    host: String,
+    token: Option<String>,
+    user: Option<String>,
+    profile: Profile,
+}
+
+impl Login {
+    /// Collect the login values, only the host is required.
+    ///
+    /// When 'ask' is set, a missing host is requested from the user.
+    fn new(options: &Options, ask: bool) -> Result<Login, dss::Error> {
+        let config = load_config(options)?;
+        let profile = config.profile(profile_name(options, &config))?;
+        let value = |arg: &Option<String>, env: &str, profile: &Option<String>| {
+            arg.clone()
+                .or_else(|| std::env::var(env).ok())
+                .or_else(|| profile.clone())
+        };
+
+        let host = match value(&options.host, "DSS_HOST", &profile.host) {
+            Some(host) => host,
+            None if ask => read_input("Please Enter the hostname or ip")?,
+            None => {
+                return Err(dss::Error::InvalidInput(
+                    "No host given, use --host, DSS_HOST or a config profile".into(),
+                ))
             }
+        };
+
+        Ok(Login {
+            host: host.trim().to_string(),
+            token: value(&options.token, "DSS_TOKEN", &profile.application_token),
+            user: value(&options.user, "DSS_USER", &profile.user),
+            profile,
+        })
+    }
 
-        // set the light
-        } else if cmd.starts_with("light") | cmd.starts_with("licht") {
-            // don't continue if not enough parameters are available
-            if cmds.len() < 3 {
-                print_light_help();
-                continue;
+    /// Get the user and the password, the missing values are requested
+    /// from the user when 'ask' is set.
+    ///
+    /// A missing password is always asked for, when a terminal is available.
+    fn credentials(&self, options: &Options, ask: bool) -> Result<(String, String), dss::Error> {
+        let user = match self.user.clone() {
+            Some(user) => user,
+            None if ask => read_input("Please Enter the username")?,
+            None => {
+                return Err(dss::Error::InvalidInput(
+                    "No credentials given, use --token or --user and a password".into(),
+                ))
             }
+        };
+
+        // the password is only asked for, it's never echoed
+        let password = match options
+            .password
+            .clone()
+            .or_else(|| std::env::var("DSS_PASSWORD").ok())
+        {
+            Some(password) => password,
+            None => match self.profile.password()? {
+                Some(password) => password,
+                None if ask || std::io::stdin().is_terminal() => {
+                    rpassword::prompt_password("*> Please Enter the password: ")?
+                }
+                None => {
+                    return Err(dss::Error::InvalidInput(
+                        "No password given, use DSS_PASSWORD or a config profile".into(),
+                    ))
+                }
+            },
+        };
+
+        Ok((user.trim().to_string(), password.trim().to_string()))
+    }
+}
 
-            // interprete the value to set
-            let val = get_value(cmds[1]);
+/// Login with the values of the arguments, the enviroment or the config profile.
+///
+/// When 'ask' is set, the missing values are requested from the user.
+fn connect(options: &Options, ask: bool) -> Result<dss::Appartement, dss::Error> {
+    let login = Login::new(options, ask)?;
+    let host = login.host.as_str();
+    let cache = login.profile.cache.as_deref().map(expand_home);
 
-            // interpret the group to set
-            let group = cmds.get(3).and_then(|g| usize::from_str(g).ok());
+    // an application token is preferred over the password
+    if let Some(token) = &login.token {
+        return match cache {
+            Some(cache) => dss::Appartement::connect_token_file(host, token.trim(), &cache),
+            None => dss::Appartement::from_api(
+                api_builder(host)?
+                    .application_token(token.trim())
+                    .connect()?,
+            ),
+        };
+    }
 
-            // when the zone can be converted to a number, set the value
-            if let Ok(zone) = get_room_id(&appt, cmds[2]) {
-                appt.set_value(zone, group, dss::Value::Light(val))?;
+    let (user, password) = login.credentials(options, ask)?;
+    match cache {
+        Some(cache) => dss::Appartement::connect_file(host, &user, &password, &cache),
+        None => dss::Appartement::from_api(api_builder(host)?.user(&user, &password).connect()?),
+    }
+}
+
+/// The api builder for the host, which trusts the certificate of the dss on
+/// first use and pins it by the fingerprint in the state directory.
+fn api_builder(host: &str) -> Result<dss::RawApiBuilder, dss::Error> {
+    let builder = dss::RawApi::builder(host);
+    let dir = match default_state_dir() {
+        Some(dir) => dir,
+        None => return Ok(builder),
+    };
+    std::fs::create_dir_all(&dir)?;
+
+    // one file per host, the characters which can't be in a file name are replaced
+    let name: String = host
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
             } else {
-                print_light_help();
-            }
-        }
-        // set the shadow
-        else if cmd.starts_with("shadow") | cmd.starts_with("schatten") {
-            // don't continue if not enough parameters are available
-            if cmds.len() < 4 {
-                print_shadow_help();
-                continue;
+                '_'
             }
+        })
+        .collect();
+    let file = dir.join(format!("{}.fingerprint", name));
+    Ok(builder.tls(dss::Tls::TrustOnFirstUse(file.to_string_lossy().into())))
+}
 
-            // interprete the open value to set
-            let open = get_value(cmds[1]);
+/// Request an application token with the user and password and save it
+/// in the selected profile, so the password isn't needed anymore.
+fn request_token(options: &Options) -> Result<(), dss::Error> {
+    let login = Login::new(options, false)?;
+    let (user, password) = login.credentials(options, false)?;
+    let name = options.command.get(1).map_or("dss-cli", |n| n.as_str());
 
-            // interprete the angle value to set
-            let angle = get_value(cmds[2]);
+    let token = api_builder(&login.host)?
+        .user(user.as_str(), password.as_str())
+        .request_application_token(name)?;
+    let file = save_token(options, &login.host, &token)?;
 
-            // interpret the group to set
-            let group = cmds.get(4).and_then(|g| usize::from_str(g).ok());
+    println!("The application token is saved in {}", file.display());
+    Ok(())
+}
 
-            // when the zone can be converted to a number, set the value
-            if let Ok(zone) = get_room_id(&appt, cmds[3]) {
-                appt.set_value(zone, group, dss::Value::Shadow(open, angle))?;
-            } else {
-                print_shadow_help();
+/// Write the application token into the selected profile of the config file,
+/// the file and the profile are created when they don't exist.
+fn save_token(
+    options: &Options,
+    host: &str,
+    token: &str,
+) -> Result<std::path::PathBuf, dss::Error> {
+    let file = config_file(options).ok_or_else(|| {
+        dss::Error::InvalidInput("No config file to save the token, use --config".into())
+    })?;
+    let invalid = |e: &dyn std::fmt::Display| {
+        dss::Error::InvalidInput(format!("Invalid config file {}: {}", file.display(), e))
+    };
+
+    let content = match std::fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut values: toml::Table = toml::from_str(&content).map_err(|e| invalid(&e))?;
+    let config: Config = toml::from_str(&content).map_err(|e| invalid(&e))?;
+
+    // the top level values are used, when no profile is selected
+    let profile = match profile_name(options, &config) {
+        Some(name) => values
+            .entry("profiles")
+            .or_insert_with(|| toml::Table::new().into())
+            .as_table_mut()
+            .ok_or_else(|| invalid(&"profiles is not a table"))?
+            .entry(name)
+            .or_insert_with(|| toml::Table::new().into())
+            .as_table_mut()
+            .ok_or_else(|| invalid(&"the profile is not a table"))?,
+        None => &mut values,
+    };
+    profile
+        .entry("host")
+        .or_insert_with(|| host.to_string().into());
+    profile.insert("application_token".into(), token.to_string().into());
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&file, toml::to_string(&values).map_err(|e| invalid(&e))?)?;
+    Ok(file)
+}
+
+/// Replace a leading '~' of a path with the home directory.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Ask the user for a value.
+fn read_input(question: &str) -> Result<String, dss::Error> {
+    println!("*> {}", question);
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Execute a single command and return.
+fn execute(appt: &dss::Appartement, args: &[String], output: Output) -> Result<(), dss::Error> {
+    let args: Vec<String> = args.iter().map(|a| a.to_lowercase()).collect();
+    let arg = |i: usize, name: &str| {
+        args.get(i)
+            .map(|a| a.as_str())
+            .ok_or_else(|| dss::Error::InvalidInput(format!("Missing argument <{}>", name)))
+    };
+    let group = |i: usize| {
+        args.get(i)
+            .map(|g| {
+                usize::from_str(g)
+                    .map_err(|_| dss::Error::InvalidInput(format!("Invalid group {}", g)))
+            })
+            .transpose()
+    };
+
+    // only the watch takes options after the command
+    if let Some((cmd, rest)) = args.split_first() {
+        match rest.iter().find(|a| a.starts_with("--")) {
+            Some(option) if cmd != "watch" => {
+                return Err(dss::Error::InvalidInput(format!(
+                    "Unknown option {}",
+                    option
+                )))
             }
+            _ => {}
         }
-        // exit the programm
-        else if cmd == "exit" {
-            std::process::exit(0);
-        // Show the help
-        } else if cmd.starts_with("help") | cmd.starts_with("hilfe") {
-            // print default help when no parameters are available
-            if cmds.is_empty() {
-                print_help();
-                continue;
-            }
+    }
 
-            // print specific error
-            match cmds[1] {
-                "zone" => print_zone_help(),
-                "light" => print_light_help(),
-                "shadow" => print_shadow_help(),
-                _ => print_help(),
-            }
-        } else {
-            // check if not enough parameters are available
-            if cmds.len() < 3 {
-                print_help();
-                continue;
+    match arg(0, "command")? {
+        "zones" => print_zones(&appt.get_zones()?, output)?,
+        "zone" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            print_zone(appt, zone, output)?;
+        }
+        "light" | "licht" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            let value = arg(2, "value")?;
+
+            // the steps don't need a value
+            let val = match value {
+                "up" | "heller" | "down" | "dunkler" => 0.0,
+                v => parse_value(v)?,
+            };
+            set_light(appt, zone, group(3)?, value, val)?;
+        }
+        "shadow" | "schatten" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            let open = parse_value(arg(2, "open")?)?;
+            let angle = parse_value(arg(3, "angle")?)?;
+            appt.set_value(zone, group(4)?, dss::Value::Shadow(open, angle))?;
+        }
+        "scene" | "szene" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            let typ = parse_type(arg(2, "type")?)?;
+            arg(3, "scene")?;
+
+            // the scene is given by it's number or name, which can contain spaces
+            let scene = args[3..].join(" ");
+            match usize::from_str(&scene) {
+                Ok(number) => appt.call_scene_by_number(zone, typ, number)?,
+                Err(_) => appt.call_scene_by_name(zone, typ, &scene)?,
             }
+        }
+        "temperature" | "temperatur" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            let value = arg(2, "value")?;
+            let value = f32::from_str(value)
+                .map_err(|_| dss::Error::InvalidInput(format!("Invalid temperature {}", value)))?;
+            appt.set_value(zone, None, dss::Value::Temperature(value))?;
+        }
+        "heating" | "heizung" => {
+            let zone = get_room_id(appt, arg(1, "zone")?)?;
+            let mode = dss::HeatingMode::from_str(arg(2, "mode")?)?;
+            appt.set_value(zone, None, dss::Value::HeatingMode(mode))?;
+        }
+        "update" => {
+            appt.update_all()?;
+        }
+        "watch" => {
+            let watch = Watch::parse(appt, &args[1..])?;
+            let events = appt.event_channel()?;
+            let deadline = watch.timeout.map(|t| std::time::Instant::now() + t);
+            let mut count = 0;
+            let mut error = None;
+
+            // a closed channel ends with the failure, which couldn't be recovered
+            let closed = |error: Option<dss::Error>| {
+                error.unwrap_or_else(|| dss::Error::InvalidResponse("Events closed".into()))
+            };
+
+            // follow the events until the count or the timeout is reached
+            while watch.count != Some(count) {
+                let res = match deadline {
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(std::time::Instant::now());
+                        match events.recv_timeout(left) {
+                            Ok(res) => res,
+                            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => break,
+                            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                                return Err(closed(error))
+                            }
+                        }
+                    }
+                    None => match events.recv() {
+                        Ok(res) => res,
+                        Err(_) => return Err(closed(error)),
+                    },
+                };
 
-            // if enough is avilable get the room id
-            if let Ok(zone) = get_room_id(&appt, cmds[0]) {
-                if cmds[1] == "light" || cmds[1] == "licht" {
-                    // interprete the value to set
-                    let val = get_value(cmds[2]);
-
-                    // set light value
-                    appt.set_value(zone, None, dss::Value::Light(val))?;
-                } else if cmds[1] == "shadow" || cmds[1] == "schatten" {
-                    // check if not enough parameters are available
-                    if cmds.len() < 4 {
-                        print_help();
+                // the failures are reported, while the events keep coming
+                let event = match res {
+                    Ok(event) => event,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        error = Some(e);
                         continue;
                     }
+                };
 
-                    // interprete the open value to set
-                    let open = get_value(cmds[2]);
+                if watch.matches(&event) {
+                    print_event(&event, std::time::SystemTime::now(), output)?;
+                    count += 1;
+                }
+            }
+        }
+        "token" => {
+            return Err(dss::Error::InvalidInput(
+                "The token is requested from the command line, like 'dss token'".into(),
+            ))
+        }
+        "help" | "hilfe" => match args.get(1) {
+            Some(name) => find_command(name)
+                .ok_or_else(|| dss::Error::InvalidInput(format!("Unknown command {}", name)))?
+                .print_help(),
+            None => print_usage(),
+        },
+        cmd => {
+            return Err(dss::Error::InvalidInput(format!(
+                "Unknown command {}, see 'help'",
+                cmd
+            )))
+        }
+    }
 
-                    // interprete the angle value to set
-                    let angle = get_value(cmds[3]);
+    Ok(())
+}
 
-                    // set shadow value
-                    appt.set_value(zone, None, dss::Value::Shadow(open, angle))?;
-                } else if cmds[1] == "zone" {
-                    // show the zone details
-                    println!("{:#?}\n", appt.get_zones()?.iter().find(|z| z.id == zone));
-                }
-            } else {
-                print_help();
+/// The interactive shell, which executes the commands line by line.
+fn shell(options: &Options) -> Result<(), dss::Error> {
+    // shown welcome screen
+    println!();
+    println!("**************************************************************");
+    println!("*                 Digital Strom Server - CLI                 *");
+    println!("**************************************************************");
+
+    // login
+    println!("*> Login...");
+    let appt = connect(options, true)?;
+    println!("*> Success");
+    println!("*>");
+
+    // the zone names are completed with the tab key
+    let zones = appt.get_zones()?;
+    let mut editor = rustyline::Editor::new().map_err(readline_error)?;
+    editor.set_helper(Some(ShellHelper {
+        zones: zones.iter().map(|z| z.name.to_lowercase()).collect(),
+    }));
+
+    // the history is kept between the sessions
+    let history = default_history_file();
+    if let Some(file) = &history {
+        let _ = editor.load_history(file);
+    }
+
+    loop {
+        let line = match editor.readline("*dss> ") {
+            Ok(line) => line.trim().to_string(),
+            Err(rustyline::error::ReadlineError::Interrupted) => continue,
+            Err(rustyline::error::ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+        if line.is_empty() {
+            continue;
+        }
+        // the history keeps the line as it was typed
+        editor
+            .add_history_entry(line.as_str())
+            .map_err(readline_error)?;
+
+        let line = line.to_lowercase();
+        if line == "exit" {
+            break;
+        }
+        if line == "help" || line == "hilfe" {
+            println!("Commands:");
+            print_commands();
+            println!("  {:<38}Exit the shell", "exit");
+            continue;
+        }
+
+        // a failed command is shown, but doesn't end the shell
+        let args = shell_args(&zones, &line);
+        if let Err(e) = watch_limit(&args).and_then(|_| execute(&appt, &args, options.output)) {
+            println!("*> Error: {}", e);
+            if let (dss::Error::InvalidInput(_), Some(cmd)) = (&e, find_command(&args[0])) {
+                println!("*> Usage: {}, like '{}'", cmd.usage(), cmd.example);
             }
         }
     }
+
+    if let Some(file) = &history {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        editor.save_history(file).map_err(readline_error)?;
+    }
+
+    Ok(())
 }
 
-/// Login function either by arguments or manually step by step
-fn login() -> Result<dss::Appartement, dss::Error> {
-    let mut host = String::from("");
-    let mut user = String::from("");
-    let mut pass = String::from("");
+/// Bring the words of a shell line into the order of the commands.
+///
+/// Besides 'light office on', the shell understands the zone before
+/// the command, like 'office light on' and the values before the zone,
+/// like 'light on office'. Zone names with spaces are quoted, like
+/// 'light "living room" on'.
+fn shell_args(zones: &[dss::Zone], line: &str) -> Vec<String> {
+    let mut words = split_words(line);
+    let is_zone = |w: &str| {
+        zones
+            .iter()
+            .any(|z| z.id.to_string() == w || z.name.to_lowercase() == w)
+    };
 
-    // when enviorement parameters are defined try them
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() == 4 {
-        host = args[1].clone();
-        user = args[2].clone();
-        pass = args[3].clone();
+    if words.len() >= 2 && find_command(&words[0]).is_none() && find_command(&words[1]).is_some() {
+        words.swap(0, 1);
+        return words;
     }
-    // or show login dialog to the user
-    else {
-        println!("*> Please Enter the hostname or ip");
-        std::io::stdin()
-            .read_line(&mut host)
-            .expect("Please enter a valid String");
-        println!("*> Please Enter the username");
-        std::io::stdin()
-            .read_line(&mut user)
-            .expect("Please enter a valid String");
-        println!("*> Please Enter the password");
-        std::io::stdin()
-            .read_line(&mut pass)
-            .expect("Please enter a valid String");
+
+    let values = match words.first().and_then(|w| find_command(w)) {
+        Some(cmd) if cmd.names[0] == "shadow" => 2,
+        Some(cmd) if ["light", "temperature", "heating"].contains(&cmd.names[0]) => 1,
+        _ => 0,
+    };
+    if values > 0 && words.len() > values + 1 && !is_zone(&words[1]) && is_zone(&words[values + 1])
+    {
+        let zone = words.remove(values + 1);
+        words.insert(1, zone);
     }
 
-    println!("*> Please be aware, the login and first fetch can take more than 30 seconds!");
-    println!("*>");
-    println!("*> Login...");
+    words
+}
+
+/// Split a shell line into words, the quoted parts are kept together.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quoted = false;
 
-    // try to login
-    dss::Appartement::connect(host.trim(), user.trim(), pass.trim())
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    words
+}
+
+/// The default location of the shell history.
+fn default_history_file() -> Option<std::path::PathBuf> {
+    Some(default_state_dir()?.join("history"))
+}
+
+/// The directory of the shell history and the certificate fingerprints.
+fn default_state_dir() -> Option<std::path::PathBuf> {
+    let dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+
+    Some(dir.join("dss"))
+}
+
+/// Convert the errors of the line editor.
+fn readline_error(err: rustyline::error::ReadlineError) -> dss::Error {
+    match err {
+        rustyline::error::ReadlineError::Io(e) => dss::Error::Io(e),
+        e => dss::Error::Io(std::io::Error::other(e)),
+    }
+}
+
+/// Completes the commands, zone names and keywords in the shell.
+struct ShellHelper {
+    zones: Vec<String>,
 }
 
+impl ShellHelper {
+    /// The possible values of a command argument.
+    fn candidates(&self, arg: &str) -> Vec<&str> {
+        match arg {
+            "<zone>" => self.zones.iter().map(|z| z.as_str()).collect(),
+            "<value>" => vec!["on", "off", "up", "down"],
+            "<open>" | "<angle>" => vec!["open", "close"],
+            "<type>" => vec![
+                "light",
+                "shadow",
+                "heating",
+                "audio",
+                "video",
+                "joker",
+                "cooling",
+                "ventilation",
+                "window",
+                "temperature",
+            ],
+            "<mode>" => vec!["off", "comfort", "economy", "night", "holiday", "cooling"],
+            "[command]" => COMMANDS.iter().map(|c| c.names[0]).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl rustyline::completion::Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        // the offsets are taken from the line as it is, an open quote starts the word
+        let line = &line[..pos];
+        let start = match line.matches('"').count() % 2 {
+            1 => line.rfind('"').unwrap_or(0),
+            _ => line
+                .char_indices()
+                .rfind(|(_, c)| c.is_whitespace())
+                .map_or(0, |(i, c)| i + c.len_utf8()),
+        };
+        let words: Vec<String> = split_words(&line[..start].to_lowercase());
+        let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+        let commands = || COMMANDS.iter().map(|c| c.names[0]);
+
+        let candidates: Vec<&str> = match words.first().and_then(|w| find_command(w)) {
+            // the watch filters can be given in any order
+            Some(cmd) if !cmd.filters.is_empty() => cmd.filter_names(),
+            Some(cmd) => cmd
+                .args
+                .get(words.len() - 1)
+                .map_or(vec![], |arg| self.candidates(arg)),
+            // the command or the zone before the command
+            None if words.is_empty() => commands()
+                .chain(self.zones.iter().map(|z| z.as_str()))
+                .chain(std::iter::once("exit"))
+                .collect(),
+            None if words.len() == 1 => commands().collect(),
+            None => vec![],
+        };
+
+        // the names with spaces are completed with quotes
+        let word = line[start..].trim_start_matches('"').to_lowercase();
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .filter(|c| c.starts_with(&word))
+                .map(|c| {
+                    if c.contains(char::is_whitespace) {
+                        format!("\"{}\"", c)
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl rustyline::hint::Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl rustyline::highlight::Highlighter for ShellHelper {}
+
+impl rustyline::validate::Validator for ShellHelper {}
+
+impl rustyline::Helper for ShellHelper {}
+
 /// Get the room id either from the room name or id
 fn get_room_id(appt: &dss::Appartement, inp: &str) -> Result<usize, dss::Error> {
     // check if an ID is available
@@ -222,51 +1056,451 @@ fn get_room_id(appt: &dss::Appartement, inp: &str) -> Result<usize, dss::Error>
         }
     }
 
-    Err("No matching room found".into())
+    Err(dss::Error::InvalidInput(format!(
+        "No matching room found for {}",
+        inp
+    )))
 }
 
-/// Get the value from the input string
-fn get_value(inp: &str) -> f32 {
+/// Set the light value or dimm it up or down by one step
+fn set_light(
+    appt: &dss::Appartement,
+    zone: usize,
+    group: Option<usize>,
+    inp: &str,
+    val: f32,
+) -> Result<(), dss::Error> {
     match inp {
+        "up" | "heller" => appt.increase_light(zone),
+        "down" | "dunkler" => appt.decrease_light(zone),
+        _ => appt.set_value(zone, group, dss::Value::Light(val)),
+    }
+}
+
+/// Parse a value between 0.0 and 1.0 or one of the keywords.
+fn parse_value(inp: &str) -> Result<f32, dss::Error> {
+    let value = match inp {
         // check for keywords
         "close" | "on" | "an" | "zu" => 1.0,
         "open" | "off" | "aus" | "auf" => 0.0,
         // try to convert the number
-        _ => {
-            if let Ok(stat) = f32::from_str(inp) {
-                stat
-            } else {
-                0.0
+        _ => f32::from_str(inp).unwrap_or(-1.0),
+    };
+
+    if !(0.0..=1.0).contains(&value) {
+        return Err(dss::Error::InvalidInput(format!(
+            "Invalid value {}, use a number between 0.0 and 1.0",
+            inp
+        )));
+    }
+
+    Ok(value)
+}
+
+/// The filters and limits of the watch command.
+#[derive(Debug, Default)]
+struct Watch {
+    zone: Option<usize>,
+    typ: Option<dss::Type>,
+    action: Option<String>,
+    origin: Option<String>,
+    count: Option<usize>,
+    timeout: Option<std::time::Duration>,
+}
+
+impl Watch {
+    /// Read the filters from the arguments of the watch command.
+    fn parse(appt: &dss::Appartement, args: &[String]) -> Result<Watch, dss::Error> {
+        let mut watch = Watch::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || {
+                value
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| dss::Error::InvalidInput(format!("Missing value for {}", name)))
+            };
+
+            match name {
+                "--zone" => watch.zone = Some(get_room_id(appt, &value()?)?),
+                "--type" => watch.typ = Some(parse_type(&value()?)?),
+                "--action" => watch.action = Some(value()?),
+                "--origin" => watch.origin = Some(value()?),
+                "-n" | "--count" => {
+                    let count = value()?;
+                    watch.count = Some(usize::from_str(&count).map_err(|_| {
+                        dss::Error::InvalidInput(format!("Invalid count {}", count))
+                    })?);
+                }
+                "--timeout" => watch.timeout = Some(parse_duration(&value()?)?),
+                n => {
+                    return Err(dss::Error::InvalidInput(format!(
+                        "Unknown watch filter {}",
+                        n
+                    )))
+                }
             }
         }
+
+        Ok(watch)
+    }
+
+    /// Check if the event passes all filters.
+    ///
+    /// Events without the filtered attribute, like a state change
+    /// without a zone, are never passing.
+    fn matches(&self, event: &dss::Event) -> bool {
+        let (zone, typ, action, origin) = match event {
+            dss::Event::CallScene(e) | dss::Event::UndoScene(e) => (
+                Some(e.zone),
+                Some(&e.typ),
+                Some(&e.action),
+                vec![&e.origin, &e.dsuid, &e.token],
+            ),
+            dss::Event::ButtonClick(e) => (Some(e.zone), None, None, vec![&e.device]),
+            dss::Event::DeviceBinaryInput(e) => (Some(e.zone), None, None, vec![&e.device]),
+            dss::Event::ZoneSensorValue(e) => (Some(e.zone), Some(&e.typ), None, vec![&e.device]),
+            dss::Event::DeviceSensor(e) => (Some(e.zone), None, None, vec![&e.device]),
+            _ => (None, None, None, vec![]),
+        };
+
+        // the actions are matched by their name, with or without the parameter
+        let action = action.map(|a| (a.name(), a.to_string()));
+
+        self.zone.is_none_or(|z| zone == Some(z))
+            && self.typ.as_ref().is_none_or(|t| typ == Some(t))
+            && self.action.as_ref().is_none_or(|a| {
+                action
+                    .as_ref()
+                    .is_some_and(|(name, full)| name == a || full == a)
+            })
+            && self
+                .origin
+                .as_ref()
+                .is_none_or(|o| origin.iter().any(|e| e.eq_ignore_ascii_case(o)))
     }
 }
 
-fn print_help() {
-    println!("zones     Get all zones and the included data");
-    println!("zone      Get a specific zone data");
-    println!("light     Set the light for a zone");
-    println!("shadow    Set the shadow for a zone");
-    println!("exit      Exit the DSS CLI");
-    println!("help      Show this help text");
-    println!();
+/// Check that a watch in the shell ends, it can't be interrupted there.
+fn watch_limit(args: &[String]) -> Result<(), dss::Error> {
+    let limited = args.iter().any(|a| {
+        let name = a.split('=').next().unwrap_or(a);
+        name == "-n" || name == "--count" || name == "--timeout"
+    });
+
+    match args.first() {
+        Some(cmd) if cmd == "watch" && !limited => Err(dss::Error::InvalidInput(
+            "The watch needs a --count or --timeout in the shell".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Get a duration like '30', '30s', '500ms', '5m' or '1h'.
+fn parse_duration(inp: &str) -> Result<std::time::Duration, dss::Error> {
+    let invalid = || dss::Error::InvalidInput(format!("Invalid duration {}", inp));
+    let split = inp.find(|c: char| !c.is_ascii_digit()).unwrap_or(inp.len());
+    let value = u64::from_str(&inp[..split]).map_err(|_| invalid())?;
+
+    match &inp[split..] {
+        "ms" => Ok(std::time::Duration::from_millis(value)),
+        "" | "s" => Ok(std::time::Duration::from_secs(value)),
+        "m" => Ok(std::time::Duration::from_secs(value * 60)),
+        "h" => Ok(std::time::Duration::from_secs(value * 3600)),
+        _ => Err(invalid()),
+    }
 }
 
-fn print_zone_help() {
-    println!("Please define a valid zone number, like following: ");
-    println!("zone office");
-    println!("zone 2");
+/// Get the type from it's name or number.
+fn parse_type(inp: &str) -> Result<dss::Type, dss::Error> {
+    let typ = match inp {
+        "light" | "licht" => dss::Type::Light,
+        "shadow" | "schatten" => dss::Type::Shadow,
+        "heating" | "heizung" => dss::Type::Heating,
+        "audio" => dss::Type::Audio,
+        "video" => dss::Type::Video,
+        "joker" => dss::Type::Joker,
+        "cooling" => dss::Type::Cooling,
+        "ventilation" => dss::Type::Ventilation,
+        "window" => dss::Type::Window,
+        "temperature" | "temperatur" => dss::Type::TemperatureControl,
+        _ => dss::Type::from_str(inp).unwrap_or_default(),
+    };
+
+    if typ == dss::Type::Unknown {
+        return Err(dss::Error::InvalidInput(format!("Unknown type {}", inp)));
+    }
+
+    Ok(typ)
 }
 
-fn print_light_help() {
-    println!("Please define a valid light command and zone, like the following: ");
-    println!("light off office");
-    println!("light on 2");
+/// Print a single zone with it's groups.
+fn print_zone(appt: &dss::Appartement, zone: usize, output: Output) -> Result<(), dss::Error> {
+    let zones = appt.get_zones()?;
+    let zone = zones
+        .iter()
+        .find(|z| z.id == zone)
+        .ok_or(dss::Error::UnknownZone(zone))?;
+
+    // a single zone is printed as object in json
+    if output == Output::Json {
+        println!("{}", serde_json::to_string_pretty(zone)?);
+        return Ok(());
+    }
+    print_zones(std::slice::from_ref(zone), output)
 }
 
-fn print_shadow_help() {
-    println!("Please define a valid shadow command and zone, like the following: ");
-    println!("shadow open open office");
-    println!("shadow 0.5 close office");
-    println!("shadow open open 2");
+/// Print the zones with their groups.
+fn print_zones(zones: &[dss::Zone], output: Output) -> Result<(), dss::Error> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(zones)?),
+        Output::Table => {
+            let mut rows = vec![];
+            for zone in zones {
+                if zone.groups.is_empty() {
+                    rows.push(vec![zone.id.to_string(), zone.name.clone()]);
+                }
+                for group in &zone.groups {
+                    rows.push(vec![
+                        zone.id.to_string(),
+                        zone.name.clone(),
+                        group.id.to_string(),
+                        group.typ.to_string(),
+                        format_value(&group.status),
+                        group.devices.len().to_string(),
+                    ]);
+                }
+            }
+            print_table(
+                &["ZONE", "NAME", "GROUP", "TYPE", "STATUS", "DEVICES"],
+                &rows,
+            );
+        }
+        Output::Plain => {
+            for zone in zones {
+                println!("{} ({})", zone.name, zone.id);
+                for group in &zone.groups {
+                    println!(
+                        "  {} {}: {} ({} device{})",
+                        group.typ,
+                        group.id,
+                        format_value(&group.status),
+                        group.devices.len(),
+                        if group.devices.len() == 1 { "" } else { "s" }
+                    );
+                }
+                for sensor in &zone.sensors {
+                    println!("  {}: {}", sensor.typ, sensor.value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a single event with the time it was received as one line.
+fn print_event(
+    event: &dss::Event,
+    time: std::time::SystemTime,
+    output: Output,
+) -> Result<(), dss::Error> {
+    let mut columns = format_event(event);
+    columns.insert(0, format_time(time));
+
+    let line = match output {
+        Output::Json => {
+            let mut json = serde_json::to_value(event)?;
+            if let Some(obj) = json.as_object_mut() {
+                obj.insert("time".into(), columns.remove(0).into());
+            }
+            json.to_string()
+        }
+        Output::Table => columns
+            .iter()
+            .zip([24, 16, 8, 24, 0])
+            .map(|(column, width)| format!("{:<width$}", column, width = width))
+            .collect::<Vec<String>>()
+            .join(" ")
+            .trim_end()
+            .to_string(),
+        Output::Plain => columns
+            .into_iter()
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+
+    // stop when nobody reads the output anymore
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", line)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The time in the RFC 3339 format, like '2020-01-31T18:30:00.000Z'.
+fn format_time(time: std::time::SystemTime) -> String {
+    let since = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let (days, secs) = (since.as_secs() / 86400, since.as_secs() % 86400);
+
+    // convert the days into the civil date of the gregorian calendar
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+/// The name, zone and details of an event.
+fn format_event(event: &dss::Event) -> Vec<String> {
+    let zone = |z: usize| format!("zone {}", z);
+
+    match event {
+        dss::Event::CallScene(e) | dss::Event::UndoScene(e) => vec![
+            event.name().to_string(),
+            zone(e.zone),
+            format!("{} {} scene {}", e.typ, e.group, e.scene),
+            format_value(&e.value),
+        ],
+        dss::Event::ButtonClick(e) => vec![
+            event.name().to_string(),
+            zone(e.zone),
+            e.device.clone(),
+            format!("button {} click {}", e.button_index, e.click_type),
+        ],
+        dss::Event::DeviceBinaryInput(e) => vec![
+            event.name().to_string(),
+            zone(e.zone),
+            e.device.clone(),
+            format!("input {} state {}", e.index, e.state),
+        ],
+        dss::Event::StateChange(e) => vec![
+            event.name().to_string(),
+            String::new(),
+            e.name.clone(),
+            format!("{} -> {}", e.old_value, e.value),
+        ],
+        dss::Event::ZoneSensorValue(e) => vec![
+            event.name().to_string(),
+            zone(e.zone),
+            e.sensor.typ.to_string(),
+            e.sensor.value.to_string(),
+        ],
+        dss::Event::DeviceSensor(e) => vec![
+            event.name().to_string(),
+            zone(e.zone),
+            e.device.clone(),
+            e.event.clone(),
+        ],
+        dss::Event::Other(_, json) => vec![
+            event.name().to_string(),
+            String::new(),
+            json.to_string(),
+            String::new(),
+        ],
+        _ => vec![
+            event.name().to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+        ],
+    }
+}
+
+/// A short human readable form of a value.
+fn format_value(value: &dss::Value) -> String {
+    match value {
+        dss::Value::Light(l) if *l <= 0.0 => "off".into(),
+        dss::Value::Light(l) if *l >= 1.0 => "on".into(),
+        dss::Value::Light(l) => format!("{:.0}%", l * 100.0),
+        dss::Value::Shadow(open, angle) => {
+            format!("closed {:.0}% angle {:.0}%", open * 100.0, angle * 100.0)
+        }
+        dss::Value::Temperature(t) => format!("{:.1} °C", t),
+        dss::Value::HeatingMode(mode) => mode.to_string(),
+        _ => "unknown".into(),
+    }
+}
+
+/// Print the rows with aligned columns.
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let line = |columns: Vec<&str>| {
+        let line: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = w))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    line(header.to_vec());
+    for row in rows {
+        line(row.iter().map(|c| c.as_str()).collect());
+    }
+}
+
+/// Print the commands of the table with their arguments.
+fn print_commands() {
+    for cmd in COMMANDS {
+        println!("  {:<38}{}", cmd.usage(), cmd.help);
+    }
+}
+
+fn print_usage() {
+    println!("Usage: dss [OPTIONS] [COMMAND]");
+    println!();
+    println!("Without a command the interactive shell is started.");
+    println!();
+    println!("Commands:");
+    print_commands();
+    println!();
+    for cmd in COMMANDS.iter().filter(|c| !c.filters.is_empty()) {
+        let name = cmd.names[0];
+        println!("{}{} filters:", name[..1].to_uppercase(), &name[1..]);
+        cmd.print_filters();
+        println!();
+    }
+    println!("Options:");
+    println!("  --host <host>          Host or base url of the dss          [env: DSS_HOST]");
+    println!("  --user <user>          User to login with                   [env: DSS_USER]");
+    println!("  --password <password>  Password of the user                 [env: DSS_PASSWORD]");
+    println!("  --token <token>        Application token to login with      [env: DSS_TOKEN]");
+    println!("  --config <file>        Default: ~/.config/dss/config.toml   [env: DSS_CONFIG]");
+    println!("  -p, --profile <name>   Profile of the config file to use    [env: DSS_PROFILE]");
+    println!("  -o, --output <format>  Print the results as plain, table or json");
+    println!("  -h, --help             Show this help");
+    println!("  -V, --version          Show the version");
+    println!();
+    println!("Exit codes:");
+    println!("  0 success, 1 error, 2 invalid usage, 3 login failed,");
+    println!("  4 unknown zone, group, device or scene, 5 dss not reachable");
 }
@@ -0,0 +1,1107 @@
+//! An in-process mock of the digitalSTROM server.
+//!
+//! This module is only available with the 'mock' feature enabled.
+//! The 'MockServer' serves the json api of the dss for a 'Fixture'
+//! appartment over plain http on a random local port. This allows to
+//! test and develop against the api without a real installation.
+//!
+//! ```no_run
+//! let mock = dss::mock::MockServer::start(dss::mock::Fixture::default()).unwrap();
+//! let appt = dss::Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+//!
+//! appt.set_value(1, None, dss::Value::Light(1.0)).unwrap();
+//! assert_eq!(mock.last_called_scene(1, dss::Type::Light), Some(5));
+//! ```
+
+use crate::{DeviceType, Error, HeatingMode, Result, SensorType, Type};
+use serde_json::json;
+
+/// The token handed out by the mock on a successful login,
+/// followed by the number of the session.
+const SESSION_TOKEN: &str = "mock-session-token";
+
+/// The raw output value a light is dimmed by the scenes 'Decrement' and 'Increment'.
+const LIGHT_STEP: i64 = 25;
+
+/// The appartment served by the 'MockServer'.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub user: String,
+    pub password: String,
+    /// The application token which is accepted for 'system/loginApplication'.
+    pub application_token: Option<String>,
+    pub zones: Vec<FixtureZone>,
+    pub devices: Vec<FixtureDevice>,
+    pub circuits: Vec<FixtureCircuit>,
+}
+
+/// A zone of the fixture appartment.
+#[derive(Debug, Clone)]
+pub struct FixtureZone {
+    pub id: usize,
+    pub name: String,
+    /// The types available within the zone with their reachable scenes.
+    pub scenes: Vec<(Type, Vec<usize>)>,
+    /// The latest values of the zone sensors.
+    pub sensors: Vec<(SensorType, f64)>,
+    /// The nominal temperatures of the operation modes, empty when
+    /// the climate of the zone isn't controlled.
+    pub climate: Vec<(HeatingMode, f32)>,
+}
+
+/// A device of the fixture appartment.
+#[derive(Debug, Clone)]
+pub struct FixtureDevice {
+    pub id: String,
+    pub name: String,
+    pub zone_id: usize,
+    pub typ: Type,
+    pub device_type: DeviceType,
+    /// The scenes the device doesn't care about.
+    pub dont_care: Vec<usize>,
+    /// The values of the device sensors by their index, like the power consumption.
+    pub sensors: Vec<f64>,
+}
+
+/// A circuit of the fixture appartment, which meters the consumption.
+#[derive(Debug, Clone)]
+pub struct FixtureCircuit {
+    pub id: String,
+    pub name: String,
+    /// The actual power consumption in W, none when the meter has no value.
+    pub power: Option<f64>,
+    /// The energy meter value in Wh, none when the meter has no value.
+    pub energy: Option<f64>,
+}
+
+impl Default for Fixture {
+    /// A small appartment with an office with light and shadow and
+    /// a living room with two light scene groups.
+    fn default() -> Self {
+        Fixture {
+            user: "dssadmin".into(),
+            password: "secret".into(),
+            application_token: Some("mock-application-token".into()),
+            zones: vec![
+                FixtureZone {
+                    id: 1,
+                    name: "Office".into(),
+                    scenes: vec![(Type::Light, vec![0, 5]), (Type::Shadow, vec![0, 5])],
+                    sensors: vec![
+                        (SensorType::TemperatureIndoors, 21.5),
+                        (SensorType::HumidityIndoors, 45.0),
+                    ],
+                    climate: vec![],
+                },
+                FixtureZone {
+                    id: 2,
+                    name: "Living".into(),
+                    scenes: vec![
+                        (Type::Light, vec![0, 1, 2, 5, 6, 7]),
+                        (Type::Heating, vec![]),
+                    ],
+                    sensors: vec![],
+                    climate: vec![
+                        (HeatingMode::Comfort, 21.0),
+                        (HeatingMode::Economy, 20.0),
+                        (HeatingMode::Night, 18.0),
+                        (HeatingMode::Holiday, 16.0),
+                    ],
+                },
+            ],
+            devices: vec![
+                FixtureDevice {
+                    id: "303505d7f8000000000000400000001".into(),
+                    name: "Desk".into(),
+                    zone_id: 1,
+                    typ: Type::Light,
+                    device_type: DeviceType::Light,
+                    dont_care: vec![],
+                    sensors: vec![12.5],
+                },
+                FixtureDevice {
+                    id: "303505d7f8000000000000400000002".into(),
+                    name: "Blind".into(),
+                    zone_id: 1,
+                    typ: Type::Shadow,
+                    device_type: DeviceType::Shadow,
+                    dont_care: vec![],
+                    sensors: vec![],
+                },
+                FixtureDevice {
+                    id: "303505d7f8000000000000400000003".into(),
+                    name: "Ceiling".into(),
+                    zone_id: 2,
+                    typ: Type::Light,
+                    device_type: DeviceType::Light,
+                    dont_care: vec![2],
+                    sensors: vec![],
+                },
+                FixtureDevice {
+                    id: "303505d7f8000000000000400000004".into(),
+                    name: "Floor lamp".into(),
+                    zone_id: 2,
+                    typ: Type::Light,
+                    device_type: DeviceType::Light,
+                    dont_care: vec![1],
+                    sensors: vec![],
+                },
+                FixtureDevice {
+                    id: "303505d7f8000000000000400000005".into(),
+                    name: "Valve".into(),
+                    zone_id: 2,
+                    typ: Type::Heating,
+                    device_type: DeviceType::Unknown,
+                    dont_care: vec![],
+                    sensors: vec![],
+                },
+            ],
+            circuits: vec![
+                FixtureCircuit {
+                    id: "302ed89f43f0000000000f0000000001".into(),
+                    name: "Ground floor".into(),
+                    power: Some(120.0),
+                    energy: Some(5000.0),
+                },
+                FixtureCircuit {
+                    id: "302ed89f43f0000000000f0000000002".into(),
+                    name: "First floor".into(),
+                    power: Some(80.5),
+                    energy: Some(2500.0),
+                },
+            ],
+        }
+    }
+}
+
+/// The mutable state of the mock, shared between the request threads.
+#[derive(Debug, Default)]
+struct State {
+    fixture: Fixture,
+    requests: Vec<String>,
+    headers: std::collections::HashMap<String, Vec<(String, String)>>,
+    last_called: std::collections::HashMap<(usize, u8), usize>,
+    scene_names: std::collections::HashMap<(usize, usize, usize), String>,
+    outputs: std::collections::HashMap<(String, usize), u64>,
+    operation_modes: std::collections::HashMap<usize, u8>,
+    nominal: std::collections::HashMap<(usize, u8), f32>,
+    session: usize,
+    unavailable: usize,
+    properties: std::collections::BTreeMap<String, serde_json::Value>,
+    subscriptions: std::collections::HashMap<String, Vec<String>>,
+    events: std::collections::HashMap<String, std::collections::VecDeque<serde_json::Value>>,
+    /// The requested application tokens and whether they got enabled
+    tokens: Vec<(String, bool)>,
+    /// The zone requests, which are rejected by their name and zone
+    rejected: Vec<(String, usize)>,
+}
+
+/// A running mock dss, which is stopped when it gets dropped.
+pub struct MockServer {
+    host: String,
+    server: std::sync::Arc<tiny_http::Server>,
+    state: std::sync::Arc<(std::sync::Mutex<State>, std::sync::Condvar)>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Start a mock dss serving the given fixture on a random local port.
+    pub fn start(fixture: Fixture) -> Result<MockServer> {
+        let server = tiny_http::Server::http("127.0.0.1:0")
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        MockServer::serve(server, "http", fixture)
+    }
+
+    /// Start a mock dss serving the given fixture over https, with the
+    /// PEM encoded certificate and private key.
+    pub fn start_https(
+        fixture: Fixture,
+        certificate: &[u8],
+        private_key: &[u8],
+    ) -> Result<MockServer> {
+        let ssl = tiny_http::SslConfig {
+            certificate: certificate.to_vec(),
+            private_key: private_key.to_vec(),
+        };
+        let server = tiny_http::Server::https("127.0.0.1:0", ssl)
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        MockServer::serve(server, "https", fixture)
+    }
+
+    fn serve(server: tiny_http::Server, scheme: &str, fixture: Fixture) -> Result<MockServer> {
+        let addr = server.server_addr().to_ip().ok_or_else(|| {
+            Error::Io(std::io::Error::other(
+                "Mock server is not listening on an ip address",
+            ))
+        })?;
+
+        let server = std::sync::Arc::new(server);
+        let state = std::sync::Arc::new((
+            std::sync::Mutex::new(State {
+                fixture,
+                ..State::default()
+            }),
+            std::sync::Condvar::new(),
+        ));
+
+        // every request is handled in it's own thread, because
+        // 'event/get' blocks until an event arrives
+        let srv = server.clone();
+        let st = state.clone();
+        let thread = std::thread::spawn(move || {
+            while let Ok(request) = srv.recv() {
+                let st = st.clone();
+                std::thread::spawn(move || handle(&st, request));
+            }
+        });
+
+        Ok(MockServer {
+            host: format!("{}://{}", scheme, addr),
+            server,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    /// The host to connect to, like 'http://127.0.0.1:41234'.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// All requests received so far, like 'zone/callScene'.
+    pub fn requests(&self) -> Vec<String> {
+        self.lock().requests.clone()
+    }
+
+    /// The headers of the last request with the name, like 'system/login'.
+    pub fn headers(&self, name: &str) -> Vec<(String, String)> {
+        self.lock().headers.get(name).cloned().unwrap_or_default()
+    }
+
+    /// The last scene called for a zone and type.
+    pub fn last_called_scene(&self, zone: usize, typ: Type) -> Option<usize> {
+        self.lock().last_called.get(&(zone, typ as u8)).cloned()
+    }
+
+    /// The raw output value of a device at the given offset.
+    pub fn output_value(&self, device: &str, offset: usize) -> Option<u64> {
+        self.lock()
+            .outputs
+            .get(&(device.to_string(), offset))
+            .cloned()
+    }
+
+    /// Simulate a scene call within the appartment, like a pressed switch.
+    ///
+    /// The scene is stored as the last called one and the 'callScene'
+    /// event is delivered to all subscribers.
+    pub fn call_scene(&self, zone: usize, typ: Type, scene: usize) {
+        let mut state = self.lock();
+        call_scene(&mut state, zone, typ as u8, scene, "");
+        drop(state);
+        self.state.1.notify_all();
+    }
+
+    /// Deliver a raw event to all subscribers of the event name.
+    pub fn push_event(&self, name: &str, properties: serde_json::Value, source: serde_json::Value) {
+        let mut state = self.lock();
+        push_event(&mut state, name, properties, source);
+        drop(state);
+        self.state.1.notify_all();
+    }
+
+    /// Let the actual session expire, so the clients need to login again.
+    pub fn expire_session(&self) {
+        self.lock().session += 1;
+    }
+
+    /// The number of active event subscriptions.
+    pub fn subscriptions(&self) -> usize {
+        self.lock().subscriptions.len()
+    }
+
+    /// Restart the dss, which ends the actual session and drops all event subscriptions.
+    pub fn restart(&self) {
+        let mut state = self.lock();
+        state.session += 1;
+        state.subscriptions.clear();
+        state.events.clear();
+        drop(state);
+        self.state.1.notify_all();
+    }
+
+    /// Change the password of the user, the actual session stays valid.
+    pub fn set_password(&self, password: &str) {
+        self.lock().fixture.password = password.to_string();
+    }
+
+    /// Answer the next requests with '503 Service Unavailable'.
+    pub fn fail_requests(&self, count: usize) {
+        self.lock().unavailable = count;
+    }
+
+    /// Reject the request for the zone, like 'zone/getSensorValues' of a
+    /// zone without sensors.
+    pub fn reject_zone_request(&self, request: &str, zone: usize) {
+        self.lock().rejected.push((request.to_string(), zone));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer a single request of a client.
+fn handle(state: &(std::sync::Mutex<State>, std::sync::Condvar), request: tiny_http::Request) {
+    // split the url into the request name and parameters
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    // a path prefix, like from a reverse proxy, is ignored
+    let name = path
+        .split_once("/json/")
+        .map_or(path, |(_, n)| n)
+        .to_string();
+    let para: std::collections::HashMap<String, String> = query
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect();
+
+    // simulate a temporary unavailable dss
+    {
+        let mut st = state.0.lock().unwrap_or_else(|e| e.into_inner());
+
+        let headers = request
+            .headers()
+            .iter()
+            .map(|h| (h.field.to_string(), h.value.to_string()))
+            .collect();
+        st.headers.insert(name.clone(), headers);
+
+        if st.unavailable > 0 {
+            st.unavailable -= 1;
+            st.requests.push(name);
+            drop(st);
+
+            let _ = request.respond(tiny_http::Response::empty(503));
+            return;
+        }
+    }
+
+    let body = match respond(state, &name, &para) {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(msg) => json!({ "ok": false, "message": msg }),
+    };
+
+    let _ = request.respond(tiny_http::Response::from_string(body.to_string()));
+}
+
+/// Create the result of a request or the error message.
+fn respond(
+    state: &(std::sync::Mutex<State>, std::sync::Condvar),
+    name: &str,
+    para: &std::collections::HashMap<String, String>,
+) -> std::result::Result<serde_json::Value, String> {
+    let mut st = state.0.lock().unwrap_or_else(|e| e.into_inner());
+    st.requests.push(name.to_string());
+    let fixture = st.fixture.clone();
+
+    let get = |key: &str| {
+        para.get(key)
+            .cloned()
+            .ok_or(format!("Missing parameter {}", key))
+    };
+    let num = |key: &str| {
+        get(key)?
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid parameter {}", key))
+    };
+
+    // the login requests don't need a session token
+    match name {
+        "system/login" => {
+            if get("user")? == fixture.user && get("password")? == fixture.password {
+                return Ok(json!({ "token": session_token(&st) }));
+            }
+            return Err("Authentication failed".into());
+        }
+        "system/loginApplication" => {
+            let token = get("loginToken")?;
+            if Some(&token) == fixture.application_token.as_ref()
+                || st.tokens.contains(&(token, true))
+            {
+                return Ok(json!({ "token": session_token(&st) }));
+            }
+            return Err("Application-Authentication failed".into());
+        }
+        "system/requestApplicationToken" => {
+            get("applicationName")?;
+            let token = format!("mock-requested-token-{}", st.tokens.len() + 1);
+            st.tokens.push((token.clone(), false));
+            return Ok(json!({ "applicationToken": token }));
+        }
+        _ => (),
+    }
+
+    if get("token").ok() != Some(session_token(&st)) {
+        return Err("not logged in".into());
+    }
+
+    // a zone, which doesn't support the request
+    if let Ok(zone) = num("id") {
+        if st.rejected.contains(&(name.to_string(), zone)) {
+            return Err(format!("Zone {} doesn't support {}", zone, name));
+        }
+    }
+
+    // the device requests only work for known devices
+    if name.starts_with("device/") {
+        let device = get("dsid")?;
+        if !fixture.devices.iter().any(|d| d.id == device) {
+            return Err(format!("Could not find device with dsid {}", device));
+        }
+    }
+
+    match name {
+        "system/enableToken" => {
+            let token = get("applicationToken")?;
+            match st.tokens.iter_mut().find(|(t, _)| *t == token) {
+                Some((_, enabled)) => {
+                    *enabled = true;
+                    Ok(json!(null))
+                }
+                None => Err(format!("Unknown application token {}", token)),
+            }
+        }
+        "apartment/getReachableGroups" => Ok(json!({
+            "zones": fixture.zones.iter().map(|z| json!({
+                "zoneID": z.id,
+                "name": z.name,
+                "groups": z.scenes.iter().map(|(t, _)| t.clone() as u8).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>()
+        })),
+        "apartment/getDevices" => Ok(fixture.devices.iter().map(device).collect()),
+        "apartment/getStructure" => Ok(json!({
+            "apartment": {
+                "zones": fixture.zones.iter().map(|z| json!({
+                    "id": z.id,
+                    "name": z.name,
+                    "isPresent": true,
+                    "devices": fixture
+                        .devices
+                        .iter()
+                        .filter(|d| d.zone_id == z.id)
+                        .map(device)
+                        .collect::<Vec<_>>(),
+                    "groups": z.scenes.iter().map(|(t, scenes)| json!({
+                        "id": t.clone() as u8,
+                        "name": t.to_string(),
+                        "isPresent": true,
+                        "activeBasicScenes": scenes,
+                        "devices": fixture
+                            .devices
+                            .iter()
+                            .filter(|d| d.zone_id == z.id && &d.typ == t)
+                            .map(|d| d.id.clone())
+                            .collect::<Vec<_>>(),
+                    })).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>()
+            }
+        })),
+        "apartment/getCircuits" => Ok(json!({
+            "circuits": fixture.circuits.iter().map(|c| json!({
+                "dsid": c.id,
+                "dSUID": c.id,
+                "name": c.name,
+                "isPresent": true,
+                "isValid": true,
+            })).collect::<Vec<_>>()
+        })),
+        "circuit/getConsumption" | "circuit/getEnergyMeterValue" => {
+            let id = get("id")?;
+            let circuit = fixture
+                .circuits
+                .iter()
+                .find(|c| c.id == id)
+                .ok_or(format!("Could not find circuit {}", id))?;
+
+            match name {
+                "circuit/getConsumption" => circuit
+                    .power
+                    .map(|p| json!({ "consumption": p }))
+                    .ok_or("No consumption available".into()),
+                _ => circuit
+                    .energy
+                    .map(|e| json!({ "meterValue": e }))
+                    .ok_or("No meter value available".into()),
+            }
+        }
+        "metering/getLatest" => {
+            if get("from")? != ".meters(all)" {
+                return Err("Unknown meters".into());
+            }
+            let energy = get("type")? == "energy";
+
+            // the dss names the meters by their dsid and dSUID
+            Ok(json!({
+                "values": fixture.circuits.iter().filter_map(|c| {
+                    let value = if energy { c.energy } else { c.power }?;
+                    Some(json!({
+                        "dsid": c.id,
+                        "dSUID": c.id,
+                        "value": value,
+                        "date": "2020-01-01 12:00:00",
+                    }))
+                }).collect::<Vec<_>>()
+            }))
+        }
+        "metering/getValues" => {
+            let id = get("dsid")?;
+            let circuit = fixture
+                .circuits
+                .iter()
+                .find(|c| c.id == id)
+                .ok_or(format!("Could not find circuit {}", id))?;
+            let value = if get("type")? == "energy" {
+                circuit.energy
+            } else {
+                circuit.power
+            };
+
+            // the values are recorded in the resolution up to now
+            let resolution = num("resolution")?;
+            let count = num("valueCount").unwrap_or(3);
+            Ok(json!({
+                "values": (0..count)
+                    .filter_map(|i| Some((1_577_880_000 + i * resolution, value?)))
+                    .collect::<Vec<_>>()
+            }))
+        }
+        "property/query" => Ok(query(&property_tree(&st), &get("query")?)),
+        "property/getString"
+        | "property/getInteger"
+        | "property/getFloating"
+        | "property/getBoolean" => {
+            let tree = property_tree(&st);
+            let value = property(&tree, &get("path")?).ok_or("Property not found")?;
+
+            let matches = match name {
+                "property/getString" => value.is_string(),
+                "property/getInteger" => value.is_i64() || value.is_u64(),
+                "property/getFloating" => value.is_number(),
+                _ => value.is_boolean(),
+            };
+            if !matches {
+                return Err("Property has a different type".into());
+            }
+            Ok(json!({ "value": value }))
+        }
+        "property/setString" => {
+            st.properties.insert(get("path")?, json!(get("value")?));
+            Ok(json!({}))
+        }
+        "property/setInteger" | "property/setFloating" | "property/setBoolean" => {
+            let value: serde_json::Value =
+                serde_json::from_str(&get("value")?).map_err(|_| "Invalid parameter value")?;
+            st.properties.insert(get("path")?, value);
+            Ok(json!({}))
+        }
+        "property/getChildren" => {
+            let tree = property_tree(&st);
+            let node = property(&tree, &get("path")?).ok_or("Property not found")?;
+
+            let children: Vec<serde_json::Value> = node
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, child)| json!({ "name": name, "type": property_type(child) }))
+                .collect();
+            Ok(json!(children))
+        }
+        "zone/getName" => {
+            let zone = num("id")?;
+            let zone = fixture
+                .zones
+                .iter()
+                .find(|z| z.id == zone)
+                .ok_or("Zone not found")?;
+            Ok(json!({ "name": zone.name }))
+        }
+        "zone/getReachableScenes" => {
+            let (zone, typ) = (num("id")?, num("groupID")?);
+            let scenes = fixture
+                .zones
+                .iter()
+                .find(|z| z.id == zone)
+                .and_then(|z| z.scenes.iter().find(|(t, _)| t.clone() as usize == typ))
+                .map(|(_, s)| s.clone())
+                .unwrap_or_default();
+            Ok(json!({ "reachableScenes": scenes }))
+        }
+        "zone/sceneGetName" => {
+            let key = (num("id")?, num("groupID")?, num("sceneNumber")?);
+            Ok(json!({ "name": st.scene_names.get(&key).cloned().unwrap_or_default() }))
+        }
+        "zone/sceneSetName" => {
+            let key = (num("id")?, num("groupID")?, num("sceneNumber")?);
+            st.scene_names.insert(key, get("newName")?);
+            Ok(serde_json::Value::Null)
+        }
+        "zone/getLastCalledScene" => {
+            let key = (num("id")?, num("groupID")? as u8);
+            Ok(json!({ "scene": st.last_called.get(&key).cloned().unwrap_or(0) }))
+        }
+        "zone/callScene" => {
+            let (zone, typ, scene) = (num("id")?, num("groupID")?, num("sceneNumber")?);
+
+            // the step scenes dim the lights of the zone up or down
+            let step: i64 = match (typ, scene) {
+                (1, 11) => -LIGHT_STEP,
+                (1, 12) => LIGHT_STEP,
+                _ => 0,
+            };
+            if step != 0 {
+                for d in fixture
+                    .devices
+                    .iter()
+                    .filter(|d| d.zone_id == zone && d.device_type == DeviceType::Light)
+                {
+                    let value = st.outputs.entry((d.id.clone(), 0)).or_insert(0);
+                    *value = (*value as i64 + step).clamp(0, 255) as u64;
+                }
+            }
+
+            let token = session_token(&st);
+            call_scene(&mut st, zone, typ as u8, scene, &token);
+            state.1.notify_all();
+            Ok(serde_json::Value::Null)
+        }
+        "zone/setValue" => {
+            let (zone, typ, value) = (num("id")?, num("groupID")?, num("value")?);
+            for d in fixture
+                .devices
+                .iter()
+                .filter(|d| d.zone_id == zone && d.typ.clone() as usize == typ)
+            {
+                st.outputs.insert((d.id.clone(), 0), value as u64);
+            }
+            Ok(serde_json::Value::Null)
+        }
+        "zone/getSensorValues" => {
+            let id = num("id")?;
+            let zone = fixture
+                .zones
+                .iter()
+                .find(|z| z.id == id)
+                .ok_or(format!("Could not find zone {}", id))?;
+
+            // every value is delivered as own object with it's time
+            Ok(json!({
+                "id": id,
+                "values": zone.sensors.iter().filter_map(|(typ, value)| {
+                    let key = match typ {
+                        SensorType::TemperatureIndoors => "TemperatureValue",
+                        SensorType::HumidityIndoors => "HumidityValue",
+                        SensorType::BrightnessIndoors => "BrightnessValue",
+                        SensorType::CO2Concentration => "CO2concentrationValue",
+                        _ => return None,
+                    };
+                    let mut object = serde_json::Map::new();
+                    object.insert(key.to_string(), json!(value));
+                    object.insert(format!("{}Time", key), json!("2020-01-01 12:00:00"));
+                    Some(serde_json::Value::Object(object))
+                }).collect::<Vec<_>>()
+            }))
+        }
+        "zone/getTemperatureControlStatus"
+        | "zone/getTemperatureControlConfig"
+        | "zone/getTemperatureControlValues" => {
+            let id = num("id")?;
+            let zone = fixture
+                .zones
+                .iter()
+                .find(|z| z.id == id)
+                .ok_or(format!("Could not find zone {}", id))?;
+
+            // a zone without climate has no control mode
+            if zone.climate.is_empty() {
+                return Ok(json!({ "ControlMode": 0 }));
+            }
+
+            let mode = st.operation_modes.get(&id).cloned().unwrap_or(1);
+            let nominal = |mode: u8| {
+                st.nominal.get(&(id, mode)).cloned().or_else(|| {
+                    zone.climate
+                        .iter()
+                        .find(|(m, _)| *m as u8 == mode)
+                        .map(|(_, v)| *v)
+                })
+            };
+
+            match name {
+                "zone/getTemperatureControlStatus" => Ok(json!({
+                    "ControlMode": 1,
+                    "ControlState": 0,
+                    "OperationMode": mode,
+                    "TemperatureValue": zone.sensors.iter()
+                        .find(|(t, _)| t == &SensorType::TemperatureIndoors)
+                        .map(|(_, v)| v),
+                    "NominalValue": nominal(mode),
+                    "ControlValue": 50.0,
+                })),
+                "zone/getTemperatureControlConfig" => Ok(json!({
+                    "ControlMode": 1,
+                    "ReferenceZone": id,
+                    "CtrlOffset": 0,
+                    "EmergencyValue": 75,
+                    "CtrlKp": 5.0,
+                    "CtrlTs": 1,
+                    "CtrlTi": 240,
+                    "CtrlKd": 0.0,
+                    "CtrlImin": -13.325,
+                    "CtrlImax": 13.325,
+                    "CtrlYmin": 0,
+                    "CtrlYmax": 100,
+                    "CtrlAntiWindUp": true,
+                    "CtrlKeepFloorWarm": false,
+                })),
+                _ => {
+                    let mut values = serde_json::Map::new();
+                    for mode in 0..8 {
+                        if let Some(v) = nominal(mode) {
+                            values.insert(HeatingMode::from(mode).as_key().into(), json!(v));
+                        }
+                    }
+                    Ok(serde_json::Value::Object(values))
+                }
+            }
+        }
+        "zone/setTemperatureControlValues" => {
+            let id = num("id")?;
+            if !fixture
+                .zones
+                .iter()
+                .any(|z| z.id == id && !z.climate.is_empty())
+            {
+                return Err(format!("Zone {} has no temperature control", id));
+            }
+
+            for mode in 0..8 {
+                if let Ok(value) = get(HeatingMode::from(mode).as_key()) {
+                    let value = value
+                        .parse::<f32>()
+                        .map_err(|_| format!("Invalid temperature {}", value))?;
+                    st.nominal.insert((id, mode), value);
+                }
+            }
+            Ok(serde_json::Value::Null)
+        }
+        "device/getSceneMode" => Ok(json!({
+            "sceneID": num("sceneID")?,
+            "dontCare": false,
+            "localPrio": false,
+            "specialMode": false,
+            "flashMode": false,
+            "ledconIndex": 0,
+        })),
+        "device/getSensorValue" => {
+            let (device, index) = (get("dsid")?, num("sensorIndex")?);
+            let value = fixture
+                .devices
+                .iter()
+                .find(|d| d.id == device)
+                .and_then(|d| d.sensors.get(index))
+                .ok_or(format!("Could not find sensor {}", index))?;
+            Ok(json!({ "sensorIndex": index, "sensorValue": value }))
+        }
+        "device/getOutputValue" => {
+            let (device, offset) = (get("dsid")?, num("offset")?);
+            let value = st.outputs.get(&(device, offset)).cloned().unwrap_or(0);
+            Ok(json!({ "offset": offset, "value": value }))
+        }
+        "device/setOutputValue" => {
+            let (device, offset, value) = (get("dsid")?, num("offset")?, num("value")?);
+            st.outputs.insert((device, offset), value as u64);
+            Ok(serde_json::Value::Null)
+        }
+        "event/subscribe" => {
+            let id = get("subscriptionID")?;
+            st.subscriptions
+                .entry(id.clone())
+                .or_default()
+                .push(get("name")?);
+            st.events.entry(id).or_default();
+            Ok(serde_json::Value::Null)
+        }
+        "event/unsubscribe" => {
+            let id = get("subscriptionID")?;
+            st.subscriptions.remove(&id);
+            st.events.remove(&id);
+
+            // the pending requests of the subscription return right away
+            state.1.notify_all();
+            Ok(serde_json::Value::Null)
+        }
+        "event/get" => {
+            let id = get("subscriptionID")?;
+            let timeout = std::time::Duration::from_millis(num("timeout").unwrap_or(0) as u64);
+
+            // wait until an event arrives or the timeout is reached
+            let (mut st, _) = state
+                .1
+                .wait_timeout_while(st, timeout, |st| {
+                    st.events.get(&id).is_some_and(|q| q.is_empty())
+                })
+                .unwrap_or_else(|e| e.into_inner());
+
+            let events: Vec<serde_json::Value> = st
+                .events
+                .get_mut(&id)
+                .ok_or("Invalid subscription id")?
+                .drain(..)
+                .collect();
+            Ok(json!({ "events": events }))
+        }
+        _ => Err(format!("Unknown request {}", name)),
+    }
+}
+
+/// The json representation of a device.
+fn device(d: &FixtureDevice) -> serde_json::Value {
+    json!({
+        "id": d.id,
+        "name": d.name,
+        "zoneID": d.zone_id,
+        "isPresent": true,
+        "outputMode": match d.device_type {
+            DeviceType::Switch => 0,
+            DeviceType::Light => 16,
+            DeviceType::Shadow => 33,
+            DeviceType::Tv => 39,
+            DeviceType::Unknown => 255,
+        },
+        "groups": [d.typ.clone() as u8],
+        "buttonActiveGroup": d.typ.clone() as u8,
+    })
+}
+
+/// The token of the actual session.
+fn session_token(st: &State) -> String {
+    format!("{}-{}", SESSION_TOKEN, st.session)
+}
+
+/// Build the property tree of the dss out of the fixture and the actual state.
+fn property_tree(st: &State) -> serde_json::Value {
+    let mut zones = serde_json::Map::new();
+
+    for z in &st.fixture.zones {
+        let mut groups = serde_json::Map::new();
+        for (typ, _) in &z.scenes {
+            let typ = typ.clone() as u8;
+            let scene = st.last_called.get(&(z.id, typ)).cloned().unwrap_or(0);
+            groups.insert(
+                format!("group{}", typ),
+                json!({ "group": typ, "lastCalledScene": scene }),
+            );
+        }
+
+        let mut devices = serde_json::Map::new();
+        for d in st.fixture.devices.iter().filter(|d| d.zone_id == z.id) {
+            let mut scenes = serde_json::Map::new();
+            for scene in z
+                .scenes
+                .iter()
+                .filter(|(t, _)| t == &d.typ)
+                .flat_map(|(_, s)| s)
+            {
+                scenes.insert(
+                    format!("scene{}", scene),
+                    json!({
+                        "sceneID": scene,
+                        "dontCare": d.dont_care.contains(scene),
+                        "localPrio": false,
+                        "specialMode": false,
+                        "flashMode": false,
+                        "ledconIndex": 0,
+                    }),
+                );
+            }
+
+            let mut outputs = serde_json::Map::new();
+            for ((_, offset), value) in st.outputs.iter().filter(|((id, _), _)| id == &d.id) {
+                outputs.insert(
+                    format!("value{}", offset),
+                    json!({ "offset": offset, "value": value }),
+                );
+            }
+
+            devices.insert(
+                d.id.clone(),
+                json!({ "dSID": d.id, "scenes": scenes, "outputValues": outputs }),
+            );
+        }
+
+        zones.insert(
+            format!("zone{}", z.id),
+            json!({ "ZoneID": z.id, "name": z.name, "groups": groups, "devices": devices }),
+        );
+    }
+
+    let mut tree = json!({ "apartment": { "zones": zones } });
+
+    // add the properties, which got set through the api
+    for (path, value) in &st.properties {
+        let mut node = &mut tree;
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            if !node.is_object() {
+                *node = json!({});
+            }
+            node = &mut node[part];
+        }
+        *node = value.clone();
+    }
+
+    tree
+}
+
+/// Get the node at the path of the property tree.
+fn property<'a>(tree: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('/')
+        .filter(|p| !p.is_empty())
+        .try_fold(tree, |node, part| node.get(part))
+}
+
+/// The type name of a property tree node.
+fn property_type(node: &serde_json::Value) -> &'static str {
+    match node {
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(n) if n.is_f64() => "floating",
+        serde_json::Value::Number(_) => "integer",
+        _ => "none",
+    }
+}
+
+/// Evaluate a property query, like '/apartment/zones/*(ZoneID,name)',
+/// against the property tree.
+fn query(tree: &serde_json::Value, query: &str) -> serde_json::Value {
+    let path: Vec<&str> = query.trim_matches('/').split('/').collect();
+    serde_json::Value::Object(select(tree, &path, ""))
+}
+
+/// Select the nodes of the path, every '*' level is delivered as
+/// array named after the parent node.
+fn select(
+    node: &serde_json::Value,
+    path: &[&str],
+    parent: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut out = serde_json::Map::new();
+
+    let (part, rest) = match path.split_first() {
+        Some(p) => p,
+        None => return out,
+    };
+
+    if let Some(props) = part.strip_prefix('*') {
+        let props: Vec<&str> = props
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        let items = node
+            .as_object()
+            .into_iter()
+            .flat_map(|o| o.values())
+            .filter(|child| child.is_object())
+            .map(|child| {
+                let mut item = select(child, rest, "");
+                for p in &props {
+                    if let Some(v) = child.get(*p) {
+                        item.insert(p.to_string(), v.clone());
+                    }
+                }
+                serde_json::Value::Object(item)
+            })
+            .collect();
+
+        out.insert(parent.to_string(), serde_json::Value::Array(items));
+    } else if let Some(child) = node.get(*part) {
+        out = select(child, rest, part);
+    }
+
+    out
+}
+
+/// Store the scene call and inform the subscribers about it.
+fn call_scene(state: &mut State, zone: usize, typ: u8, scene: usize, token: &str) {
+    state.last_called.insert((zone, typ), scene);
+
+    // the temperature control scenes switch the operation mode
+    if typ == Type::TemperatureControl as u8 && scene < 8 {
+        state.operation_modes.insert(zone, scene as u8);
+    }
+
+    let props = json!({
+        "zoneID": zone.to_string(),
+        "groupID": typ.to_string(),
+        "sceneID": scene.to_string(),
+        "originToken": token,
+        "callOrigin": "2",
+    });
+    let source = json!({
+        "zoneID": zone,
+        "groupID": typ,
+        "isApartment": false,
+        "isGroup": true,
+        "isDevice": false,
+    });
+    push_event(state, "callScene", props, source);
+}
+
+/// Queue the event for every subscription, which listens to it.
+fn push_event(
+    state: &mut State,
+    name: &str,
+    properties: serde_json::Value,
+    source: serde_json::Value,
+) {
+    let event = json!({ "name": name, "properties": properties, "source": source });
+
+    for (id, names) in &state.subscriptions {
+        if names.iter().any(|n| n == name) {
+            if let Some(queue) = state.events.get_mut(id) {
+                queue.push_back(event.clone());
+            }
+        }
+    }
+}
+
+/// Decode a url encoded query parameter.
+fn decode(s: &str) -> String {
+    let mut out = vec![];
+    let mut bytes = s.bytes();
+
+    while let Some(b) = bytes.next() {
+        match b {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                out.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+            }
+            b => out.push(b),
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,315 @@
+use dss::async_api::{Appartement, RawApi};
+use dss::mock::{Fixture, MockServer};
+use dss::{ConnectionState, Event, EventFilter, HeatingMode, Scene, Type, Value};
+use futures::StreamExt;
+
+const DESK: &str = "303505d7f8000000000000400000001";
+
+async fn connect() -> (MockServer, Appartement) {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let appt = Appartement::connect(mock.host(), "dssadmin", "secret")
+        .await
+        .unwrap();
+    (mock, appt)
+}
+
+/// Wait for the next item of the stream, but not forever.
+async fn next<S>(stream: &mut S) -> S::Item
+where
+    S: futures::Stream + Unpin,
+{
+    tokio::time::timeout(std::time::Duration::from_secs(10), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn connect_fetches_the_structure() {
+    let (_mock, appt) = connect().await;
+    let zones = appt.get_zones().await;
+
+    assert_eq!(zones.len(), 2);
+
+    let office = zones.iter().find(|z| z.name == "Office").unwrap();
+    assert_eq!(office.types, vec![Type::Light, Type::Shadow]);
+
+    let living = zones.iter().find(|z| z.name == "Living").unwrap();
+    let heating = living
+        .groups
+        .iter()
+        .find(|g| g.typ == Type::Heating)
+        .unwrap();
+    assert_eq!(heating.status, Value::HeatingMode(HeatingMode::Comfort));
+}
+
+#[tokio::test]
+async fn connect_fails_with_wrong_password() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert!(Appartement::connect(mock.host(), "dssadmin", "wrong")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn set_value_calls_scenes_and_dims_the_lights() {
+    let (mock, appt) = connect().await;
+
+    appt.set_value(1, None, Value::Light(1.0)).await.unwrap();
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(5));
+
+    appt.set_value(1, Some(0), Value::Light(0.5)).await.unwrap();
+    assert_eq!(mock.output_value(DESK, 0), Some(127));
+    assert_eq!(appt.get_value(1, 0).await.unwrap(), Value::Light(0.5));
+}
+
+#[tokio::test]
+async fn light_steps_read_back_the_brightness() {
+    let (mock, appt) = connect().await;
+
+    appt.increase_light(1).await.unwrap();
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(12));
+    assert_eq!(
+        appt.get_value(1, 0).await.unwrap(),
+        Value::Light(25.0 / 255.0)
+    );
+
+    appt.decrease_light(1).await.unwrap();
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(11));
+    assert_eq!(appt.get_value(1, 0).await.unwrap(), Value::Light(0.0));
+}
+
+#[tokio::test]
+async fn set_value_sets_the_climate() {
+    let (_mock, appt) = connect().await;
+
+    appt.set_value(2, None, Value::Temperature(22.5))
+        .await
+        .unwrap();
+    assert_eq!(
+        appt.get_temperature_control(2).await.unwrap().nominal,
+        Some(22.5)
+    );
+
+    appt.set_value(2, None, Value::HeatingMode(HeatingMode::Night))
+        .await
+        .unwrap();
+    let status = appt.get_temperature_control(2).await.unwrap();
+    assert_eq!(status.operation_mode, HeatingMode::Night);
+    assert_eq!(status.nominal, Some(18.0));
+}
+
+#[tokio::test]
+async fn consumption_sums_up_the_circuits() {
+    let (_mock, appt) = connect().await;
+
+    let consumption = appt.consumption().await.unwrap();
+    assert_eq!(consumption.circuits.len(), 2);
+    assert_eq!(consumption.power, Some(200.5));
+    assert_eq!(consumption.energy, Some(7500.0));
+}
+
+#[tokio::test]
+async fn status_stream_receives_the_state_changes() {
+    let (mock, appt) = connect().await;
+    let mut status = Box::pin(appt.status_stream());
+    assert_eq!(next(&mut status).await.state, ConnectionState::Connected);
+
+    // the password got changed
+    mock.set_password("changed");
+    mock.expire_session();
+    assert!(appt.consumption().await.is_err());
+
+    assert_eq!(next(&mut status).await.state, ConnectionState::AuthFailed);
+}
+
+#[tokio::test]
+async fn event_stream_receives_scene_calls() {
+    let (mock, appt) = connect().await;
+    let mut events = Box::pin(appt.event_stream().await.unwrap());
+
+    mock.call_scene(2, Type::Light, 6);
+
+    match next(&mut events).await.unwrap() {
+        Event::CallScene(e) => {
+            assert_eq!(e.zone, 2);
+            assert_eq!(e.value, Value::Light(1.0));
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    assert_eq!(appt.get_value(2, 1).await.unwrap(), Value::Light(1.0));
+}
+
+#[tokio::test]
+async fn dropped_event_streams_are_unsubscribed() {
+    let (mock, appt) = connect().await;
+    let events = appt.event_stream().await.unwrap();
+    assert_eq!(mock.subscriptions(), 1);
+
+    drop(events);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+    while mock.subscriptions() > 0 {
+        assert!(std::time::Instant::now() < deadline);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn event_streams_share_one_subscription() {
+    let (mock, appt) = connect().await;
+    // the streams are served in order, so the small one is done when the others got the event
+    let small = appt
+        .event_stream_filtered(EventFilter::all(), 1)
+        .await
+        .unwrap();
+    let mut all = appt.event_stream().await.unwrap();
+    let mut office = appt
+        .event_stream_filtered(EventFilter::zone(1).typ(Type::Light), 16)
+        .await
+        .unwrap();
+    assert_eq!(mock.subscriptions(), 1);
+
+    mock.call_scene(2, Type::Light, 6);
+    mock.call_scene(1, Type::Light, 5);
+
+    assert_eq!(next(&mut all).await.unwrap().zone(), Some(2));
+    assert_eq!(next(&mut all).await.unwrap().zone(), Some(1));
+    assert_eq!(next(&mut office).await.unwrap().zone(), Some(1));
+    assert_eq!(small.dropped(), 1);
+}
+
+#[tokio::test]
+async fn handlers_are_called_with_the_matching_events() {
+    let (mock, appt) = connect().await;
+    let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+
+    let _subscription = appt
+        .on(EventFilter::zone(2), move |event| {
+            let _ = send.send(event.clone());
+        })
+        .await
+        .unwrap();
+
+    mock.call_scene(1, Type::Light, 5);
+    mock.call_scene(2, Type::Light, 6);
+
+    let mut events = Box::pin(futures::stream::unfold(recv, |mut recv| async move {
+        recv.recv().await.map(|e| (e, recv))
+    }));
+    match next(&mut events).await {
+        Event::CallScene(e) => assert_eq!(e.zone, 2),
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn panicking_handlers_keep_the_task_running() {
+    let (mock, appt) = connect().await;
+    let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+
+    let subscription = appt
+        .on(EventFilter::all(), move |event| {
+            let _ = send.send(event.clone());
+            if let Event::CallScene(e) = event {
+                assert_ne!(e.zone, 1, "handler failed");
+            }
+        })
+        .await
+        .unwrap();
+
+    mock.call_scene(1, Type::Light, 5);
+    mock.call_scene(2, Type::Light, 6);
+
+    let mut events = Box::pin(futures::stream::unfold(recv, |mut recv| async move {
+        recv.recv().await.map(|e| (e, recv))
+    }));
+    next(&mut events).await;
+    match next(&mut events).await {
+        Event::CallScene(e) => assert_eq!(e.zone, 2),
+        e => panic!("Unexpected event {:?}", e),
+    }
+    assert_eq!(subscription.panics(), 1);
+}
+
+#[tokio::test]
+async fn property_tree_getters_and_setters() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        api.get_property_string("/apartment/zones/zone1/name")
+            .await
+            .unwrap(),
+        "Office"
+    );
+    assert!(api
+        .get_property_boolean("/apartment/zones/zone1/name")
+        .await
+        .is_err());
+
+    api.set_property_integer("/config/level", 3).await.unwrap();
+    api.set_property_boolean("/config/enabled", true)
+        .await
+        .unwrap();
+
+    assert_eq!(api.get_property_integer("/config/level").await.unwrap(), 3);
+    assert!(api.get_property_boolean("/config/enabled").await.unwrap());
+
+    let mut children: Vec<String> = api
+        .get_property_children("/config")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    children.sort();
+    assert_eq!(children, vec!["enabled", "level"]);
+}
+
+#[tokio::test]
+async fn device_sensor_values_are_requested_by_index() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret")
+        .await
+        .unwrap();
+
+    assert_eq!(api.get_device_sensor_value(DESK, 0).await.unwrap(), 12.5);
+    assert!(api.get_device_sensor_value(DESK, 1).await.is_err());
+}
+
+#[tokio::test]
+async fn scenes_are_called_by_preset_name_or_number() {
+    let (mock, appt) = connect().await;
+
+    appt.call_scene(2, Type::Light, Scene::Preset2)
+        .await
+        .unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(17));
+
+    appt.set_scene_name(2, Type::Light, Scene::Area1On, "Reading")
+        .await
+        .unwrap();
+    assert_eq!(
+        appt.find_scene(2, Type::Light, "reading").await.unwrap(),
+        Scene::Area1On
+    );
+    appt.call_scene_by_name(2, Type::Light, "Preset 1")
+        .await
+        .unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(5));
+    assert!(matches!(
+        appt.call_scene_by_name(2, Type::Light, "Party").await,
+        Err(dss::Error::UnknownScene(_))
+    ));
+
+    // the apartment scenes are called for the zone 0
+    appt.call_scene_by_number(2, Type::Light, 72).await.unwrap();
+    assert_eq!(mock.last_called_scene(0, Type::Light), Some(72));
+    appt.call_scene_by_number(2, Type::Light, 16).await.unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(16));
+}
@@ -0,0 +1,424 @@
+#![cfg(feature = "cli")]
+
+use dss::mock::{Fixture, MockServer};
+use dss::Type;
+
+fn dss(mock: &MockServer, args: &[&str]) -> std::process::Output {
+    command(mock, args).output().unwrap()
+}
+
+/// The cli command with the login of the mock.
+fn command(mock: &MockServer, args: &[&str]) -> std::process::Command {
+    let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_dss"));
+    cmd.args([
+        "--host",
+        mock.host(),
+        "--user",
+        "dssadmin",
+        "--password",
+        "secret",
+    ])
+    .args(args)
+    .env_remove("DSS_HOST")
+    .env_remove("DSS_USER")
+    .env_remove("DSS_PASSWORD")
+    .env_remove("DSS_TOKEN")
+    .env_remove("DSS_CONFIG")
+    .env_remove("DSS_PROFILE")
+    .env("XDG_CONFIG_HOME", "/nonexistent");
+    cmd
+}
+
+/// Run the cli with only a config file to login.
+fn dss_config(name: &str, config: &str, args: &[&str]) -> std::process::Output {
+    let file = std::env::temp_dir().join(format!("dss-cli-{}-{}.toml", std::process::id(), name));
+    std::fs::write(&file, config).unwrap();
+
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_dss"))
+        .args(["--config", file.to_str().unwrap()])
+        .args(args)
+        .env_remove("DSS_HOST")
+        .env_remove("DSS_USER")
+        .env_remove("DSS_PASSWORD")
+        .env_remove("DSS_TOKEN")
+        .env_remove("DSS_PROFILE")
+        .env("DSS_CLI_TEST_PASSWORD", "secret")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&file);
+    out
+}
+
+#[test]
+fn zones_are_printed() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["zones"]);
+
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Office"));
+}
+
+#[test]
+fn light_and_scene_commands_call_scenes() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert!(dss(&mock, &["light", "office", "on"]).status.success());
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(5));
+
+    assert!(dss(&mock, &["scene", "2", "light", "17"]).status.success());
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(17));
+    assert!(dss(&mock, &["scene", "2", "light", "16"]).status.success());
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(16));
+
+    assert!(dss(&mock, &["scene", "office", "light", "preset", "3"])
+        .status
+        .success());
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(18));
+    assert_eq!(
+        dss(&mock, &["scene", "office", "light", "party"])
+            .status
+            .code(),
+        Some(4)
+    );
+}
+
+#[test]
+fn shadow_command_moves_the_devices() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert!(dss(&mock, &["shadow", "office", "0.5", "0.2"])
+        .status
+        .success());
+    assert_eq!(
+        mock.output_value("303505d7f8000000000000400000002", 2),
+        Some(32767)
+    );
+}
+
+#[test]
+fn failures_have_distinct_exit_codes() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert_eq!(dss(&mock, &["dance"]).status.code(), Some(2));
+    assert_eq!(
+        dss(&mock, &["light", "office", "2.0"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(dss(&mock, &["light", "9", "0.5"]).status.code(), Some(4));
+    assert_eq!(
+        dss(&mock, &["--password", "wrong", "zones"]).status.code(),
+        Some(3)
+    );
+}
+
+#[test]
+fn commands_are_not_taken_as_login() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["office", "zone", "office"]);
+
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Unknown command office"));
+}
+
+#[test]
+fn login_is_read_from_the_config_file() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let config = format!(
+        "host = \"{}\"\nuser = \"dssadmin\"\npassword = \"secret\"\n",
+        mock.host()
+    );
+    let out = dss_config("plain", &config, &["zone", "living"]);
+
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Living"));
+}
+
+#[test]
+fn profiles_are_selected_by_flag_or_default() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let config = format!(
+        "default_profile = \"home\"\n\
+         [profiles.home]\nhost = \"{0}\"\napplication_token = \"mock-application-token\"\n\
+         [profiles.office]\nhost = \"{0}\"\nuser = \"dssadmin\"\npassword_env = \"DSS_CLI_TEST_PASSWORD\"\n\
+         [profiles.broken]\nhost = \"{0}\"\nuser = \"dssadmin\"\npassword_command = \"echo wrong\"\n",
+        mock.host()
+    );
+
+    assert!(dss_config("profiles", &config, &["zones"]).status.success());
+    assert!(
+        dss_config("profiles", &config, &["--profile", "office", "zones"])
+            .status
+            .success()
+    );
+    assert_eq!(
+        dss_config("profiles", &config, &["-p", "broken", "zones"])
+            .status
+            .code(),
+        Some(3)
+    );
+    assert_eq!(
+        dss_config("profiles", &config, &["--profile", "missing", "zones"])
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn token_is_requested_and_saved_in_the_profile() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let file = std::env::temp_dir().join(format!("dss-cli-{}-token.toml", std::process::id()));
+    let config = format!(
+        "[profiles.home]\nhost = \"{}\"\nuser = \"dssadmin\"\npassword_env = \"DSS_CLI_TEST_PASSWORD\"\n",
+        mock.host()
+    );
+    std::fs::write(&file, config).unwrap();
+
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_dss"))
+            .args(["--config", file.to_str().unwrap(), "-p", "home"])
+            .args(args)
+            .env_remove("DSS_HOST")
+            .env_remove("DSS_USER")
+            .env_remove("DSS_PASSWORD")
+            .env_remove("DSS_TOKEN")
+            .env_remove("DSS_PROFILE")
+            .env("DSS_CLI_TEST_PASSWORD", "secret")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .unwrap()
+    };
+    assert!(run(&["token", "tests"]).status.success());
+    let saved = std::fs::read_to_string(&file).unwrap();
+    assert!(saved.contains("application_token = \"mock-requested-token-1\""));
+
+    // the profile logs in with the token afterwards
+    let before = mock.requests().len();
+    let out = run(&["zones"]);
+    let _ = std::fs::remove_file(&file);
+
+    assert!(out.status.success());
+    assert!(mock.requests()[before..].contains(&"system/loginApplication".to_string()));
+}
+
+#[test]
+fn missing_password_is_not_read_from_a_pipe() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let config = format!("host = \"{}\"\nuser = \"dssadmin\"\n", mock.host());
+
+    assert_eq!(
+        dss_config("nopassword", &config, &["zones"]).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn structure_is_cached_in_the_profile_file() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let cache = std::env::temp_dir().join(format!("dss-cli-{}-cache.json", std::process::id()));
+    let config = format!(
+        "[profiles.home]\nhost = \"{}\"\napplication_token = \"mock-application-token\"\ncache = \"{}\"\n",
+        mock.host(),
+        cache.display()
+    );
+
+    // the first connect fetches the structure, afterwards it's read from the cache
+    let out = dss_config("cache", &config, &["-p", "home", "zones"]);
+    assert!(out.status.success());
+    let cached = std::fs::read_to_string(&cache).unwrap();
+    assert!(cached.contains("Office"));
+
+    std::fs::write(&cache, cached.replace("Office", "Stale")).unwrap();
+    let out = dss_config("cache", &config, &["-p", "home", "zones"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Stale"));
+
+    // the update replaces the stale cache
+    let out = dss_config("cache", &config, &["-p", "home", "update"]);
+    let cached = std::fs::read_to_string(&cache);
+    let _ = std::fs::remove_file(&cache);
+
+    assert!(out.status.success());
+    let cached = cached.unwrap();
+    assert!(cached.contains("Office"));
+    assert!(!cached.contains("Stale"));
+}
+
+#[test]
+fn login_is_read_from_the_environment() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_dss"))
+        .arg("zones")
+        .env("DSS_HOST", mock.host())
+        .env("DSS_TOKEN", "mock-application-token")
+        .env_remove("DSS_CONFIG")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .output()
+        .unwrap();
+
+    assert!(out.status.success());
+}
+
+#[test]
+fn zones_are_printed_as_json() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["--output", "json", "zones"]);
+    assert!(out.status.success());
+
+    let zones: Vec<dss::Zone> = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(zones.len(), 2);
+
+    let out = dss(&mock, &["zone", "office", "-o", "json"]);
+    let zone: dss::Zone = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(zone.name, "Office");
+}
+
+#[test]
+fn zones_are_printed_as_table() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["--output=table", "zones"]);
+    assert!(out.status.success());
+
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    let lines: Vec<&str> = out.lines().collect();
+    assert!(lines[0].starts_with("ZONE  NAME"));
+
+    // every column starts at the same position
+    let status = lines[0].find("STATUS").unwrap();
+    let row = lines.iter().find(|l| l.contains("Shadow")).unwrap();
+    assert_eq!(row.find("closed"), Some(status));
+}
+
+#[test]
+fn unknown_output_is_rejected() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert_eq!(dss(&mock, &["-o", "xml", "zones"]).status.code(), Some(2));
+}
+
+#[test]
+fn options_after_the_command_are_only_taken_by_watch() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["zones", "--bogus"]);
+
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Unknown option --bogus"));
+}
+
+#[test]
+fn watch_prints_the_filtered_events_with_time() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let child = command(
+        &mock,
+        &[
+            "-o",
+            "json",
+            "watch",
+            "--zone",
+            "office",
+            "--action",
+            "all_light_off",
+            "--count",
+            "1",
+            "--timeout=10s",
+        ],
+    )
+    .stdout(std::process::Stdio::piped())
+    .spawn()
+    .unwrap();
+
+    // wait until the events are polled
+    let start = std::time::Instant::now();
+    while !mock.requests().iter().any(|r| r == "event/get") {
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    mock.call_scene(2, Type::Light, 5);
+    mock.call_scene(1, Type::Light, 0);
+
+    let out = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert!(out.status.success());
+    assert_eq!(lines.len(), 1);
+
+    let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(event["name"], "callScene");
+    assert_eq!(event["properties"]["zoneID"], 1);
+    assert!(event["time"].as_str().unwrap().ends_with('Z'));
+}
+
+#[test]
+fn watch_exits_after_the_timeout() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let out = dss(&mock, &["watch", "--type", "shadow", "--timeout", "200ms"]);
+
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+    assert_eq!(
+        dss(&mock, &["watch", "--color", "red"]).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn shell_executes_the_lines_and_keeps_the_history() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let state = std::env::temp_dir().join(format!("dss-cli-{}-state", std::process::id()));
+
+    let mut child = command(&mock, &[])
+        .env("XDG_STATE_HOME", &state)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(
+        child.stdin.as_mut().unwrap(),
+        b"office light on\nlight on 2\nlight nowhere on\nhelp shadow\nhelp watch\nwatch\nexit\n",
+    )
+    .unwrap();
+
+    let out = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let history = std::fs::read_to_string(state.join("dss").join("history"));
+    let _ = std::fs::remove_dir_all(&state);
+
+    assert!(out.status.success());
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(5));
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(5));
+    assert!(stdout.contains("No matching room found for nowhere"));
+    assert!(stdout.contains("Usage: shadow <zone> <open> <angle> [group]"));
+    assert!(stdout.contains("-n, --count <n>"));
+    assert!(stdout.contains("The watch needs a --count or --timeout in the shell"));
+    assert!(history.unwrap().contains("light on 2"));
+}
+
+#[test]
+fn shell_takes_quoted_zone_names() {
+    let mut fixture = Fixture::default();
+    fixture.zones[1].name = "Living Room".into();
+    let mock = MockServer::start(fixture).unwrap();
+    let state = std::env::temp_dir().join(format!("dss-cli-{}-quoted", std::process::id()));
+
+    let mut child = command(&mock, &[])
+        .env("XDG_STATE_HOME", &state)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(
+        child.stdin.as_mut().unwrap(),
+        b"\"Living Room\" light on\nexit\n",
+    )
+    .unwrap();
+
+    let out = child.wait_with_output().unwrap();
+    let history = std::fs::read_to_string(state.join("dss").join("history"));
+    let _ = std::fs::remove_dir_all(&state);
+
+    assert!(out.status.success());
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(5));
+    assert!(history.unwrap().contains("\"Living Room\" light on"));
+}
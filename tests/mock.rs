@@ -0,0 +1,1298 @@
+use dss::mock::{Fixture, MockServer};
+use dss::{
+    Appartement, Backoff, ConnectionState, Event, EventFilter, HeatingMode, MeteringType,
+    MeteringUnit, RawApi, Scene, Type, Value,
+};
+
+const DESK: &str = "303505d7f8000000000000400000001";
+const BLIND: &str = "303505d7f8000000000000400000002";
+const CEILING: &str = "303505d7f8000000000000400000003";
+const FLOOR_LAMP: &str = "303505d7f8000000000000400000004";
+
+fn connect() -> (MockServer, Appartement) {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let appt = Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+    (mock, appt)
+}
+
+/// Wait until the subscription at the dss is closed, but not forever.
+fn wait_unsubscribed(mock: &MockServer) {
+    let start = std::time::Instant::now();
+    while mock.subscriptions() > 0 {
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn connect_fetches_the_structure() {
+    let (_mock, appt) = connect();
+    let zones = appt.get_zones().unwrap();
+
+    assert_eq!(zones.len(), 2);
+
+    let office = zones.iter().find(|z| z.name == "Office").unwrap();
+    assert_eq!(office.types, vec![Type::Light, Type::Shadow]);
+
+    let light = office.groups.iter().find(|g| g.typ == Type::Light).unwrap();
+    assert_eq!(light.devices.len(), 1);
+    assert_eq!(light.devices[0].id, DESK);
+
+    let shadow = office
+        .groups
+        .iter()
+        .find(|g| g.typ == Type::Shadow)
+        .unwrap();
+    assert_eq!(shadow.devices[0].id, BLIND);
+}
+
+#[test]
+fn connect_sorts_devices_into_scene_groups() {
+    let (mock, appt) = connect();
+    let zones = appt.get_zones().unwrap();
+    let living = zones.iter().find(|z| z.name == "Living").unwrap();
+
+    let devices = |id: usize| -> Vec<String> {
+        let group = living.groups.iter().find(|g| g.id == id).unwrap();
+        group.devices.iter().map(|d| d.id.clone()).collect()
+    };
+    assert_eq!(devices(1), vec![CEILING]);
+    assert_eq!(devices(2), vec![FLOOR_LAMP]);
+
+    // the structure is fetched in bulk, not per device
+    let requests = mock.requests();
+    assert!(!requests.contains(&"device/getSceneMode".to_string()));
+    assert!(!requests.contains(&"zone/getReachableScenes".to_string()));
+}
+
+#[test]
+fn connect_fails_with_wrong_password() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert!(Appartement::connect(mock.host(), "dssadmin", "wrong").is_err());
+}
+
+#[test]
+fn connect_with_application_token() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let appt = Appartement::connect_token(mock.host(), "mock-application-token").unwrap();
+
+    assert_eq!(appt.get_zones().unwrap().len(), 2);
+    assert!(mock
+        .requests()
+        .contains(&"system/loginApplication".to_string()));
+}
+
+#[test]
+fn requested_application_tokens_are_enabled_and_login() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    let token =
+        RawApi::request_application_token(mock.host(), "dssadmin", "secret", "tests").unwrap();
+    assert_eq!(token, "mock-requested-token-1");
+
+    let api = RawApi::connect_token(mock.host(), &token).unwrap();
+    assert_eq!(api.application_token(), Some(token.as_str()));
+    assert_eq!(api.get_zone_name(1).unwrap(), "Office");
+
+    let requests = mock.requests();
+    let position = |name: &str| requests.iter().position(|r| r == name).unwrap();
+    assert!(position("system/requestApplicationToken") < position("system/enableToken"));
+    assert!(position("system/enableToken") < position("system/loginApplication"));
+}
+
+#[test]
+fn builder_connects_with_a_base_url() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::builder(format!("{}/dss/", mock.host()))
+        .user("dssadmin", "secret")
+        .timeout(std::time::Duration::from_secs(10))
+        .header("X-Proxy", "dss")
+        .connect()
+        .unwrap();
+
+    let appt = Appartement::from_api(api).unwrap();
+    assert_eq!(appt.get_zones().unwrap().len(), 2);
+
+    // the headers are sent with the login and every following request
+    for request in ["system/login", "apartment/getStructure"] {
+        let headers = mock.headers(request);
+        assert!(
+            headers
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case("x-proxy") && v == "dss"),
+            "No header in {}: {:?}",
+            request,
+            headers
+        );
+    }
+}
+
+#[test]
+fn builder_needs_credentials() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    assert!(RawApi::builder(mock.host()).connect().is_err());
+}
+
+#[test]
+fn set_value_calls_light_scenes() {
+    let (mock, appt) = connect();
+
+    appt.set_value(1, None, Value::Light(1.0)).unwrap();
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(5));
+
+    appt.set_value(1, None, Value::Light(0.0)).unwrap();
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(0));
+}
+
+#[test]
+fn set_value_dims_the_light_devices() {
+    let (mock, appt) = connect();
+
+    appt.set_value(1, Some(0), Value::Light(0.5)).unwrap();
+
+    assert_eq!(mock.output_value(DESK, 0), Some(127));
+    assert_eq!(appt.get_value(1, 0).unwrap(), Value::Light(0.5));
+}
+
+#[test]
+fn set_value_moves_the_shadow_devices() {
+    let (mock, appt) = connect();
+
+    appt.set_value(1, Some(0), Value::Shadow(0.5, 0.5)).unwrap();
+
+    assert_eq!(mock.output_value(BLIND, 2), Some(32767));
+    assert_eq!(mock.output_value(BLIND, 4), Some(127));
+}
+
+#[test]
+fn set_value_dims_all_lights_of_the_zone() {
+    let (mock, appt) = connect();
+
+    appt.set_value(2, None, Value::Light(0.5)).unwrap();
+
+    assert_eq!(mock.output_value(CEILING, 0), Some(127));
+    assert_eq!(mock.output_value(FLOOR_LAMP, 0), Some(127));
+    assert_eq!(appt.get_value(2, 1).unwrap(), Value::Light(0.5));
+    assert_eq!(appt.get_value(2, 2).unwrap(), Value::Light(0.5));
+}
+
+#[test]
+fn increase_light_calls_the_step_scene() {
+    let (mock, appt) = connect();
+
+    appt.increase_light(1).unwrap();
+
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(12));
+    assert_eq!(mock.output_value(DESK, 0), Some(25));
+
+    // the brightness after the step is read back from the device
+    assert_eq!(appt.get_value(1, 0).unwrap(), Value::Light(25.0 / 255.0));
+}
+
+#[test]
+fn decrease_light_calls_the_step_scene() {
+    let (mock, appt) = connect();
+    appt.set_value(1, Some(0), Value::Light(0.5)).unwrap();
+
+    appt.decrease_light(1).unwrap();
+
+    assert_eq!(mock.last_called_scene(1, Type::Light), Some(11));
+    assert_eq!(mock.output_value(DESK, 0), Some(102));
+    assert_eq!(appt.get_value(1, 0).unwrap(), Value::Light(102.0 / 255.0));
+}
+
+#[test]
+fn light_steps_fail_for_unknown_zones() {
+    let (_mock, appt) = connect();
+
+    match appt.increase_light(9) {
+        Err(dss::Error::UnknownZone(9)) => {}
+        res => panic!("Unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn event_channel_receives_scene_calls() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.call_scene(2, Type::Light, 6);
+
+    let event = events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap();
+    match event {
+        Event::CallScene(e) => {
+            assert_eq!(e.zone, 2);
+            assert_eq!(e.typ, Type::Light);
+            assert_eq!(e.value, Value::Light(1.0));
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    assert_eq!(appt.get_value(2, 1).unwrap(), Value::Light(1.0));
+}
+
+/// Push an event to the mock and receive it from a raw event channel.
+fn receive_event(name: &str, properties: serde_json::Value, source: serde_json::Value) -> Event {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let mut api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let (events, _status) = api
+        .new_event_channel_with(&["buttonClick", "deviceBinaryInputEvent", "stateChange", name])
+        .unwrap();
+
+    mock.push_event(name, properties, source);
+
+    events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn button_click_events_are_parsed() {
+    let event = receive_event(
+        "buttonClick",
+        serde_json::json!({ "buttonIndex": "2", "clickType": "1" }),
+        serde_json::json!({ "zoneID": 1, "dsid": DESK, "isDevice": true }),
+    );
+
+    match event {
+        Event::ButtonClick(e) => {
+            assert_eq!(e.zone, 1);
+            assert_eq!(e.device, DESK);
+            assert_eq!(e.button_index, 2);
+            assert_eq!(e.click_type, 1);
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn binary_input_events_are_parsed() {
+    let event = receive_event(
+        "deviceBinaryInputEvent",
+        serde_json::json!({ "inputIndex": "0", "inputType": "13", "inputState": "1" }),
+        serde_json::json!({ "zoneID": 2, "dsid": CEILING, "isDevice": true }),
+    );
+
+    match event {
+        Event::DeviceBinaryInput(e) => {
+            assert_eq!(e.zone, 2);
+            assert_eq!(e.device, CEILING);
+            assert_eq!(e.index, 0);
+            assert_eq!(e.input_type, 13);
+            assert_eq!(e.state, 1);
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn state_change_events_are_parsed() {
+    let event = receive_event(
+        "stateChange",
+        serde_json::json!({
+            "statename": "presence",
+            "state": "absent",
+            "value": "2",
+            "oldvalue": "1",
+        }),
+        serde_json::json!({ "isApartment": true }),
+    );
+
+    match event {
+        Event::StateChange(e) => {
+            assert_eq!(e.name, "presence");
+            assert_eq!(e.state, "absent");
+            assert_eq!(e.value, "2");
+            assert_eq!(e.old_value, "1");
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn unknown_events_are_delivered_as_other() {
+    let event = receive_event(
+        "highlevelevent",
+        serde_json::json!({ "id": "1234" }),
+        serde_json::json!({ "isApartment": true }),
+    );
+
+    match event {
+        Event::Other(name, json) => {
+            assert_eq!(name, "highlevelevent");
+            assert_eq!(json["properties"]["id"], "1234");
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn running_and_model_ready_are_subscribed_by_default() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.push_event("model_ready", serde_json::json!({}), serde_json::json!({}));
+
+    match events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap()
+    {
+        Event::ModelReady => {}
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn event_channel_subscribes_again_after_a_restart() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.restart();
+
+    // the lost subscription is reported, before it's restored
+    let timeout = std::time::Duration::from_secs(10);
+    assert!(events.recv_timeout(timeout).unwrap().is_err());
+    match events.recv_timeout(timeout).unwrap().unwrap() {
+        Event::Reconnected => {}
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    mock.call_scene(2, Type::Light, 6);
+    match events.recv_timeout(timeout).unwrap().unwrap() {
+        Event::CallScene(e) => assert_eq!(e.zone, 2),
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn event_channel_waits_after_failures_without_subscribing_again() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::builder(mock.host())
+        .user("dssadmin", "secret")
+        .backoff(Backoff {
+            retries: 0,
+            initial: std::time::Duration::from_millis(100),
+            max: std::time::Duration::from_secs(1),
+        })
+        .connect()
+        .unwrap();
+    let appt = Appartement::from_api(api).unwrap();
+    let events = appt.event_channel().unwrap();
+    let subscribed = mock.requests().len();
+
+    // the failures are reported with a growing delay in between
+    mock.fail_requests(2);
+    let timeout = std::time::Duration::from_secs(10);
+    assert!(events.recv_timeout(timeout).unwrap().is_err());
+    let first = std::time::Instant::now();
+    assert!(events.recv_timeout(timeout).unwrap().is_err());
+    assert!(first.elapsed() >= std::time::Duration::from_millis(100));
+
+    // the subscription wasn't lost, so the events just continue
+    mock.call_scene(2, Type::Light, 6);
+    match events.recv_timeout(timeout).unwrap().unwrap() {
+        Event::CallScene(e) => assert_eq!(e.zone, 2),
+        e => panic!("Unexpected event {:?}", e),
+    }
+    assert!(!mock.requests()[subscribed..]
+        .iter()
+        .any(|r| r == "event/subscribe"));
+}
+
+#[test]
+fn event_channel_reports_failed_events_and_continues() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    // the shadow groups of an unknown zone can't be found
+    mock.call_scene(9, Type::Shadow, 43);
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    match events.recv_timeout(timeout).unwrap() {
+        Err(dss::Error::EventApply(e)) => match *e {
+            dss::Error::UnknownZone(9) => {}
+            ref e => panic!("Unexpected error {:?}", e),
+        },
+        res => panic!("Unexpected result {:?}", res),
+    }
+    match events.recv_timeout(timeout).unwrap().unwrap() {
+        Event::CallScene(e) => assert_eq!(e.zone, 2),
+        e => panic!("Unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn invalid_structure_files_are_an_error() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let file = std::env::temp_dir().join(format!("dss-cache-invalid-{}.json", std::process::id()));
+    std::fs::write(&file, "{ no zones").unwrap();
+
+    let res = Appartement::connect_file(mock.host(), "dssadmin", "secret", file.to_str().unwrap());
+    std::fs::remove_file(&file).unwrap();
+
+    assert!(matches!(res, Err(dss::Error::Json(_))));
+}
+
+#[test]
+fn event_channel_reports_a_failing_structure_file_once() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let file = std::env::temp_dir().join(format!("dss-cache-fails-{}.json", std::process::id()));
+    let appt = Appartement::connect_file(mock.host(), "dssadmin", "secret", file.to_str().unwrap())
+        .unwrap();
+    let events = appt.event_channel().unwrap();
+
+    // a directory in place of the file makes every write fail
+    std::fs::remove_file(&file).unwrap();
+    std::fs::create_dir(&file).unwrap();
+
+    mock.call_scene(1, Type::Light, 5);
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    let results: Vec<_> = (0..3)
+        .map(|_| events.recv_timeout(timeout).unwrap())
+        .collect();
+    std::fs::remove_dir(&file).unwrap();
+
+    assert!(matches!(results[0], Ok(Event::CallScene(ref e)) if e.zone == 1));
+    match results[1] {
+        Err(dss::Error::EventApply(ref e)) => assert!(matches!(**e, dss::Error::Io(_))),
+        ref res => panic!("Unexpected result {:?}", res),
+    }
+    assert!(matches!(results[2], Ok(Event::CallScene(ref e)) if e.zone == 2));
+    assert!(events
+        .recv_timeout(std::time::Duration::from_millis(200))
+        .is_err());
+}
+
+#[test]
+fn event_channel_closes_after_a_failed_login() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.set_password("changed");
+    mock.restart();
+
+    let timeout = std::time::Duration::from_secs(10);
+    let mut last = None;
+    loop {
+        match events.recv_timeout(timeout) {
+            Ok(res) => last = Some(res),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(e) => panic!("Channel not closed: {:?}", e),
+        }
+    }
+
+    match last {
+        Some(Err(dss::Error::Event(e))) => match *e {
+            dss::Error::Authentication(_) => {}
+            ref e => panic!("Unexpected error {:?}", e),
+        },
+        res => panic!("Unexpected result {:?}", res),
+    }
+
+    // the failure is kept, even when it didn't fit into the channel
+    match events.error() {
+        Some(dss::Error::Event(e)) => match *e {
+            dss::Error::Authentication(_) => {}
+            ref e => panic!("Unexpected error {:?}", e),
+        },
+        res => panic!("Unexpected error {:?}", res),
+    }
+}
+
+#[test]
+fn event_channels_share_one_subscription() {
+    let (mock, appt) = connect();
+    let all = appt.event_channel().unwrap();
+    let office = appt
+        .event_channel_filtered(EventFilter::zone(1).typ(Type::Light), 16)
+        .unwrap();
+    assert_eq!(mock.subscriptions(), 1);
+
+    mock.call_scene(2, Type::Light, 6);
+    mock.call_scene(1, Type::Light, 5);
+
+    let timeout = std::time::Duration::from_secs(10);
+    assert_eq!(all.recv_timeout(timeout).unwrap().unwrap().zone(), Some(2));
+    assert_eq!(all.recv_timeout(timeout).unwrap().unwrap().zone(), Some(1));
+    assert_eq!(
+        office.recv_timeout(timeout).unwrap().unwrap().zone(),
+        Some(1)
+    );
+    assert!(office.try_recv().is_err());
+}
+
+#[test]
+fn appartements_use_their_own_subscriptions() {
+    let (mock, first) = connect();
+    let second = Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    let first = first.event_channel().unwrap();
+    let second = second.event_channel().unwrap();
+    assert_eq!(mock.subscriptions(), 2);
+
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    assert!(first.recv_timeout(timeout).unwrap().is_ok());
+    assert!(second.recv_timeout(timeout).unwrap().is_ok());
+}
+
+#[test]
+fn full_event_channels_drop_the_events() {
+    let (mock, appt) = connect();
+    // the channels are served in order, so the small one is done when all got the event
+    let small = appt
+        .event_channel_filtered(EventFilter::all().name("callScene"), 1)
+        .unwrap();
+    let all = appt.event_channel().unwrap();
+
+    for scene in [5, 6, 7].iter() {
+        mock.call_scene(2, Type::Light, *scene);
+    }
+
+    let timeout = std::time::Duration::from_secs(10);
+    for _ in 0..3 {
+        all.recv_timeout(timeout).unwrap().unwrap();
+    }
+    assert!(small.try_recv().unwrap().is_ok());
+    assert!(small.try_recv().is_err());
+    assert_eq!(small.dropped(), 2);
+    assert_eq!(all.dropped(), 0);
+}
+
+#[test]
+fn dropped_event_channels_are_unsubscribed() {
+    let (mock, appt) = connect();
+    let first = appt.event_channel().unwrap();
+    let second = appt.event_channel().unwrap();
+
+    drop(first);
+    mock.call_scene(2, Type::Light, 6);
+    assert!(second
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .is_ok());
+    assert_eq!(mock.subscriptions(), 1);
+
+    // the subscription ends right away, not with the next answer of the dss
+    drop(second);
+    wait_unsubscribed(&mock);
+}
+
+#[test]
+fn handlers_are_called_with_the_matching_events() {
+    let (mock, appt) = connect();
+    let (send, recv) = std::sync::mpsc::channel();
+    let _subscription = appt
+        .on(EventFilter::zone(2).typ(Type::Light), move |event| {
+            let _ = send.send(event.clone());
+        })
+        .unwrap();
+
+    mock.call_scene(1, Type::Light, 5);
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    match recv.recv_timeout(timeout).unwrap() {
+        Event::CallScene(e) => assert_eq!((e.zone, e.typ), (2, Type::Light)),
+        e => panic!("Unexpected event {:?}", e),
+    }
+    assert!(recv.try_recv().is_err());
+}
+
+#[test]
+fn dropped_subscriptions_unregister_the_handlers() {
+    let (mock, appt) = connect();
+    let (send, recv) = std::sync::mpsc::channel();
+    let first = {
+        let send = send.clone();
+        appt.on(EventFilter::all(), move |_| {
+            let _ = send.send("first");
+        })
+        .unwrap()
+    };
+    let second = appt
+        .on(EventFilter::all(), move |_| {
+            let _ = send.send("second");
+        })
+        .unwrap();
+    assert_eq!(mock.subscriptions(), 1);
+
+    drop(first);
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    assert_eq!(recv.recv_timeout(timeout).unwrap(), "second");
+    assert!(recv.try_recv().is_err());
+
+    // the subscription at the dss ends right away
+    drop(second);
+    wait_unsubscribed(&mock);
+}
+
+#[test]
+fn panicking_handlers_are_counted_and_called_again() {
+    let (mock, appt) = connect();
+    let (send, recv) = std::sync::mpsc::channel();
+    let subscription = appt
+        .on(EventFilter::all(), move |event| {
+            let _ = send.send(event.clone());
+            if let Event::CallScene(e) = event {
+                assert_ne!(e.zone, 1, "handler failed");
+            }
+        })
+        .unwrap();
+
+    mock.call_scene(1, Type::Light, 5);
+    mock.call_scene(2, Type::Light, 6);
+
+    let timeout = std::time::Duration::from_secs(10);
+    assert!(recv.recv_timeout(timeout).is_ok());
+    assert!(recv.recv_timeout(timeout).is_ok());
+    assert_eq!(subscription.panics(), 1);
+    assert!(subscription.error().is_none());
+}
+
+#[test]
+fn scenes_are_called_by_preset_or_name() {
+    let (mock, appt) = connect();
+
+    appt.call_scene(2, Type::Light, Scene::Preset2).unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(17));
+
+    appt.set_scene_name(2, Type::Light, Scene::Area1On, "Reading")
+        .unwrap();
+    assert_eq!(
+        appt.get_scene_name(2, Type::Light, Scene::Area1On).unwrap(),
+        "Reading"
+    );
+    assert_eq!(
+        appt.get_scene_name(2, Type::Light, Scene::Preset0).unwrap(),
+        ""
+    );
+
+    appt.call_scene_by_name(2, Type::Light, "reading").unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(6));
+
+    appt.call_scene_by_name(2, Type::Light, "Preset 1").unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(5));
+
+    assert!(matches!(
+        appt.call_scene_by_name(2, Type::Light, "Party"),
+        Err(dss::Error::UnknownScene(_))
+    ));
+    assert!(appt.call_scene(2, Type::Light, Scene::Unknown).is_err());
+}
+
+#[test]
+fn apartment_scenes_and_numbers_are_called() {
+    let (mock, appt) = connect();
+
+    appt.call_scene(2, Type::Light, Scene::Absent).unwrap();
+    assert_eq!(mock.last_called_scene(0, Type::Light), Some(72));
+    assert_ne!(mock.last_called_scene(2, Type::Light), Some(72));
+
+    // the numbers without a scene are sent as they are
+    appt.call_scene_by_number(2, Type::Light, 16).unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(16));
+    appt.call_scene_by_number(2, Type::Light, 17).unwrap();
+    assert_eq!(mock.last_called_scene(2, Type::Light), Some(17));
+}
+
+#[test]
+fn property_tree_getters_and_setters() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    assert_eq!(
+        api.get_property_string("/apartment/zones/zone1/name")
+            .unwrap(),
+        "Office"
+    );
+    assert_eq!(
+        api.get_property_integer("/apartment/zones/zone2/ZoneID")
+            .unwrap(),
+        2
+    );
+    assert!(api
+        .get_property_boolean("/apartment/zones/zone1/name")
+        .is_err());
+
+    api.set_property_string("/config/name", "Home").unwrap();
+    api.set_property_integer("/config/level", 3).unwrap();
+    api.set_property_boolean("/config/enabled", true).unwrap();
+
+    assert_eq!(api.get_property_string("/config/name").unwrap(), "Home");
+    assert_eq!(api.get_property_integer("/config/level").unwrap(), 3);
+    assert!(api.get_property_boolean("/config/enabled").unwrap());
+
+    let mut children: Vec<(String, String)> = api
+        .get_property_children("/config")
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.name, c.typ))
+        .collect();
+    children.sort();
+    assert_eq!(
+        children,
+        vec![
+            ("enabled".to_string(), "boolean".to_string()),
+            ("level".to_string(), "integer".to_string()),
+            ("name".to_string(), "string".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn property_query_deserializes_into_structs() {
+    #[derive(serde::Deserialize)]
+    struct Zones {
+        zones: Vec<ZoneName>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct ZoneName {
+        #[serde(rename = "ZoneID")]
+        id: usize,
+        name: String,
+    }
+
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    let res: Zones = api
+        .property_query_as("/apartment/zones/*(ZoneID,name)")
+        .unwrap();
+    let mut zones: Vec<(usize, String)> = res.zones.into_iter().map(|z| (z.id, z.name)).collect();
+    zones.sort();
+
+    assert_eq!(zones, vec![(1, "Office".into()), (2, "Living".into())]);
+}
+
+#[test]
+fn connect_reads_the_zone_sensor_values() {
+    let (_mock, appt) = connect();
+    let zones = appt.get_zones().unwrap();
+
+    let office = zones.iter().find(|z| z.id == 1).unwrap();
+    let temperature = office.sensor(dss::SensorType::TemperatureIndoors).unwrap();
+    assert_eq!(temperature.value, 21.5);
+    assert_eq!(temperature.time, "2020-01-01 12:00:00");
+    assert_eq!(
+        office
+            .sensor(dss::SensorType::HumidityIndoors)
+            .unwrap()
+            .value,
+        45.0
+    );
+    assert!(office.sensor(dss::SensorType::CO2Concentration).is_none());
+
+    let living = zones.iter().find(|z| z.id == 2).unwrap();
+    assert!(living.sensors.is_empty());
+}
+
+#[test]
+fn zones_without_sensor_support_have_no_sensor_values() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    mock.reject_zone_request("zone/getSensorValues", 1);
+
+    let appt = Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let zones = appt.update_all().unwrap();
+
+    let office = zones.iter().find(|z| z.id == 1).unwrap();
+    assert!(office.sensors.is_empty());
+    assert!(office.groups.iter().any(|g| g.typ == Type::Shadow));
+}
+
+#[test]
+fn zones_rejecting_the_climate_have_no_climate_groups() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    mock.reject_zone_request("zone/getTemperatureControlStatus", 2);
+
+    let appt = Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let zones = appt.update_all().unwrap();
+
+    let living = zones.iter().find(|z| z.id == 2).unwrap();
+    assert!(!living
+        .groups
+        .iter()
+        .any(|g| g.typ == Type::Heating || g.typ == Type::TemperatureControl));
+    assert!(living.groups.iter().any(|g| g.typ == Type::Light));
+}
+
+#[test]
+fn zone_sensor_events_update_the_sensor_values() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.push_event(
+        "zoneSensorValue",
+        serde_json::json!({
+            "sensorType": "13",
+            "sensorValueFloat": "52.5",
+            "zoneID": "1",
+            "groupID": "0",
+            "originDSUID": DESK,
+        }),
+        serde_json::json!({ "zoneID": 1, "isApartment": false, "isGroup": true }),
+    );
+
+    let event = events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap();
+    match event {
+        Event::ZoneSensorValue(e) => {
+            assert_eq!(e.zone, 1);
+            assert_eq!(e.device, DESK);
+            assert_eq!(e.sensor.typ, dss::SensorType::HumidityIndoors);
+            assert_eq!(e.sensor.value, 52.5);
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    // the other sensor values of the zone are kept
+    let zones = appt.get_zones().unwrap();
+    let office = zones.iter().find(|z| z.id == 1).unwrap();
+    assert_eq!(
+        office
+            .sensor(dss::SensorType::HumidityIndoors)
+            .unwrap()
+            .value,
+        52.5
+    );
+    assert_eq!(
+        office
+            .sensor(dss::SensorType::TemperatureIndoors)
+            .unwrap()
+            .value,
+        21.5
+    );
+}
+
+#[test]
+fn device_sensor_values_are_requested_by_index() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    assert_eq!(api.get_device_sensor_value(DESK, 0).unwrap(), 12.5);
+    assert!(api.get_device_sensor_value(DESK, 1).is_err());
+}
+
+#[test]
+fn zone_sensor_values_are_requested_per_zone() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    assert_eq!(api.get_zone_sensor_values(2).unwrap(), vec![]);
+    assert!(api.get_zone_sensor_values(9).is_err());
+}
+
+#[test]
+fn connect_reads_the_shadow_values_in_bulk() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    api.set_shadow_device_open(BLIND, 0.5).unwrap();
+    api.set_shadow_device_angle(BLIND, 0.5).unwrap();
+
+    let outputs = api.get_zone_output_values(1).unwrap();
+    let blind = outputs.iter().find(|o| o.device == BLIND).unwrap();
+    assert_eq!(blind.value(2), Some(32767));
+
+    let appt = Appartement::from_api(api).unwrap();
+    assert!(!mock.requests().iter().any(|r| r == "device/getOutputValue"));
+
+    let zones = appt.get_zones().unwrap();
+    let office = zones.iter().find(|z| z.id == 1).unwrap();
+    let shadow = office.groups.iter().find(|g| g.typ == Type::Shadow);
+    match &shadow.unwrap().status {
+        Value::Shadow(open, _) => assert!((*open - 0.5).abs() < 0.01),
+        v => panic!("Unexpected value {:?}", v),
+    }
+}
+
+#[test]
+fn consumption_sums_up_the_circuits() {
+    let (_mock, appt) = connect();
+    let consumption = appt.consumption().unwrap();
+
+    assert_eq!(consumption.circuits.len(), 2);
+    assert_eq!(consumption.circuits[0].circuit.name, "Ground floor");
+    assert_eq!(consumption.circuits[0].power, Some(120.0));
+    assert_eq!(consumption.circuits[1].energy, Some(2500.0));
+    assert_eq!(consumption.power, Some(200.5));
+    assert_eq!(consumption.energy, Some(7500.0));
+}
+
+#[test]
+fn consumption_of_circuits_without_meter_values_is_unknown() {
+    let mut fixture = Fixture::default();
+    fixture.circuits[1].power = None;
+    let mock = MockServer::start(fixture).unwrap();
+    let appt = Appartement::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let consumption = appt.consumption().unwrap();
+
+    assert_eq!(consumption.circuits[0].power, Some(120.0));
+    assert_eq!(consumption.circuits[1].power, None);
+    assert_eq!(consumption.power, None);
+    assert_eq!(consumption.energy, Some(7500.0));
+}
+
+#[test]
+fn meter_values_of_the_circuits() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let circuit = "302ed89f43f0000000000f0000000001";
+
+    let latest = api
+        .get_latest_meter_values(MeteringType::Consumption, MeteringUnit::W)
+        .unwrap();
+    assert_eq!(latest.len(), 2);
+    assert_eq!(latest[0].id, circuit);
+    assert_eq!(latest[0].value, 120.0);
+
+    let samples = api
+        .get_meter_values(circuit, MeteringType::Energy, MeteringUnit::Wh, 60, Some(2))
+        .unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[1].timestamp - samples[0].timestamp, 60);
+    assert_eq!(samples[0].value, 5000.0);
+
+    assert_eq!(api.get_circuit_consumption(circuit).unwrap(), 120.0);
+    assert_eq!(api.get_circuit_energy_meter_value(circuit).unwrap(), 5000.0);
+}
+
+/// The status of the group with the type in group 0 of the zone.
+fn status_of(appt: &Appartement, zone: usize, typ: Type) -> Value {
+    appt.get_zones()
+        .unwrap()
+        .into_iter()
+        .find(|z| z.id == zone)
+        .unwrap()
+        .groups
+        .into_iter()
+        .find(|g| g.id == 0 && g.typ == typ)
+        .unwrap()
+        .status
+}
+
+#[test]
+fn connect_reads_the_temperature_control() {
+    let (_mock, appt) = connect();
+
+    assert_eq!(
+        status_of(&appt, 2, Type::TemperatureControl),
+        Value::Temperature(21.0)
+    );
+    assert_eq!(
+        status_of(&appt, 2, Type::Heating),
+        Value::HeatingMode(HeatingMode::Comfort)
+    );
+
+    // the office climate isn't controlled
+    let zones = appt.get_zones().unwrap();
+    let office = zones.iter().find(|z| z.id == 1).unwrap();
+    assert!(!office
+        .groups
+        .iter()
+        .any(|g| g.typ == Type::TemperatureControl || g.typ == Type::Heating));
+}
+
+#[test]
+fn temperature_control_status_and_config() {
+    let (mock, appt) = connect();
+
+    let status = appt.get_temperature_control(2).unwrap();
+    assert_eq!(status.control_mode, 1);
+    assert_eq!(status.operation_mode, HeatingMode::Comfort);
+    assert_eq!(status.nominal, Some(21.0));
+    assert_eq!(appt.get_temperature_control(1).unwrap().control_mode, 0);
+
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let config = api.get_temperature_control_config(2).unwrap();
+    assert_eq!(config.control_mode, 1);
+    assert_eq!(config.reference_zone, Some(2));
+
+    let values = api.get_temperature_control_values(2).unwrap();
+    assert_eq!(values.comfort, Some(21.0));
+    assert_eq!(values.night, Some(18.0));
+    assert_eq!(values.cooling, None);
+}
+
+#[test]
+fn set_value_sets_the_nominal_temperature() {
+    let (mock, appt) = connect();
+
+    appt.set_value(2, None, Value::Temperature(22.5)).unwrap();
+
+    // the temperature is set for the actual operation mode
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    let values = api.get_temperature_control_values(2).unwrap();
+    assert_eq!(values.comfort, Some(22.5));
+    assert_eq!(values.economy, Some(20.0));
+
+    assert_eq!(
+        status_of(&appt, 2, Type::TemperatureControl),
+        Value::Temperature(22.5)
+    );
+}
+
+#[test]
+fn set_value_switches_the_heating_mode() {
+    let (mock, appt) = connect();
+
+    appt.set_value(2, None, Value::HeatingMode(HeatingMode::Night))
+        .unwrap();
+
+    assert_eq!(
+        mock.last_called_scene(2, Type::TemperatureControl),
+        Some(HeatingMode::Night as usize)
+    );
+    assert_eq!(
+        status_of(&appt, 2, Type::Heating),
+        Value::HeatingMode(HeatingMode::Night)
+    );
+    assert_eq!(
+        status_of(&appt, 2, Type::TemperatureControl),
+        Value::Temperature(18.0)
+    );
+}
+
+#[test]
+fn heating_mode_events_update_the_climate() {
+    let (mock, appt) = connect();
+    let events = appt.event_channel().unwrap();
+
+    mock.call_scene(2, Type::TemperatureControl, HeatingMode::Economy as usize);
+
+    match events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap()
+    {
+        Event::CallScene(e) => {
+            assert_eq!(e.typ, Type::TemperatureControl);
+            assert_eq!(e.value, Value::Temperature(20.0));
+        }
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    assert_eq!(
+        status_of(&appt, 2, Type::Heating),
+        Value::HeatingMode(HeatingMode::Economy)
+    );
+    assert_eq!(
+        status_of(&appt, 2, Type::TemperatureControl),
+        Value::Temperature(20.0)
+    );
+}
+
+#[test]
+fn temperature_of_uncontrolled_zones_is_rejected() {
+    let (_mock, appt) = connect();
+
+    match appt.set_value(1, None, Value::Temperature(22.0)) {
+        Err(dss::Error::Rejected(_)) => {}
+        res => panic!("Unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn errors_describe_the_failure() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+
+    match Appartement::connect(mock.host(), "dssadmin", "wrong") {
+        Err(dss::Error::Authentication(_)) => (),
+        r => panic!("Unexpected result {:?}", r.map(|_| ())),
+    }
+
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+    match api.get_light_device_value("unknown") {
+        Err(dss::Error::UnknownDevice(device)) => assert_eq!(device, "unknown"),
+        r => panic!("Unexpected result {:?}", r),
+    }
+
+    // a known device isn't reported as unknown, when the request is rejected
+    match api.generic_request("device/getOutputValue", Some(vec![("dsid", DESK)]), 2) {
+        Err(dss::Error::Rejected(msg)) => assert!(msg.contains("offset")),
+        r => panic!("Unexpected result {:?}", r),
+    }
+    assert!(!mock
+        .requests()
+        .contains(&"apartment/getDevices".to_string()));
+
+    let appt = Appartement::from_api(api).unwrap();
+    assert!(matches!(
+        appt.get_value(9, 0),
+        Err(dss::Error::UnknownZone(9))
+    ));
+    assert!(matches!(
+        appt.get_value(1, 9),
+        Err(dss::Error::UnknownGroup { zone: 1, group: 9 })
+    ));
+}
+
+fn logins(mock: &MockServer) -> usize {
+    mock.requests()
+        .iter()
+        .filter(|r| r.as_str() == "system/login")
+        .count()
+}
+
+#[test]
+fn expired_session_is_renewed_and_the_request_replayed() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    mock.expire_session();
+
+    assert_eq!(api.get_zone_name(1).unwrap(), "Office");
+    assert_eq!(logins(&mock), 2);
+}
+
+#[test]
+fn expired_session_is_renewed_once_for_all_threads() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    mock.expire_session();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let api = api.clone();
+            std::thread::spawn(move || api.get_zone_name(2))
+        })
+        .collect();
+
+    for t in threads {
+        assert_eq!(t.join().unwrap().unwrap(), "Living");
+    }
+    assert_eq!(logins(&mock), 2);
+}
+
+#[test]
+fn rejected_requests_dont_login_again() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::connect(mock.host(), "dssadmin", "secret").unwrap();
+
+    assert!(api.get_light_device_value("unknown").is_err());
+    assert_eq!(logins(&mock), 1);
+}
+
+#[test]
+fn transient_failures_are_retried_with_backoff() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let backoff = Backoff {
+        retries: 2,
+        initial: std::time::Duration::from_millis(10),
+        max: std::time::Duration::from_millis(20),
+    };
+    let api = RawApi::builder(mock.host())
+        .user("dssadmin", "secret")
+        .backoff(backoff)
+        .connect()
+        .unwrap();
+
+    mock.fail_requests(2);
+    assert_eq!(api.get_zone_name(1).unwrap(), "Office");
+
+    mock.fail_requests(3);
+    match api.get_zone_name(1) {
+        Err(e) => assert!(e.is_transient()),
+        r => panic!("Unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn changing_requests_are_not_retried() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let api = RawApi::builder(mock.host())
+        .user("dssadmin", "secret")
+        .backoff(Backoff {
+            retries: 2,
+            initial: std::time::Duration::from_millis(10),
+            max: std::time::Duration::from_millis(20),
+        })
+        .connect()
+        .unwrap();
+
+    let before = mock.last_called_scene(1, Type::Light);
+    mock.fail_requests(1);
+    match api.call_scene(1, Type::Light, 5) {
+        Err(e) => assert!(e.is_transient()),
+        r => panic!("Unexpected result {:?}", r),
+    }
+    let calls = mock
+        .requests()
+        .iter()
+        .filter(|r| *r == "zone/callScene")
+        .count();
+    assert_eq!(calls, 1);
+    assert_eq!(mock.last_called_scene(1, Type::Light), before);
+}
+
+#[test]
+fn status_tells_the_last_request_and_event() {
+    let (mock, appt) = connect();
+    let status = appt.status();
+    assert_eq!(status.state, ConnectionState::Connected);
+    assert!(status.last_request.is_some());
+    assert_eq!(status.last_event, None);
+
+    let events = appt.event_channel().unwrap();
+    mock.call_scene(2, Type::Light, 6);
+    events
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap()
+        .unwrap();
+
+    assert!(appt.status().last_event.is_some());
+}
+
+#[test]
+fn status_channel_receives_the_state_changes() {
+    let mock = MockServer::start(Fixture::default()).unwrap();
+    let backoff = Backoff {
+        retries: 2,
+        initial: std::time::Duration::from_millis(10),
+        max: std::time::Duration::from_millis(20),
+    };
+    let api = RawApi::builder(mock.host())
+        .user("dssadmin", "secret")
+        .backoff(backoff)
+        .connect()
+        .unwrap();
+    let status = api.status_channel();
+
+    // the dss becomes unreachable and comes back
+    mock.fail_requests(3);
+    assert!(api.get_zone_name(1).is_err());
+    assert_eq!(api.status().state, ConnectionState::Disconnected);
+    assert_eq!(api.get_zone_name(1).unwrap(), "Office");
+
+    // the password got changed
+    mock.set_password("changed");
+    mock.expire_session();
+    assert!(api.get_zone_name(1).is_err());
+
+    let states: Vec<ConnectionState> = status.try_iter().map(|s| s.state).collect();
+    assert_eq!(
+        states,
+        vec![
+            ConnectionState::Connected,
+            ConnectionState::Reconnecting,
+            ConnectionState::Disconnected,
+            ConnectionState::Connected,
+            ConnectionState::AuthFailed,
+        ]
+    );
+}
@@ -0,0 +1,86 @@
+use dss::mock::{Fixture, MockServer};
+use dss::{Appartement, RawApi, Tls};
+
+const CERTIFICATE: &[u8] = include_bytes!("certs/dss.pem");
+const PRIVATE_KEY: &[u8] = include_bytes!("certs/dss.key");
+const FINGERPRINT: &str = concat!(
+    "6F:5B:DA:FA:DA:3E:CE:D7:69:18:B2:F1:54:D1:74:68:",
+    "61:3B:EE:6A:F9:B3:72:AE:26:A6:1B:AB:7B:9A:C8:59"
+);
+
+fn start() -> MockServer {
+    MockServer::start_https(Fixture::default(), CERTIFICATE, PRIVATE_KEY).unwrap()
+}
+
+fn connect(mock: &MockServer, tls: Tls) -> Result<RawApi, dss::Error> {
+    RawApi::builder(mock.host())
+        .user("dssadmin", "secret")
+        .tls(tls)
+        .connect()
+}
+
+#[test]
+fn pinned_certificate_is_accepted() {
+    let mock = start();
+
+    assert!(connect(&mock, Tls::Certificate(CERTIFICATE.to_vec())).is_ok());
+}
+
+#[test]
+fn pinned_fingerprint_is_accepted() {
+    let mock = start();
+
+    assert!(connect(&mock, Tls::Fingerprint(FINGERPRINT.into())).is_ok());
+    assert!(connect(
+        &mock,
+        Tls::Fingerprint(FINGERPRINT.replace(':', "").to_lowercase())
+    )
+    .is_ok());
+}
+
+#[test]
+fn wrong_fingerprint_is_rejected() {
+    let mock = start();
+    let wrong = FINGERPRINT.replace("6F", "00");
+
+    assert!(connect(&mock, Tls::Fingerprint(wrong)).is_err());
+    assert!(connect(&mock, Tls::Fingerprint("no fingerprint".into())).is_err());
+}
+
+#[test]
+fn invalid_certificate_is_rejected_by_default_roots() {
+    let mock = start();
+
+    match connect(&mock, Tls::Roots) {
+        Err(e) => assert!(!e.is_transient()),
+        Ok(_) => panic!("The certificate should be rejected"),
+    }
+    assert!(connect(&mock, Tls::Insecure).is_ok());
+}
+
+#[test]
+fn trust_on_first_use_stores_the_fingerprint() {
+    let mock = start();
+    let file = std::env::temp_dir().join(format!("dss-tofu-{}.fingerprint", std::process::id()));
+    let _ = std::fs::remove_file(&file);
+    let path = file.to_str().unwrap().to_string();
+
+    // the first connection stores the fingerprint
+    assert!(connect(&mock, Tls::TrustOnFirstUse(path.clone())).is_ok());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), FINGERPRINT);
+
+    // a changed certificate is rejected afterwards
+    std::fs::write(&file, FINGERPRINT.replace("6F", "00")).unwrap();
+    assert!(connect(&mock, Tls::TrustOnFirstUse(path)).is_err());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn default_connections_verify_against_the_roots() {
+    let mock = start();
+
+    // the self signed certificate is only accepted, when it's pinned
+    assert!(RawApi::connect(mock.host(), "dssadmin", "secret").is_err());
+    assert!(Appartement::connect_token(mock.host(), "mock-application-token").is_err());
+}